@@ -0,0 +1,40 @@
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::{scratch_directory, synthetic_database};
+use criterion::{criterion_group, criterion_main, Criterion};
+use mix::Database;
+use std::fs;
+
+/// How many packages the database knows about.
+const PACKAGE_COUNT: usize = 50_000;
+
+fn large_database(c: &mut Criterion) {
+    let directory = scratch_directory("bench-database");
+    let database = synthetic_database(&directory, PACKAGE_COUNT);
+    let path = directory.join("mix.db");
+    let mut group = c.benchmark_group("database of 50,000 packages");
+    group.sample_size(10);
+    group.bench_function("save then load", |b| {
+        b.iter(|| {
+            database.save(&path).unwrap();
+            Database::load(&path).unwrap()
+        })
+    });
+    group.bench_function("all_packages", |b| b.iter(|| database.all_packages()));
+    group.bench_function("look up by name", |b| {
+        b.iter(|| database.info("package-25000").unwrap())
+    });
+    let names: Vec<String> = (0..PACKAGE_COUNT)
+        .step_by(50)
+        .map(|index| format!("package-{}", index))
+        .collect();
+    group.bench_function("look up 1,000 names", |b| {
+        b.iter(|| mix::selection::packages_from_names(&names, &database).unwrap())
+    });
+    group.finish();
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+criterion_group!(benches, large_database);
+criterion_main!(benches);
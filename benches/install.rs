@@ -0,0 +1,92 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use mix::{Config, Database, Package, Selections, SharedPackage};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+use xz2::write::XzEncoder;
+
+/// How many files the large package contains.
+const FILE_COUNT: usize = 32;
+/// The size of each file of the large package.
+const FILE_SIZE: usize = 1024 * 1024;
+
+/// Write a package tarball with many files, its manifest first.
+fn make_large_package(directory: &Path) -> PathBuf {
+    let path = directory.join("large.tar.xz");
+    let mut builder = tar::Builder::new(XzEncoder::new(File::create(&path).unwrap(), 6));
+    let mut append = |path: &str, entry_type, contents: &[u8]| {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(0o644);
+        header.set_size(contents.len() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, path, contents).unwrap();
+    };
+    append(".MANIFEST", tar::EntryType::Regular, b"name = \"large\"");
+    append("data", tar::EntryType::Directory, b"");
+    for index in 0..FILE_COUNT {
+        // Text-like contents, so decompressing is real work.
+        let contents: Vec<u8> = format!("{} ", index)
+            .bytes()
+            .chain((0..FILE_SIZE as u32).flat_map(|n| n.wrapping_mul(2654435761).to_le_bytes()))
+            .take(FILE_SIZE)
+            .map(|byte| b'a' + byte % 16)
+            .collect();
+        append(
+            &format!("data/{}", index),
+            tar::EntryType::Regular,
+            &contents,
+        );
+    }
+    builder.into_inner().unwrap().finish().unwrap();
+    path
+}
+
+/// Provide a database installing into a fresh root inside the directory.
+fn empty_database(directory: &Path) -> Database {
+    let _ = fs::remove_dir_all(directory.join("root"));
+    let _ = fs::remove_dir_all(directory.join("cache"));
+    fs::create_dir(directory.join("root")).unwrap();
+    let mut database = Database::new_empty(directory.join("cache"));
+    database.set_config(Config {
+        root: directory.join("root"),
+        ..Config::default()
+    });
+    database
+}
+
+fn install_large_package(c: &mut Criterion) {
+    let directory = std::env::temp_dir().join(format!("mix-bench-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let tarball = make_large_package(&directory);
+    let mut group = c.benchmark_group("install large package");
+    group.sample_size(10);
+    group.bench_function("from_tarball then install", |b| {
+        b.iter_batched(
+            || empty_database(&directory),
+            |mut database| {
+                let mut package = Package::from_tarball(File::open(&tarball).unwrap()).unwrap();
+                package.local_path = Some(tarball.clone());
+                let mut selections = Selections::default();
+                selections.install.push(SharedPackage::new(package));
+                database.apply(selections).unwrap();
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.bench_function("install_tarball", |b| {
+        b.iter_batched(
+            || empty_database(&directory),
+            |mut database| {
+                mix::package::install_tarball(&tarball, &mut database).unwrap();
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+criterion_group!(benches, install_large_package);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+use crate::Error;
+use std::{
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The name of the external package builder, looked up on the `PATH`.
+const MIXPKG: &str = "mixpkg";
+
+/// Build the PKGBUILD in the given directory with mixpkg, providing the path of
+/// the produced tarball. The tarball can be loaded with
+/// [Package::from_tarball](crate::Package::from_tarball) and installed like
+/// any other package.
+/// # Errors
+/// If mixpkg fails, the error contains everything mixpkg wrote to stderr.
+pub fn build_package(pkgbuild_dir: &Path) -> crate::Result<PathBuf> {
+    let package = pkgbuild_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let srcdir = pkgbuild_dir.join("src");
+    let pkgdir = pkgbuild_dir.join("pkg");
+    create_dir_all(&srcdir)?;
+    create_dir_all(&pkgdir)?;
+    let output = Command::new(MIXPKG)
+        .arg("compile")
+        .arg(format!("-p{}", pkgbuild_dir.join("PKGBUILD").display()))
+        .arg(format!("-s{}", srcdir.display()))
+        .arg(format!("-d{}", pkgdir.display()))
+        .current_dir(pkgbuild_dir)
+        .output()?;
+    let log = String::from_utf8_lossy(&output.stderr).into_owned();
+    if !output.status.success() {
+        return Err(Error::BuildFailed { package, log });
+    }
+    // mixpkg reports the tarball location as the last thing it does.
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .rev()
+        .find_map(|line| line.split_once(" package in "))
+        .map(|(_, path)| PathBuf::from(path))
+        .ok_or(Error::BuildFailed { package, log })
+}
@@ -1,8 +1,25 @@
 use anyhow::{anyhow, Context, Result};
 use indicatif::*;
-use mix::{Database, Error as MixError, Selections};
-use std::{path::PathBuf, process};
-use structopt::StructOpt;
+use mix::{
+    history,
+    net::MirrorOrder,
+    package::{format_optional_dependency, FileDiscrepancy},
+    util::format_bytes,
+    Config, Database, Error as MixError, InstallState, Package, Recovery, Selections,
+    SharedPackage, Version,
+};
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use std::{
+    fs,
+    io::{self, BufWriter, IsTerminal, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::atomic::Ordering,
+};
+use structopt::{clap::Shell, StructOpt};
 
 #[derive(Debug, StructOpt)]
 #[structopt()]
@@ -11,26 +28,223 @@ struct Options {
     #[structopt(short = "C", long, default_value = "mix.conf", parse(from_os_str))]
     configuration: PathBuf,
 
-    /// The file containing the package database.
-    #[structopt(long, default_value = ".mix.db", parse(from_os_str))]
+    /// The file containing the package database. Defaults to the database
+    /// configuration option, then $MIX_DB, then /var/lib/mix/mix.db.
+    #[structopt(long = "database", parse(from_os_str))]
+    database_flag: Option<PathBuf>,
+
+    /// Where downloaded packages are stored prior to installing. Defaults to the
+    /// package_cache configuration option, then $MIX_CACHE, then /var/lib/mix/cache/.
+    #[structopt(long = "package-cache", parse(from_os_str))]
+    package_cache_flag: Option<PathBuf>,
+
+    /// The database file, resolved from the flag and the configuration.
+    #[structopt(skip)]
     database: PathBuf,
 
-    #[structopt(long, default_value = ".mix.cache/", parse(from_os_str))]
-    /// Where downloaded packages are stored prior to installing.
+    /// The package cache, resolved from the flag and the configuration.
+    #[structopt(skip)]
     package_cache: PathBuf,
 
+    /// Do not ask for confirmation or show warnings that would need attention.
+    #[structopt(long)]
+    noconfirm: bool,
+
+    /// Print the changes the command would make, one per line as
+    /// "<action> <name> <version>" separated by tabs, without making them.
+    /// Commands that don't select packages to change, such as sync or clean,
+    /// refuse it.
+    #[structopt(long)]
+    print: bool,
+
+    /// The order to try repository mirrors in: ordered or random.
+    #[structopt(long)]
+    mirror: Option<MirrorOrder>,
+
+    /// Only print errors and the output of queries.
+    #[structopt(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print more details, such as the versions of the packages being changed.
+    #[structopt(short, long)]
+    verbose: bool,
+
+    /// When to color the output: always, never, or auto to color it only on
+    /// a terminal when NO_COLOR isn't set.
+    #[structopt(long, default_value = "auto")]
+    color: ColorChoice,
+
     #[structopt(subcommand)]
     command: SubCommands,
 }
 
+/// How much the command line prints about what it's doing.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum Verbosity {
+    /// Only errors and the output of queries.
+    Quiet,
+    /// Progress messages and summaries.
+    Normal,
+    /// Everything in Normal, with more detail.
+    Verbose,
+}
+
+/// When the output is colored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ColorChoice {
+    /// Always color the output.
+    Always,
+    /// Never color the output.
+    Never,
+    /// Color the output on a terminal, unless NO_COLOR is set.
+    Auto,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(choice: &str) -> Result<Self, String> {
+        match choice {
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            "auto" => Ok(Self::Auto),
+            other => Err(format!(
+                "unknown color choice {}, expected always, never, or auto",
+                other
+            )),
+        }
+    }
+}
+
+/// How packages are listed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ListFormat {
+    /// Aligned columns for reading.
+    Table,
+    /// One JSON object per package per line, for other tools.
+    JsonLines,
+}
+
+impl FromStr for ListFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, String> {
+        match format {
+            "table" => Ok(Self::Table),
+            "jsonl" => Ok(Self::JsonLines),
+            other => Err(format!(
+                "unknown list format {}, expected table or jsonl",
+                other
+            )),
+        }
+    }
+}
+
+/// A package as listed in the jsonl format.
+#[derive(Serialize)]
+struct ListEntry<'a> {
+    name: &'a str,
+    version: &'a Version,
+    state: &'a InstallState,
+    description: Option<&'a str>,
+    withdrawn: bool,
+}
+
+/// Colors the parts of the output that benefit from it. Without color, text
+/// is passed through exactly as it is.
+#[derive(Clone, Copy, Debug)]
+struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    /// Color text describing a package change by the kind of change.
+    fn action(self, action: &str, text: &str) -> String {
+        if !self.enabled {
+            return text.to_owned();
+        }
+        match action {
+            "install" => text.green().to_string(),
+            "remove" => text.red().to_string(),
+            "upgrade" | "downgrade" | "repair" => text.yellow().to_string(),
+            _ => text.to_owned(),
+        }
+    }
+
+    /// Color the install state of a package.
+    fn state(self, state: &InstallState) -> String {
+        match state {
+            InstallState::Manual | InstallState::Dependency if self.enabled => {
+                state.green().to_string()
+            }
+            _ => state.to_string(),
+        }
+    }
+}
+
+impl Options {
+    /// Provide the colors to use, as chosen by --color.
+    fn palette(&self) -> Palette {
+        let enabled = match self.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+        };
+        Palette { enabled }
+    }
+
+    /// Provide the verbosity requested by the flags.
+    fn verbosity(&self) -> Verbosity {
+        if self.quiet {
+            Verbosity::Quiet
+        } else if self.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+}
+
 #[derive(Debug, StructOpt)]
 enum SubCommands {
     /// Install the given packages.
     #[structopt(alias = "in")]
     Install {
         #[structopt()]
-        /// The packages to install.
+        /// The packages to install, by name, by the path or http(s) URL of a
+        /// package tarball, or - to read a package tarball from stdin (along
+        /// with --noconfirm, as stdin can't also answer the prompt).
         targets: Vec<String>,
+        #[structopt(long, parse(from_os_str))]
+        /// Also install the packages listed in the file, one name per line.
+        /// Blank lines and comments starting with # are ignored.
+        from_file: Option<PathBuf>,
+        #[structopt(long)]
+        /// Only download the packages needed for the install, without installing them.
+        download_only: bool,
+        #[structopt(long)]
+        /// The expected SHA-256 checksum of the tarball from a URL or stdin target.
+        sha256: Option<String>,
+        #[structopt(long, conflicts_with = "asexplicit")]
+        /// Install the targets as dependencies, so they can be removed as orphans.
+        asdeps: bool,
+        #[structopt(long)]
+        /// Install the targets explicitly. This is the default.
+        asexplicit: bool,
+        #[structopt(long)]
+        /// Skip the tarball, URL and stdin targets that are already installed
+        /// at the same version. Targets named by package are skipped once
+        /// they're installed with or without this flag.
+        needed: bool,
+        #[structopt(long)]
+        /// Remove installed packages that conflict with the targets instead of failing.
+        replace_conflicting: bool,
+        #[structopt(long)]
+        /// Install only the given tarballs, resolving dependencies among them,
+        /// without loading or saving the package database.
+        no_database: bool,
     },
     /// Remove the given packages.
     #[structopt(alias = "re")]
@@ -38,6 +252,12 @@ enum SubCommands {
         #[structopt()]
         /// The packages to uninstall.
         targets: Vec<String>,
+        #[structopt(long)]
+        /// Also remove every package that depends on the targets.
+        cascade: bool,
+        #[structopt(long)]
+        /// List the files that will be deleted when asking for confirmation.
+        show_files: bool,
     },
     /// Update the given packages, or every out of date package if no arguments are given.
     #[structopt(alias = "up")]
@@ -45,10 +265,20 @@ enum SubCommands {
         #[structopt()]
         /// The packages to update (defaults to every package)
         targets: Vec<String>,
+        #[structopt(long, number_of_values = 1)]
+        /// Hold back a package for this update only. Can be given multiple times.
+        exclude: Vec<String>,
+        #[structopt(long)]
+        /// Show the first line of the changelog of each package being updated.
+        changelog: bool,
     },
     /// Bring the package database up to date.
     #[structopt(alias = "sy")]
-    Sync,
+    Sync {
+        #[structopt(long)]
+        /// Trust the package lists of repositories without a signing key.
+        allow_unsigned: bool,
+    },
     /// Download the files of the given packages.
     #[structopt(alias = "fe")]
     Fetch {
@@ -58,7 +288,154 @@ enum SubCommands {
     },
     /// List every known package.
     #[structopt(alias = "li")]
-    List,
+    List {
+        #[structopt(long)]
+        /// Only list installed packages that are not available from any repository.
+        foreign: bool,
+        #[structopt(long, conflicts_with = "foreign")]
+        /// Show everything the given package depends on as a tree instead.
+        tree: Option<String>,
+        #[structopt(long, default_value = "table")]
+        /// How to list the packages: table, or jsonl for one JSON object per line.
+        format: ListFormat,
+    },
+    /// Show all of the metadata of a package.
+    #[structopt(alias = "if")]
+    Info {
+        #[structopt()]
+        /// The package to show.
+        target: String,
+    },
+    /// List the files belonging to a package.
+    #[structopt(alias = "fi")]
+    Files {
+        #[structopt()]
+        /// The package to list the files of.
+        target: String,
+    },
+    /// Restore the missing files of an installed package from its tarball,
+    /// leaving the files that still exist untouched.
+    Repair {
+        #[structopt()]
+        /// The package to repair.
+        target: String,
+    },
+    /// Check that the files of an installed package still exist.
+    Verify {
+        #[structopt()]
+        /// The package to verify.
+        target: String,
+        #[structopt(long)]
+        /// Also compare the contents and permissions of the files against the
+        /// package tarball, reporting every file that differs.
+        diff: bool,
+    },
+    /// Show what changed in the newest version of a package.
+    Changelog {
+        #[structopt()]
+        /// The package to show the changelog of.
+        target: String,
+    },
+    /// Show which installed packages own a file.
+    Owns {
+        #[structopt(parse(from_os_str))]
+        /// The path of the file, or only its name with --name.
+        target: PathBuf,
+        #[structopt(long)]
+        /// Match any file with the given name, in any directory.
+        name: bool,
+    },
+    /// Compare the installed packages against another package database.
+    Diff {
+        #[structopt(parse(from_os_str))]
+        /// The package database to compare against.
+        other: PathBuf,
+    },
+    /// Check if any installed package has an update available. Exits with 0
+    /// if updates are available, 2 if every package is up to date, and 1 on
+    /// errors. The number of updates is only printed to a terminal.
+    CheckUpdate,
+    /// Show the history of changes made to the database.
+    #[structopt(alias = "hi")]
+    History {
+        #[structopt()]
+        /// Only show changes to this package.
+        package: Option<String>,
+    },
+    /// Revert the most recent change made to the database.
+    Undo,
+    /// Remove the packages installed as dependencies that nothing needs anymore.
+    Autoremove,
+    /// Remove old package versions from the download cache.
+    Clean,
+    /// Check the package database for inconsistencies between packages.
+    Doctor {
+        #[structopt(long)]
+        /// Fix the broken packages in one transaction: restore missing files,
+        /// install missing dependencies, and remove the packages whose
+        /// dependencies can't be installed.
+        fix_broken: bool,
+    },
+    /// Write a completion script for the given shell to stdout.
+    #[structopt(setting = structopt::clap::AppSettings::Hidden)]
+    Completions {
+        #[structopt(possible_values = &Shell::variants(), case_insensitive = true)]
+        /// The shell to complete commands in.
+        shell: Shell,
+    },
+    /// Change whether an installed package was installed explicitly or as a dependency.
+    Mark {
+        #[structopt()]
+        /// The package to mark.
+        target: String,
+        #[structopt(long, conflicts_with = "dependency", required_unless = "dependency")]
+        /// Mark the package as explicitly installed, so it is never removed as an orphan.
+        explicit: bool,
+        #[structopt(long)]
+        /// Mark the package as installed as a dependency.
+        dependency: bool,
+    },
+}
+
+/// The subcommands taking package names, for shell completion.
+const PACKAGE_SUBCOMMANDS: &str =
+    "install remove update fetch info files repair verify changelog mark";
+
+impl SubCommands {
+    /// Check if the subcommand changes the database, so it needs to be saved.
+    fn modifies_database(&self) -> bool {
+        match self {
+            Self::Install {
+                download_only,
+                no_database,
+                ..
+            } => !download_only && !no_database,
+            Self::Remove { .. }
+            | Self::Update { .. }
+            | Self::Sync { .. }
+            | Self::Undo
+            | Self::Autoremove
+            | Self::Mark { .. }
+            | Self::Doctor { fix_broken: true } => true,
+            _ => false,
+        }
+    }
+
+    /// Check if the subcommand makes its changes itself instead of selecting
+    /// them, so --print can't show them without making them.
+    fn acts_directly(&self) -> bool {
+        matches!(
+            self,
+            Self::Install {
+                download_only: true,
+                ..
+            } | Self::Sync { .. }
+                | Self::Fetch { .. }
+                | Self::Repair { .. }
+                | Self::Clean
+                | Self::Mark { .. }
+        )
+    }
 }
 
 /// When there is no database found, prompt to create a new database.
@@ -72,7 +449,13 @@ fn create_new_database(options: &Options) -> Result<()> {
         .interact()
         .context("Failed to display prompt.")?
     {
-        println!("Creating a new database.");
+        if options.verbosity() > Verbosity::Quiet {
+            println!("Creating a new database.");
+        }
+        if let Some(parent) = options.database.parent() {
+            std::fs::create_dir_all(parent)
+                .context("Failed to create the directory for the database.")?;
+        }
         let database = Database::new_empty(&options.package_cache);
         database
             .save(&options.database)
@@ -88,9 +471,40 @@ fn create_new_database(options: &Options) -> Result<()> {
     Ok(())
 }
 
-/// Load the package database. This will exit the process if the package database cannot be loaded for any reason.
-fn get_package_database(options: &Options) -> Database {
-    match Database::load(&options.database) {
+/// Load the configuration and the package database, resolving where they are kept into
+/// the options. This will exit the process if the package database cannot be loaded for any reason.
+fn get_package_database(options: &mut Options) -> Database {
+    let mut config = match Config::load(&options.configuration) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Failed to load the configuration: {}", error);
+            process::exit(1)
+        }
+    };
+    if let Some(order) = options.mirror {
+        config.mirror_order = order;
+    }
+    options.database = config.database_path(options.database_flag.as_deref());
+    options.package_cache = config.package_cache_path(options.package_cache_flag.as_deref());
+    let mut database = match options.command {
+        // Nothing is known besides the tarballs being installed.
+        SubCommands::Install {
+            no_database: true, ..
+        } => Database::new_empty(&options.package_cache),
+        _ => load_package_database(options),
+    };
+    database.set_package_cache(&options.package_cache);
+    database.set_config(config);
+    database
+}
+
+/// Load the package database from disk, offering to create it if it's missing
+/// and to recover it if it's corrupt.
+fn load_package_database(options: &Options) -> Database {
+    let database = Database::load_or_recover(&options.database, |error, backup| {
+        choose_recovery(options, error, backup).unwrap_or(Recovery::Abort)
+    });
+    match database {
         Ok(database) => database,
         Err(error) => match error {
             MixError::FileNotFound(_) => {
@@ -100,91 +514,844 @@ fn get_package_database(options: &Options) -> Database {
                 }
                 Database::load(&options.database).unwrap()
             }
+            MixError::SerializationError(error) => {
+                eprintln!("The database is corrupt: {}", error);
+                process::exit(1)
+            }
             // The error is of an unprepared type, so we can't deal with it
             error => unimplemented!("Unhandled error loading database: {:#?}", error),
         },
     }
 }
 
-/// Perform the subcommand if it does not require modifying the database, and
-/// get the needed changes if it does.
-fn process_subcommand(
-    subcommand: &SubCommands,
+/// When the database is corrupt, offer to restore its backup or to start over.
+fn choose_recovery(options: &Options, error: &MixError, backup: Option<&Path>) -> Result<Recovery> {
+    eprintln!(
+        "The database {} could not be read: {}",
+        options.database.display(),
+        error
+    );
+    if options.noconfirm {
+        return Ok(Recovery::Abort);
+    }
+    let mut choices = vec![];
+    if let Some(backup) = backup {
+        eprintln!(
+            "A backup from the last save was found at {}.",
+            backup.display()
+        );
+        choices.push(("Restore the backup", Recovery::Restore));
+    }
+    choices.push((
+        "Start with an empty database, forgetting every installed package",
+        Recovery::StartFresh,
+    ));
+    choices.push(("Give up", Recovery::Abort));
+    let items: Vec<&str> = choices.iter().map(|(item, _)| *item).collect();
+    let choice = dialoguer::Select::new()
+        .with_prompt("What should be done with it?")
+        .items(&items)
+        .default(0)
+        .interact()
+        .context("Failed to display prompt!")?;
+    Ok(choices[choice].1)
+}
+
+/// Download the packages into the download cache, reporting what was downloaded.
+fn download(
+    packages: &[SharedPackage],
     database: &Database,
+    verbosity: Verbosity,
+) -> Result<(), MixError> {
+    let fetched = database.fetch_all(packages)?;
+    if verbosity == Verbosity::Quiet {
+        return Ok(());
+    }
+    if !fetched.is_empty() {
+        println!("Packages downloaded:");
+        for package in &fetched {
+            println!("\t{}", package.borrow().name);
+        }
+    }
+    let cached = packages.len() - fetched.len();
+    if cached > 0 {
+        println!("{} packages were already cached.", cached);
+    }
+    Ok(())
+}
+
+/// Whether the path of a URL ends in `/`, so it names a directory rather than
+/// a package tarball.
+fn names_a_directory(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.ends_with('/')
+}
+
+/// Warn the user if the package lists are older than the configured limit.
+fn warn_stale_sync(database: &Database) {
+    let config = database.config();
+    if !config.warn_stale_sync {
+        return;
+    }
+    if let Some(age) = database.sync_age() {
+        let days = age.as_secs() / (60 * 60 * 24);
+        if days > config.stale_sync_days {
+            eprintln!(
+                "Warning: package lists are {} days old, consider running mix sync.",
+                days
+            );
+        }
+    }
+}
+
+/// Print a line for each package, with the columns aligned. The packages are
+/// written through a buffer, as there may be tens of thousands of them.
+fn print_packages(
+    packages: &[impl Deref<Target = Package>],
+    verbosity: Verbosity,
+    palette: Palette,
+) -> io::Result<()> {
+    let name_width = packages.iter().map(|package| package.name.len()).max();
+    let version_width = packages
+        .iter()
+        .map(|package| package.version.to_string().len())
+        .max();
+    let stdout = io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+    for package in packages {
+        if verbosity == Verbosity::Quiet {
+            writeln!(output, "{}", package.name)?;
+            continue;
+        }
+        write!(
+            output,
+            "{:name_width$}  {:version_width$}  {}",
+            package.name,
+            package.version.to_string(),
+            palette.state(&package.state),
+            name_width = name_width.unwrap_or_default(),
+            version_width = version_width.unwrap_or_default(),
+        )?;
+        if package.withdrawn {
+            write!(output, "  (no longer in its repository)")?;
+        }
+        writeln!(output)?;
+    }
+    output.flush()
+}
+
+/// Print each package as a JSON object on its own line, as the packages are
+/// provided, so the whole list is never held in memory.
+fn print_json_lines(
+    packages: impl Iterator<Item = impl Deref<Target = Package>>,
+) -> Result<(), MixError> {
+    let stdout = io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+    for package in packages {
+        let entry = ListEntry {
+            name: &package.name,
+            version: &package.version,
+            state: &package.state,
+            description: package.description.as_deref(),
+            withdrawn: package.withdrawn,
+        };
+        serde_json::to_writer(&mut output, &entry)?;
+        writeln!(output)?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Print the differences between the database and the database at the given path.
+fn print_diff(database: &Database, other: &Path) -> Result<(), MixError> {
+    let diff = database.diff(&Database::load(other)?);
+    if !diff.only_here.is_empty() {
+        println!("Packages only installed here:");
+        for name in &diff.only_here {
+            println!("\t{}", name);
+        }
+    }
+    if !diff.only_there.is_empty() {
+        println!("Packages only installed in {}:", other.display());
+        for name in &diff.only_there {
+            println!("\t{}", name);
+        }
+    }
+    if !diff.version_differs.is_empty() {
+        println!("Packages installed with different versions:");
+        for (name, here, there) in &diff.version_differs {
+            println!("\t{}\t{} -> {}", name, here, there);
+        }
+    }
+    Ok(())
+}
+
+/// Print every logged change, optionally only those made to one package.
+fn print_history(options: &Options, package: &Option<String>) -> Result<(), MixError> {
+    for transaction in history::read(&history::log_path(&options.database))? {
+        let time = humantime::format_rfc3339_seconds(transaction.time());
+        for change in transaction.changes {
+            if package.is_none() || package.as_ref() == Some(&change.name) {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    time, change.action, change.name, change.version
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Perform the subcommand if it does not need a transaction, and get the
+/// needed changes if it does.
+fn process_subcommand(
+    options: &Options,
+    database: &mut Database,
 ) -> Result<Option<Selections>, MixError> {
+    let subcommand = &options.command;
     use SubCommands::*;
     Ok(match subcommand {
-        Install { targets: _ } => todo!("Installing packages is not yet implemented."),
-        Remove { targets: _ } => todo!("Removing packages is not yet implemented."),
-        Update { targets: _ } => todo!("Updating packages is not yet implemented."),
-        SubCommands::Sync => todo!("Synchronizing with remote servers is not yet implemented."),
-        SubCommands::Fetch { targets: _ } => {
-            todo!("Fetching packages from remote servers is not yet implemented.")
-        }
-        SubCommands::List => {
-            for package in database.all_packages() {
-                let package = package;
-                println!("{}\t{}\t{}", package.name, package.version, package.state);
+        Install {
+            targets,
+            from_file,
+            download_only,
+            sha256,
+            asdeps,
+            asexplicit,
+            needed,
+            replace_conflicting,
+            ..
+        } => {
+            if !options.noconfirm {
+                warn_stale_sync(database);
+            }
+            let mut targets = targets.clone();
+            if let Some(path) = from_file {
+                let list = fs::read_to_string(path).map_err(|error| match error.kind() {
+                    io::ErrorKind::NotFound => MixError::FileNotFound(path.clone()),
+                    _ => error.into(),
+                })?;
+                targets.extend(mix::selection::parse_package_list(&list));
+            }
+            let (tarballs, names): (Vec<&String>, Vec<&String>) =
+                targets.iter().partition(|target| {
+                    *target == "-"
+                        || target.starts_with("http://")
+                        || target.starts_with("https://")
+                });
+            let (files, names): (Vec<&String>, Vec<&String>) = names
+                .into_iter()
+                .partition(|target| target.ends_with(".tar.xz"));
+            if options.print && !tarballs.is_empty() {
+                return Err(MixError::InvalidArguments(String::from(
+                    "--print can't be used with URL or stdin targets, as they're downloaded to be read",
+                )));
+            }
+            if sha256.is_some() && tarballs.len() > 1 {
+                return Err(MixError::InvalidArguments(String::from(
+                    "--sha256 can only be used with a single URL or stdin target",
+                )));
+            }
+            if let Some(url) = tarballs.iter().find(|target| names_a_directory(target)) {
+                return Err(MixError::InvalidArguments(format!(
+                    "{} doesn't name a package tarball",
+                    url
+                )));
+            }
+            // Tarballs installed by themselves are only decompressed once, as
+            // their files are placed while their manifest is read.
+            let by_themselves = names.is_empty()
+                && tarballs.is_empty()
+                && !options.print
+                && !download_only
+                && !needed
+                && !asdeps
+                && !replace_conflicting;
+            if by_themselves && !files.is_empty() {
+                install_tarballs(&files, options, database)?;
+                return Ok(None);
+            }
+            let mut selections =
+                mix::selection::install_unchecked(&names, database).map_err(|(error, _)| error)?;
+            if !options.print && options.verbosity() > Verbosity::Quiet {
+                for name in &selections.already_installed {
+                    println!("{} is already installed.", name);
+                }
+            }
+            let mut target_names: Vec<String> = names.into_iter().cloned().collect();
+            if !files.is_empty() {
+                let local = mix::selection::install_tarballs(&files, database)?;
+                for package in local.install {
+                    target_names.push(package.borrow().name.clone());
+                    selections.install.push(package);
+                }
+            }
+            for tarball in tarballs {
+                let package = if tarball == "-" {
+                    database.fetch_reader(io::stdin().lock(), sha256.as_deref())?
+                } else {
+                    database.fetch_url(tarball, sha256.as_deref())?
+                };
+                target_names.push(package.borrow().name.clone());
+                selections.install.push(package);
+            }
+            if *needed {
+                let skipped = mix::selection::skip_up_to_date(&mut selections, database);
+                if !options.print && options.verbosity() > Verbosity::Quiet {
+                    for package in skipped {
+                        println!("{} is already up to date, skipping.", package.borrow().name);
+                    }
+                }
+            }
+            mix::selection::resolve_conflicts(&mut selections, database, *replace_conflicting)?;
+            // structopt makes sure at most one of the flags is given.
+            let reason = match (asdeps, asexplicit) {
+                (true, false) => InstallState::Dependency,
+                _ => InstallState::Manual,
+            };
+            mix::selection::set_install_reason(&mut selections, &target_names, reason);
+            if *download_only {
+                let packages: Vec<_> = selections
+                    .install
+                    .iter()
+                    .chain(&selections.upgrade)
+                    .cloned()
+                    .collect();
+                download(&packages, database, options.verbosity())?;
+                None
+            } else {
+                Some(selections)
+            }
+        }
+        Remove {
+            targets, cascade, ..
+        } => {
+            let selections = if *cascade {
+                mix::selection::remove_cascade(targets, database)
+            } else {
+                mix::selection::remove(targets, database)
+            }
+            .map_err(|(error, _)| error)?;
+            let dependents: Vec<_> = selections
+                .remove
+                .iter()
+                .filter(|package| !targets.contains(&package.borrow().name))
+                .collect();
+            if !dependents.is_empty() && !options.print && options.verbosity() > Verbosity::Quiet {
+                println!("The following packages depend on the targets and will also be removed:");
+                for package in dependents {
+                    println!("\t{}", package.borrow().name);
+                }
+            }
+            Some(selections)
+        }
+        Update {
+            targets,
+            exclude,
+            changelog,
+        } => {
+            let mut selections =
+                mix::selection::update(targets, database).map_err(|(error, _)| error)?;
+            let excluded = mix::selection::exclude(&mut selections, exclude);
+            if !excluded.is_empty() && !options.print && options.verbosity() > Verbosity::Quiet {
+                println!("Packages excluded this run:");
+                for package in excluded {
+                    println!("\t{}", package.borrow().name);
+                }
+            }
+            if *changelog && !options.print {
+                print_changelog_summaries(&selections, database);
+            }
+            Some(selections)
+        }
+        SubCommands::Sync { allow_unsigned } => {
+            if *allow_unsigned {
+                database.set_config(Config {
+                    allow_unsigned: true,
+                    ..database.config().clone()
+                });
+            }
+            let mut failed = vec![];
+            for repository in database.repositories() {
+                if let Err(error) = database.synchronize(repository.as_ref()) {
+                    eprintln!("Failed to synchronize {}: {}", repository.name(), error);
+                    failed.push(repository.name().to_owned());
+                }
+            }
+            if !failed.is_empty() {
+                // The repositories that were synchronized are kept.
+                database.save(&options.database)?;
+                return Err(MixError::SyncFailed(failed));
+            }
+            None
+        }
+        SubCommands::Fetch { targets } => {
+            let packages = mix::selection::packages_from_names(targets, database)
+                .map_err(|(error, _)| error)?;
+            download(&packages, database, options.verbosity())?;
+            None
+        }
+        SubCommands::List {
+            foreign,
+            tree,
+            format,
+        } => {
+            if let Some(name) = tree {
+                println!("{}", database.dependency_tree(name)?);
+            } else if *format == ListFormat::JsonLines {
+                if *foreign {
+                    print_json_lines(database.foreign().iter())?;
+                } else {
+                    print_json_lines(database.packages())?;
+                }
+            } else if *foreign {
+                print_packages(
+                    &database.foreign().iter().collect::<Vec<_>>(),
+                    options.verbosity(),
+                    options.palette(),
+                )?;
+            } else {
+                print_packages(
+                    &database.packages().collect::<Vec<_>>(),
+                    options.verbosity(),
+                    options.palette(),
+                )?;
+            }
+            None
+        }
+        Info { target } => {
+            println!("{}", database.info(target)?);
+            None
+        }
+        Files { target } => {
+            for file in database.files(target)? {
+                println!("{}", file.display());
+            }
+            None
+        }
+        Repair { target } => {
+            let repaired = database.repair(target)?;
+            if options.verbosity() > Verbosity::Quiet {
+                if repaired.is_empty() {
+                    println!("No files of {} are missing.", target);
+                }
+                for file in repaired {
+                    println!("Restored {}", file.display());
+                }
+            }
+            None
+        }
+        Verify { target, diff } => {
+            let discrepancies = if *diff {
+                database.audit(target)?
+            } else {
+                database
+                    .missing_files(target)?
+                    .into_iter()
+                    .map(FileDiscrepancy::Missing)
+                    .collect()
+            };
+            if discrepancies.is_empty() && options.verbosity() > Verbosity::Quiet {
+                println!("Every file of {} is intact.", target);
+            }
+            for discrepancy in discrepancies {
+                println!("{}", discrepancy);
+            }
+            None
+        }
+        Changelog { target } => {
+            match database.changelog(target)? {
+                Some(changelog) => println!("{}", changelog.trim_end()),
+                None => println!("No changelog available."),
+            }
+            None
+        }
+        Owns { target, name } => {
+            let owners = if *name {
+                database.providers_of_file(&target.to_string_lossy())
+            } else {
+                database.owner_of(target)
+            };
+            if owners.is_empty() {
+                eprintln!("No installed package owns {}.", target.display());
+            }
+            for owner in owners {
+                println!("{}", owner);
+            }
+            None
+        }
+        Diff { other } => {
+            print_diff(database, other)?;
+            None
+        }
+        History { package } => {
+            print_history(options, package)?;
+            None
+        }
+        Undo => Some(database.undo_selections()?),
+        // These are handled by run(): check-update needs its own exit code,
+        // and completions don't need a database.
+        CheckUpdate | Completions { .. } => None,
+        Clean => {
+            let freed = database.clean_cache()?;
+            if options.verbosity() > Verbosity::Quiet {
+                println!("Freed {} from the download cache.", format_bytes(freed));
+            }
+            None
+        }
+        Mark {
+            target,
+            explicit,
+            dependency,
+        } => {
+            // structopt makes sure exactly one of the flags is given.
+            let (state, reason) = match (explicit, dependency) {
+                (true, _) => (InstallState::Manual, "explicitly"),
+                (_, true) => (InstallState::Dependency, "as a dependency"),
+                _ => unreachable!(),
+            };
+            database.set_state(target, state)?;
+            if options.verbosity() > Verbosity::Quiet {
+                println!("Marked {} as installed {}.", target, reason);
+            }
+            None
+        }
+        Autoremove => Some(mix::selection::autoremove(database)),
+        Doctor { fix_broken: true } => Some(database.compute_repairs()),
+        Doctor { fix_broken: false } => {
+            let warnings = database.validate();
+            if warnings.is_empty() && options.verbosity() > Verbosity::Quiet {
+                println!("No problems found.");
+            }
+            for warning in warnings {
+                println!("{}", warning);
             }
             None
         }
     })
 }
 
-/// Ask the user to confirm if they wish to perform the action about to be executed.
-fn confirm_action(selections: &Selections) -> Result<bool> {
-    if !selections.install.is_empty() {
-        println!("Packages to be installed:");
-        for package in &selections.install {
-            println!("\t{}", package.borrow().name);
+/// Print the selections in the format of --print.
+fn print_selections(selections: &Selections) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+    let summary = selections.describe();
+    for (action, packages) in &summary.changes() {
+        for (name, version) in packages.iter() {
+            writeln!(output, "{}\t{}\t{}", action, name, version)?;
         }
     }
-    if !selections.upgrade.is_empty() {
-        println!("Packages to be upgraded:");
-        for package in &selections.upgrade {
-            println!("\t{}", package.borrow().name);
-        }
+    output.flush()
+}
+
+/// How many files of a package are listed before the rest are only counted.
+const FILE_SUMMARY_LENGTH: usize = 10;
+
+/// Print the first few files, and how many more there are.
+fn print_file_summary(files: &[PathBuf]) {
+    for file in files.iter().take(FILE_SUMMARY_LENGTH) {
+        println!("\t\t{}", file.display());
     }
-    if !selections.downgrade.is_empty() {
-        println!("Packages to be downgraded:");
-        for package in &selections.downgrade {
-            println!("\t{}", package.borrow().name);
-        }
+    if files.len() > FILE_SUMMARY_LENGTH {
+        println!(
+            "\t\t...and {} more files",
+            files.len() - FILE_SUMMARY_LENGTH
+        );
     }
-    if !selections.remove.is_empty() {
-        println!("Packages to be remove:");
-        for package in &selections.remove {
-            println!("\t{}", package.borrow().name);
+}
+
+/// Ask the user to confirm if they wish to perform the action about to be executed.
+/// Pressing enter only accepts the changes if they don't remove any packages.
+fn confirm_action(
+    command: &SubCommands,
+    selections: &Selections,
+    verbosity: Verbosity,
+    palette: Palette,
+) -> Result<bool> {
+    let summary = selections.describe();
+    let show_files = matches!(
+        command,
+        SubCommands::Remove {
+            show_files: true,
+            ..
+        }
+    );
+    for (action, packages) in &summary.changes() {
+        if packages.is_empty() {
+            continue;
+        }
+        let heading = match *action {
+            "install" => "installed",
+            "upgrade" => "upgraded",
+            "downgrade" => "downgraded",
+            "repair" => "repaired",
+            _ => "removed",
+        };
+        println!("Packages to be {}:", heading);
+        for (index, (name, version)) in packages.iter().enumerate() {
+            let line = if verbosity == Verbosity::Verbose {
+                format!("{} {}", name, version)
+            } else {
+                name.clone()
+            };
+            println!("\t{}", palette.action(action, &line));
+            if show_files && *action == "remove" {
+                print_file_summary(&selections.remove[index].borrow().files);
+            }
         }
     }
+    if let Some(change) = summary.size_change {
+        let sign = if change < 0 { "-" } else { "+" };
+        println!(
+            "Net size change: {}{}",
+            sign,
+            format_bytes(change.unsigned_abs())
+        );
+    }
     dialoguer::Confirm::new()
         .with_prompt("Do you want to apply these changes?")
+        .default(selections.remove.is_empty())
         .interact()
         .context("Failed to display prompt!")
 }
 
+/// Install each of the tarballs straight from the file, after confirming
+/// unless --noconfirm is given. The packages installed before one fails are
+/// saved before the error is returned.
+fn install_tarballs(
+    files: &[&String],
+    options: &Options,
+    database: &mut Database,
+) -> Result<(), MixError> {
+    if !options.noconfirm {
+        println!("Tarballs to be installed:");
+        for file in files {
+            println!("\t{}", file);
+        }
+        if !dialoguer::Confirm::new()
+            .with_prompt("Do you want to apply these changes?")
+            .default(true)
+            .interact()?
+        {
+            return Err(MixError::Aborted);
+        }
+    }
+    for file in files {
+        match mix::package::install_tarball(Path::new(file), database) {
+            Ok(package) if options.verbosity() > Verbosity::Quiet => {
+                let package = package.borrow();
+                println!("Installed {} {}", package.name, package.version);
+            }
+            Ok(_) => {}
+            Err(error) => {
+                if options.command.modifies_database() {
+                    database.save(&options.database)?;
+                }
+                return Err(error);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print the first line of the changelog of each package being upgraded.
+fn print_changelog_summaries(selections: &Selections, database: &Database) {
+    println!("Changes in this update:");
+    for package in &selections.upgrade {
+        let name = package.borrow().name.clone();
+        let summary = match database.changelog(&name) {
+            Ok(Some(changelog)) => changelog.lines().next().unwrap_or_default().to_owned(),
+            Ok(None) => String::from("no changelog available."),
+            Err(error) => format!("failed to get the changelog: {}", error),
+        };
+        println!("\t{}: {}", name, summary);
+    }
+}
+
+/// Tell the user about the optional dependencies of newly installed packages.
+fn print_optional_dependencies(packages: &[SharedPackage]) {
+    for package in packages {
+        let package = package.borrow();
+        if package.optional_dependencies.is_empty() {
+            continue;
+        }
+        println!("Optional dependencies for {}:", package.name);
+        for dependency in &package.optional_dependencies {
+            println!("\t{}", format_optional_dependency(dependency));
+        }
+    }
+}
+
 /// Prepare the progress bar for usage in mix operations.
-fn enable_progress_bar(bar: &ProgressBar, verb: &str, packages_count: usize) {
-    bar.set_length(packages_count as u64);
+fn enable_progress_bar(bar: &ProgressBar, verb: &str, length: usize) {
+    bar.set_length(length as u64);
     bar.set_prefix(verb);
     bar.reset_elapsed();
     bar.enable_steady_tick(20);
 }
 
-/// The entry point of the application.
-pub fn run() -> Result<()> {
-    let options = Options::from_args();
-    let mut database = get_package_database(&options);
-    let selections = process_subcommand(&options.command, &database)?;
+/// The exit code of check-update when no updates are available.
+const NO_UPDATES: i32 = 2;
+
+/// Check for updates, providing the exit code of check-update.
+fn check_update(database: &Database, verbosity: Verbosity) -> i32 {
+    let updates = database.outdated().len();
+    if io::stdout().is_terminal() && verbosity > Verbosity::Quiet {
+        println!("{} updates available.", updates);
+    }
+    if updates > 0 {
+        0
+    } else {
+        NO_UPDATES
+    }
+}
+
+/// Offer to resume or roll back a transaction that was interrupted.
+fn handle_pending_transaction(options: &Options, database: &mut Database) -> Result<()> {
+    let transaction = match database.pending_transaction()? {
+        Some(transaction) => transaction,
+        None => return Ok(()),
+    };
+    eprintln!("The last transaction was interrupted before it finished.");
+    if options.noconfirm {
+        eprintln!("Run mix without --noconfirm to resume it or roll it back.");
+        return Ok(());
+    }
+    let choice = dialoguer::Select::new()
+        .with_prompt("What should be done with it?")
+        .items(&[
+            "Resume it",
+            "Roll it back",
+            "Leave it for later",
+            "Forget about it",
+        ])
+        .default(0)
+        .interact()
+        .context("Failed to display prompt!")?;
+    let selections = match choice {
+        0 => database.resume_selections(&transaction)?,
+        1 => database.rollback_selections(&transaction)?,
+        2 => return Ok(()),
+        _ => return Ok(database.discard_pending()?),
+    };
+    if confirm_action(
+        &options.command,
+        &selections,
+        options.verbosity(),
+        options.palette(),
+    )? {
+        let result = database.apply(selections);
+        database
+            .save(&options.database)
+            .context("Failed to save database.")?;
+        result?;
+    }
+    Ok(())
+}
+
+/// The entry point of the application, providing the exit code.
+pub fn run() -> Result<i32> {
+    let mut options = Options::from_args();
+    // Completions are generated before the database is needed, as they're often generated at build time.
+    if let SubCommands::Completions { shell } = options.command {
+        Options::clap().gen_completions_to("mix", shell, &mut io::stdout());
+        if let Shell::Fish = shell {
+            // Complete package names from the database when the completion is used.
+            println!(
+                "complete -c mix -n \"__fish_seen_subcommand_from {}\" -f -a \"(mix --quiet list 2>/dev/null)\"",
+                PACKAGE_SUBCOMMANDS
+            );
+        }
+        return Ok(0);
+    }
+    let mut database = get_package_database(&mut options);
+    let cancelled = database.cancellation_token();
+    ctrlc::set_handler(move || {
+        eprintln!("Interrupted, stopping after the current package.");
+        cancelled.store(true, Ordering::SeqCst);
+    })
+    .context("Failed to set the interrupt handler.")?;
+    if let SubCommands::CheckUpdate = options.command {
+        return Ok(check_update(&database, options.verbosity()));
+    }
+    if options.print && options.command.acts_directly() {
+        return Err(MixError::InvalidArguments(String::from(
+            "--print only works with commands that select packages to change",
+        ))
+        .into());
+    }
+    let modifies_database = options.command.modifies_database() && !options.print;
+    // Refuse to make changes that couldn't be saved afterwards.
+    if modifies_database {
+        Database::check_writable(&options.database)?;
+    }
+    if !options.print {
+        handle_pending_transaction(&options, &mut database)?;
+    }
+    let mut selections = process_subcommand(&options, &mut database)?;
+    // Such as when every package to install is already installed.
+    let nothing_to_do = selections
+        .as_ref()
+        .is_some_and(|selections| selections.describe().count() == 0);
+    if nothing_to_do && !options.print {
+        if options.verbosity() > Verbosity::Quiet {
+            println!("Nothing to do.");
+        }
+        selections = None;
+    }
+    let mut result = Ok(());
     if let Some(selections) = selections {
-        //TODO: Add a progress bar back into the application.
-        if !confirm_action(&selections)? {
+        if options.print {
+            print_selections(&selections)?;
+            return Ok(0);
+        }
+        if !options.noconfirm
+            && !confirm_action(
+                &options.command,
+                &selections,
+                options.verbosity(),
+                options.palette(),
+            )?
+        {
             return Err(MixError::Aborted.into());
         }
-        database.apply(selections)?;
+        if !selections.downgrade.is_empty() && options.verbosity() > Verbosity::Quiet {
+            eprintln!("Not downgrading the following packages (Not yet implemented):");
+            for package in &selections.downgrade {
+                eprintln!("\t{}", package.borrow().name);
+            }
+        }
+        let installed = selections.install.clone();
+        let files = selections
+            .install
+            .iter()
+            .chain(&selections.upgrade)
+            .map(|package| package.borrow().files.len())
+            .sum::<usize>();
+        // The bar follows the files, so one large package still shows progress.
+        let bar = if files > 0 && options.verbosity() > Verbosity::Quiet {
+            ProgressBar::new(0).with_style(
+                ProgressStyle::default_bar().template("{prefix} [{bar:40}] {pos}/{len} {wide_msg}"),
+            )
+        } else {
+            ProgressBar::hidden()
+        };
+        enable_progress_bar(&bar, "Installing", files);
+        result = database.apply_with_progress(selections, &mut |path| {
+            bar.set_message(&path.display().to_string());
+            bar.inc(1);
+        });
+        bar.finish_and_clear();
+        if result.is_ok() && options.verbosity() > Verbosity::Quiet {
+            print_optional_dependencies(&installed);
+        }
     }
-    database
-        .save(&options.database)
-        .context("Failed to save database.")?;
-    Ok(())
+    // Packages changed before an interruption or error still need to be saved.
+    if modifies_database {
+        database
+            .save(&options.database)
+            .context("Failed to save database.")?;
+    }
+    result?;
+    Ok(0)
 }
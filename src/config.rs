@@ -0,0 +1,244 @@
+use crate::{hooks::Hook, net::MirrorOrder, package::MANIFEST_FILENAME, Error};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The directory the database and package cache are kept in by default.
+pub const DEFAULT_DIRECTORY: &str = "/var/lib/mix/";
+/// The environment variable that sets the location of the database.
+pub const DATABASE_VARIABLE: &str = "MIX_DB";
+/// The environment variable that sets the location of the package cache.
+pub const PACKAGE_CACHE_VARIABLE: &str = "MIX_CACHE";
+
+/// System options for mix, read from a TOML configuration file. Every option
+/// has a default, so missing options (or a missing file) are not an error.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The directory packages are installed into.
+    pub root: PathBuf,
+    /// How many versions of each package to keep in the package cache.
+    /// The tarball of the installed version is always kept.
+    pub keep_versions: usize,
+    /// Whether to warn before installing when the package lists are out of date.
+    pub warn_stale_sync: bool,
+    /// How many days old the package lists can be before they are out of date.
+    pub stale_sync_days: u64,
+    /// How many packages can be installed at once. Packages that depend on
+    /// each other are always installed one after the other.
+    pub install_jobs: usize,
+    /// The repositories packages are downloaded from.
+    pub repositories: Vec<RepositoryConfig>,
+    /// The order the mirrors of a repository are tried in.
+    pub mirror_order: MirrorOrder,
+    /// Commands to run after transactions that touch certain packages or files.
+    pub hooks: Vec<Hook>,
+    /// The architecture of this system. Only packages built for it (or for
+    /// any architecture) can be installed. Defaults to the architecture mix
+    /// was built for.
+    pub architecture: String,
+    /// The file containing the package database. See [Config::database_path]
+    /// for how the location is chosen when this is not set.
+    pub database: Option<PathBuf>,
+    /// Permission bits removed from every installed file and directory, such
+    /// as `0o022` to keep them from being writable by the group and others.
+    pub install_umask: u32,
+    /// Whether installed files keep the setuid and setgid bits they are
+    /// packaged with. Without this, the bits are removed.
+    pub allow_setuid: bool,
+    /// Where the tarballs of installed local packages are kept. See
+    /// [Config::package_cache_path] for how the location is chosen when this is not set.
+    pub package_cache: Option<PathBuf>,
+    /// Where tarballs downloaded from repositories and URLs are stored. This
+    /// can be shared between systems, and is what cleaning the cache prunes.
+    /// Defaults to the package cache.
+    pub download_cache: Option<PathBuf>,
+    /// The public key that signs the package index of each repository, by
+    /// the URL of one of its mirrors. Keys are OpenPGP public keys as
+    /// exported by `gpg --export`, without ASCII armor.
+    pub signing_keys: BTreeMap<String, PathBuf>,
+    /// Whether repositories without a signing key can be synchronized. Their
+    /// package lists are trusted as they are.
+    pub allow_unsigned: bool,
+    /// Whether the tarballs of packages from repositories must carry a good
+    /// detached signature by the [signing key](Config::signing_key) of their
+    /// repository. Local builds aren't from a repository, so they're never checked.
+    pub verify_packages: bool,
+    /// The name of the entry holding the metadata of package tarballs, for
+    /// packages built by tools that don't use `.MANIFEST`.
+    pub manifest_name: Option<String>,
+}
+
+/// A repository and the mirrors it can be downloaded from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RepositoryConfig {
+    /// The name of the repository, as recorded by the packages from it.
+    pub name: String,
+    /// The base URLs of the repository's mirrors, in order of preference.
+    pub mirrors: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("/"),
+            keep_versions: 1,
+            warn_stale_sync: true,
+            stale_sync_days: 7,
+            install_jobs: 1,
+            repositories: vec![],
+            mirror_order: MirrorOrder::Ordered,
+            hooks: vec![],
+            architecture: String::from(std::env::consts::ARCH),
+            database: None,
+            package_cache: None,
+            download_cache: None,
+            signing_keys: BTreeMap::new(),
+            allow_unsigned: false,
+            verify_packages: false,
+            manifest_name: None,
+            install_umask: 0,
+            allow_setuid: false,
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration from disk, using the defaults if the file does not exist.
+    pub fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) => match err.kind() {
+                std::io::ErrorKind::NotFound => return Ok(Self::default()),
+                _ => return Err(Error::IOError(err)),
+            },
+        };
+        let config: Self =
+            toml::from_str(&contents).map_err(|error| Error::InvalidConfig(error.to_string()))?;
+        for hook in &config.hooks {
+            hook.validate()?;
+        }
+        Ok(config)
+    }
+
+    /// Resolve the location of the database. In order of precedence, this is
+    /// the given override (such as a command line flag), the `database`
+    /// option, the `MIX_DB` environment variable, and finally `mix.db` in
+    /// [DEFAULT_DIRECTORY].
+    /// ```
+    /// # use mix::Config;
+    /// # use std::path::Path;
+    /// let config = Config {
+    ///     database: Some("/etc/mix.db".into()),
+    ///     ..Config::default()
+    /// };
+    /// assert_eq!(config.database_path(None), Path::new("/etc/mix.db"));
+    /// assert_eq!(config.database_path(Some(Path::new("test.db"))), Path::new("test.db"));
+    /// ```
+    pub fn database_path(&self, flag: Option<&Path>) -> PathBuf {
+        resolve_path(flag, &self.database, DATABASE_VARIABLE, "mix.db")
+    }
+
+    /// Resolve the location of the package cache, with the same precedence as
+    /// [Config::database_path]: the given override, the `package_cache`
+    /// option, the `MIX_CACHE` environment variable, and finally `cache/` in
+    /// [DEFAULT_DIRECTORY].
+    pub fn package_cache_path(&self, flag: Option<&Path>) -> PathBuf {
+        resolve_path(flag, &self.package_cache, PACKAGE_CACHE_VARIABLE, "cache/")
+    }
+
+    /// Provide the mode to give an installed file or directory that is
+    /// packaged with the given mode, following
+    /// [install_umask](Config::install_umask) and [allow_setuid](Config::allow_setuid).
+    /// ```
+    /// # use mix::Config;
+    /// let config = Config {
+    ///     install_umask: 0o022,
+    ///     ..Config::default()
+    /// };
+    /// assert_eq!(config.installed_mode(0o777), 0o755);
+    /// assert_eq!(config.installed_mode(0o4755), 0o755);
+    /// let config = Config {
+    ///     allow_setuid: true,
+    ///     ..config
+    /// };
+    /// assert_eq!(config.installed_mode(0o4775), 0o4755);
+    /// ```
+    pub fn installed_mode(&self, mode: u32) -> u32 {
+        let mode = mode & !self.install_umask;
+        if self.allow_setuid {
+            mode
+        } else {
+            mode & !0o6000
+        }
+    }
+
+    /// Provide the name of the entry holding the metadata of package
+    /// tarballs, which is [MANIFEST_FILENAME] unless the `manifest_name`
+    /// option is set.
+    /// ```
+    /// # use mix::Config;
+    /// assert_eq!(Config::default().manifest_filename(), ".MANIFEST");
+    /// let config = Config {
+    ///     manifest_name: Some(String::from(".PKGINFO")),
+    ///     ..Config::default()
+    /// };
+    /// assert_eq!(config.manifest_filename(), ".PKGINFO");
+    /// ```
+    pub fn manifest_filename(&self) -> &str {
+        self.manifest_name.as_deref().unwrap_or(MANIFEST_FILENAME)
+    }
+
+    /// Get the configuration of the repository with the given name.
+    pub fn repository(&self, name: &str) -> Option<&RepositoryConfig> {
+        self.repositories
+            .iter()
+            .find(|repository| repository.name == name)
+    }
+
+    /// Provide the key that signs the package index of the named repository,
+    /// which is the key of the first of its mirrors that has one.
+    /// ```
+    /// # use mix::{config::RepositoryConfig, Config};
+    /// # use std::path::Path;
+    /// let mut config = Config::default();
+    /// config.repositories.push(RepositoryConfig {
+    ///     name: String::from("core"),
+    ///     mirrors: vec![String::from("https://example.com/core/")],
+    /// });
+    /// config
+    ///     .signing_keys
+    ///     .insert(String::from("https://example.com/core"), "/etc/mix/core.gpg".into());
+    /// assert_eq!(config.signing_key("core"), Some(Path::new("/etc/mix/core.gpg")));
+    /// assert_eq!(config.signing_key("extra"), None);
+    /// ```
+    pub fn signing_key(&self, repository: &str) -> Option<&Path> {
+        let trimmed = |url: &str| url.trim_end_matches('/').to_owned();
+        self.repository(repository)?
+            .mirrors
+            .iter()
+            .find_map(|mirror| {
+                self.signing_keys
+                    .iter()
+                    .find(|(url, _)| trimmed(url) == trimmed(mirror))
+            })
+            .map(|(_, key)| key.as_path())
+    }
+}
+
+/// Pick the first of an override, a configured path, an environment variable,
+/// and a file in the default directory.
+fn resolve_path(
+    flag: Option<&Path>,
+    configured: &Option<PathBuf>,
+    variable: &str,
+    default: &str,
+) -> PathBuf {
+    flag.map(Path::to_owned)
+        .or_else(|| configured.clone())
+        .or_else(|| std::env::var_os(variable).map(PathBuf::from))
+        .unwrap_or_else(|| Path::new(DEFAULT_DIRECTORY).join(default))
+}
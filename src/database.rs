@@ -1,48 +1,304 @@
 use crate::{
-    package::{self, Package, RcRefCellPackage},
-    Error, Selections,
+    config::{Config, RepositoryConfig},
+    filesystem::{FileSystem, OsFileSystem},
+    history::{self, Action, Transaction},
+    hooks,
+    lockfile::{LockedPackage, Lockfile},
+    net,
+    package::{self, InstallState, Package, PackageInfo, SharedPackage},
+    repository::{self, local_mirror, DirectoryRepository, HttpRepository, Repository},
+    selection, signature, Error, Selections, Version,
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::File,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ffi::OsString,
+    fs::{copy, create_dir_all, read_dir, remove_file, rename, File, OpenOptions},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLockReadGuard,
+    },
+    time::{Duration, SystemTime},
 };
 
+/// The differences between the installed packages of two databases, as
+/// provided by [Database::diff](Database::diff). Every list is sorted by name.
+#[derive(Debug, Default, PartialEq)]
+pub struct DatabaseDiff {
+    /// Packages only installed in this database.
+    pub only_here: Vec<String>,
+    /// Packages only installed in the other database.
+    pub only_there: Vec<String>,
+    /// Packages installed in both databases with different versions, along
+    /// with the version in this database and the version in the other database.
+    pub version_differs: Vec<(String, Version, Version)>,
+}
+
+/// What to do about a database that couldn't be read, as chosen for
+/// [Database::load_or_recover](Database::load_or_recover).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// Load the backup written by the last successful save instead.
+    Restore,
+    /// Start over with an empty database, forgetting every installed package.
+    StartFresh,
+    /// Give up, providing the error the database failed to load with.
+    Abort,
+}
+
+/// A logical problem with the contents of the package database, as found by
+/// [Database::validate](Database::validate).
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConsistencyWarning {
+    /// The package was installed as a dependency, but nothing installed depends on it.
+    Orphan(String),
+    /// An installed package depends on a package that isn't installed.
+    BrokenDependency {
+        /// The installed package.
+        package: String,
+        /// The dependency that isn't installed.
+        dependency: String,
+    },
+    /// The package is in the database more than once with the same version,
+    /// or is installed with more than one version.
+    Duplicate(String),
+}
+
+impl std::fmt::Display for ConsistencyWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Orphan(name) => write!(
+                f,
+                "{} was installed as a dependency, but no installed package depends on it",
+                name
+            ),
+            Self::BrokenDependency {
+                package,
+                dependency,
+            } => write!(
+                f,
+                "{} depends on {}, which is not installed",
+                package, dependency
+            ),
+            Self::Duplicate(name) => write!(f, "{} is in the database more than once", name),
+        }
+    }
+}
+
+/// A package and everything it depends on, as provided by
+/// [Database::dependency_tree](Database::dependency_tree).
+/// ```
+/// # use mix::{DependencyTree, InstallState};
+/// let leaf = |name: &str, repeated| DependencyTree {
+///     name: String::from(name),
+///     state: Some(InstallState::Dependency),
+///     repeated,
+///     dependencies: vec![],
+/// };
+/// let tree = DependencyTree {
+///     name: String::from("foo"),
+///     state: Some(InstallState::Manual),
+///     repeated: false,
+///     dependencies: vec![leaf("bar", false), leaf("bar", true)],
+/// };
+/// assert_eq!(
+///     tree.to_string(),
+///     "foo [Manual]\n  bar [Dependency]\n  bar [Dependency] (see above)"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct DependencyTree {
+    /// The name of the package.
+    pub name: String,
+    /// The installation state of the package, or None if it isn't in the database.
+    pub state: Option<InstallState>,
+    /// Whether the package appears earlier in the tree. Its dependencies are
+    /// only listed the first time it appears.
+    pub repeated: bool,
+    /// The trees of the package's dependencies.
+    pub dependencies: Vec<DependencyTree>,
+}
+
+impl DependencyTree {
+    /// Write the tree with each level indented further than the last.
+    fn write_indented(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        write!(f, "{:indent$}{} ", "", self.name, indent = depth * 2)?;
+        match &self.state {
+            Some(InstallState::Uninstalled) => write!(f, "[Not installed]")?,
+            Some(state) => write!(f, "[{}]", state)?,
+            None => write!(f, "[Not found]")?,
+        }
+        if self.repeated {
+            write!(f, " (see above)")?;
+        }
+        for dependency in &self.dependencies {
+            writeln!(f)?;
+            dependency.write_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for DependencyTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
 /// The package database. It provides all actions needed to manage packages.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Database {
-    packages: Vec<RcRefCellPackage>,
+    packages: Vec<SharedPackage>,
+    #[serde(default)]
+    last_sync: Option<SystemTime>,
+    /// The validators of the last package index downloaded from each repository.
+    #[serde(default)]
+    index_validators: BTreeMap<String, net::Validators>,
     #[serde(skip)]
     package_cache: PathBuf,
+    #[serde(skip)]
+    log_path: Option<PathBuf>,
+    #[serde(skip)]
+    pending_path: Option<PathBuf>,
+    #[serde(skip)]
+    config: Config,
+    #[serde(skip)]
+    cancelled: Arc<AtomicBool>,
+    #[serde(skip)]
+    base_dir: PathBuf,
+    /// The positions in `packages` of every version of each package, in
+    /// order, so packages are found by name without going through them all.
+    #[serde(skip)]
+    by_name: HashMap<String, Vec<usize>>,
+    /// Where the files of packages are placed.
+    #[serde(skip, default = "os_filesystem")]
+    filesystem: Arc<dyn FileSystem>,
+}
+
+fn os_filesystem() -> Arc<dyn FileSystem> {
+    Arc::new(OsFileSystem)
 }
 
 impl Database {
     /// Given the name of a package, provide the package itself.
-    pub(crate) fn get_package(&self, package_name: &impl AsRef<str>) -> Option<RcRefCellPackage> {
-        self.iter()
-            .find(|package| package.borrow().name == package_name.as_ref())
+    pub(crate) fn get_package(&self, package_name: &impl AsRef<str>) -> Option<SharedPackage> {
+        self.named(package_name.as_ref()).next()
+    }
+    /// Given the name of a package, provide the installed version of it.
+    pub(crate) fn installed_package(&self, package_name: &str) -> Option<SharedPackage> {
+        self.named(package_name)
+            .find(|package| package.borrow().state != InstallState::Uninstalled)
+    }
+
+    /// Provide every version of the named package, in the order they became known.
+    fn named(&self, package_name: &str) -> impl Iterator<Item = SharedPackage> + '_ {
+        self.by_name
+            .get(package_name)
+            .into_iter()
+            .flatten()
+            .map(move |&index| self.packages[index].clone())
     }
+
+    /// Rebuild the index of the packages by name, after the packages were
+    /// replaced as a whole.
+    fn index_packages(&mut self) {
+        self.by_name.clear();
+        for (index, package) in self.packages.iter().enumerate() {
+            let name = package.borrow().name.clone();
+            self.by_name.entry(name).or_default().push(index);
+        }
+    }
+
+    /// Make a new version of a package known, taking over the install state
+    /// of the installed version it replaces. The replacement isn't marked as
+    /// installed until its files are placed.
+    pub(crate) fn replace_package(
+        &mut self,
+        installed: &SharedPackage,
+        replacement: &SharedPackage,
+    ) -> crate::Result<()> {
+        self.import_package(replacement.clone())?;
+        let state = installed.borrow().state.clone();
+        replacement.borrow_mut().state = state;
+        Ok(())
+    }
+
     /// Provide an iterator over the values of the database.
-    pub(crate) fn iter(&self) -> impl Iterator<Item = RcRefCellPackage> + '_ {
+    pub(crate) fn iter(&self) -> impl Iterator<Item = SharedPackage> + '_ {
         self.packages.iter().cloned()
     }
 
     /// Add the given package to the database.
-    pub(crate) fn import_package(&mut self, package: RcRefCellPackage) -> crate::Result<()> {
-        if self.packages.contains(&package) {
+    pub(crate) fn import_package(&mut self, package: SharedPackage) -> crate::Result<()> {
+        self.ensure_cache_dir()?;
+        let name = package.borrow().name.clone();
+        if let Some(known) = self.named(&name).find(|known| *known == package) {
+            if SharedPackage::ptr_eq(&known, &package) {
+                return Ok(());
+            }
+            let (known, package) = (known.borrow(), package.borrow());
+            if !known.same_source(&package) {
+                eprintln!(
+                    "Warning: {} {} from {} is already known from {}, keeping the known package.",
+                    package.name,
+                    package.version,
+                    package.repository.as_deref().unwrap_or("a local package"),
+                    known.repository.as_deref().unwrap_or("a local package"),
+                );
+            }
             return Ok(());
         }
-        if let Some(tarball) = &package.borrow().local_path {
-            let mut tarball = File::open(tarball)?;
+        let local_path = package.borrow().local_path.clone();
+        if let Some(local_path) = local_path {
+            let local_path = self.resolve_local_path(&local_path);
             let destination = self.package_cache.join(package.borrow().get_filename());
             let mut destination = File::create(destination)?;
-            std::io::copy(&mut tarball, &mut destination)?;
+            if local_path.is_dir() {
+                // Unpacked package trees are packed up to be installed like tarballs.
+                package::pack_directory(&local_path, &package.borrow().files, destination)?;
+            } else {
+                std::io::copy(&mut File::open(local_path)?, &mut destination)?;
+            }
         }
         package.borrow_mut().local_path = None;
+        self.by_name
+            .entry(name)
+            .or_default()
+            .push(self.packages.len());
         self.packages.push(package);
         Ok(())
     }
 
+    /// Add a package that was installed without [apply](Database::apply),
+    /// providing the handle the database keeps for it. If the database
+    /// already knows the package, such as from a repository, the known
+    /// package takes the state and files of the installed one, and its
+    /// tarball is cached unless one already is.
+    pub(crate) fn import_installed(
+        &mut self,
+        package: SharedPackage,
+    ) -> crate::Result<SharedPackage> {
+        let local_path = package.borrow().local_path.clone();
+        self.import_package(package.clone())?;
+        let name = package.borrow().name.clone();
+        let known = self
+            .named(&name)
+            .find(|known| *known == package)
+            .expect("the package was just imported");
+        if !SharedPackage::ptr_eq(&known, &package) {
+            let installed = package.borrow();
+            let mut entry = known.borrow_mut();
+            entry.state = installed.state.clone();
+            entry.files = installed.files.clone();
+            let cached = self.cached_tarball(&entry);
+            if let Some(tarball) = local_path.filter(|_| !cached.exists()) {
+                std::fs::copy(self.resolve_local_path(&tarball), cached)?;
+            }
+        }
+        Ok(known)
+    }
+
     /// Load the package database from disk.
     pub fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
         let file = match File::open(&path) {
@@ -54,54 +310,1354 @@ impl Database {
                 _ => return Err(Error::IOError(err)),
             },
         };
-        Ok(serde_cbor::from_reader(file)?)
+        let mut database: Self = serde_cbor::from_reader(file)?;
+        database.index_packages();
+        database.attach(path.as_ref())?;
+        Ok(database)
+    }
+
+    /// Load the database from the path like [load](Database::load), but if
+    /// the file is corrupt, ask `choose` how to recover. It's given the error
+    /// and the backup written by the last save, if there is one. A restored
+    /// backup is written over the corrupt database. Starting fresh moves the
+    /// contents of the corrupt database aside, with `.corrupt` after its name,
+    /// leaving it empty until it's saved over, so that save keeps the backup.
+    pub fn load_or_recover(
+        path: impl AsRef<Path>,
+        choose: impl FnOnce(&Error, Option<&Path>) -> Recovery,
+    ) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let error = match Self::load(path) {
+            Err(error @ Error::SerializationError(_)) => error,
+            result => return result,
+        };
+        let backup = backup_path(path);
+        let existing_backup = Some(backup.as_path()).filter(|backup| backup.exists());
+        match choose(&error, existing_backup) {
+            Recovery::Restore if existing_backup.is_some() => {
+                let mut database: Self = serde_cbor::from_reader(File::open(&backup)?)?;
+                database.index_packages();
+                copy(&backup, path)?;
+                database.attach(path)?;
+                Ok(database)
+            }
+            Recovery::StartFresh => {
+                let mut database = Self::new_empty(PathBuf::new());
+                database.attach(path)?;
+                // It's already empty if starting fresh again before a save.
+                if path.metadata()?.len() > 0 {
+                    let mut corrupt = OsString::from(path);
+                    corrupt.push(".corrupt");
+                    copy(path, corrupt)?;
+                    File::create(path)?;
+                }
+                Ok(database)
+            }
+            Recovery::Restore | Recovery::Abort => Err(error),
+        }
     }
 
-    /// Save the current package database to the disk.
+    /// Record where the database was loaded from, so the files belonging to
+    /// it are found next to it.
+    fn attach(&mut self, path: &Path) -> crate::Result<()> {
+        self.log_path = Some(history::log_path(path));
+        self.pending_path = Some(history::pending_path(path));
+        if let Some(directory) = path.canonicalize()?.parent() {
+            self.base_dir = directory.to_owned();
+        }
+        Ok(())
+    }
+
+    /// Check that the database at the path can be saved over, without changing
+    /// it. Operations that change the database should check this before
+    /// making changes, so the changes are never made without being recorded.
+    pub fn check_writable(path: &Path) -> crate::Result<()> {
+        match OpenOptions::new().append(true).open(path) {
+            Ok(_) => Ok(()),
+            Err(source) => Err(Error::DatabaseNotWritable {
+                path: path.to_owned(),
+                source,
+            }),
+        }
+    }
+
+    /// Save the current package database to the disk. The database being
+    /// replaced, unless it's empty, is kept as the [backup](backup_path).
     pub fn save(&self, path: &Path) -> crate::Result<()> {
-        let file = File::create(path)?;
-        Ok(serde_cbor::to_writer(file, self)?)
+        // The new database replaces the old one in a single rename, so a
+        // crash while writing can't leave a half-written database behind.
+        let mut temporary = OsString::from(path);
+        temporary.push(".new");
+        let temporary = PathBuf::from(temporary);
+        serde_cbor::to_writer(File::create(&temporary)?, self)?;
+        // A corrupt database is emptied when starting fresh, so it never
+        // replaces the backup.
+        if path.metadata().is_ok_and(|metadata| metadata.len() > 0) {
+            copy(path, backup_path(path))?;
+        }
+        rename(&temporary, path)?;
+        Ok(())
     }
 
     /// Create an empty database. Should only be used on fresh installs.
     pub fn new_empty(package_cache: impl Into<PathBuf>) -> Self {
         Self {
             packages: vec![],
+            last_sync: None,
+            index_validators: BTreeMap::new(),
             package_cache: package_cache.into(),
+            log_path: None,
+            pending_path: None,
+            config: Config::default(),
+            cancelled: Arc::default(),
+            base_dir: PathBuf::new(),
+            by_name: HashMap::new(),
+            filesystem: os_filesystem(),
+        }
+    }
+
+    /// Set the directory relative [local paths](Package::local_path) are
+    /// resolved against. A loaded database uses the directory containing the
+    /// database file, and a new database uses the working directory.
+    pub fn set_base_dir(&mut self, base_dir: impl Into<PathBuf>) {
+        self.base_dir = base_dir.into();
+    }
+
+    /// Resolve the local path of a package. Absolute paths are used as they
+    /// are, and relative paths are relative to the base directory.
+    /// ```
+    /// # use mix::Database;
+    /// # use std::path::Path;
+    /// let mut database = Database::new_empty("cache");
+    /// database.set_base_dir("/srv/packages");
+    /// assert_eq!(
+    ///     database.resolve_local_path(Path::new("foo.tar.xz")),
+    ///     Path::new("/srv/packages/foo.tar.xz")
+    /// );
+    /// assert_eq!(
+    ///     database.resolve_local_path(Path::new("/tmp/foo.tar.xz")),
+    ///     Path::new("/tmp/foo.tar.xz")
+    /// );
+    /// ```
+    pub fn resolve_local_path(&self, local_path: &Path) -> PathBuf {
+        self.base_dir.join(local_path)
+    }
+
+    /// Provide the full metadata of a single package, installed or not.
+    pub fn info(&self, name: &str) -> crate::Result<PackageInfo> {
+        match self.get_package(&name) {
+            Some(package) => Ok(PackageInfo::from(&*package.borrow())),
+            None => Err(Error::PackageNotFound(vec![String::from(name)])),
+        }
+    }
+
+    /// Provide the paths of every file belonging to a package, within the
+    /// configured root. For a package that isn't installed, the files are read
+    /// from its tarball if it's in the package cache.
+    pub fn files(&self, name: &str) -> crate::Result<Vec<PathBuf>> {
+        let package = self
+            .get_package(&name)
+            .ok_or_else(|| Error::PackageNotFound(vec![String::from(name)]))?;
+        let package = package.borrow();
+        let files = if package.state != InstallState::Uninstalled {
+            package.files.clone()
+        } else if self.cached_tarball(&package).exists() {
+            Package::all_from_tarball_with_manifest_name(
+                self.open_package_tarball(&package)?,
+                self.config.manifest_filename(),
+            )?
+            .into_iter()
+            .find(|sub_package| sub_package.name == package.name)
+            .ok_or(Error::InvalidPackageError)?
+            .files
+        } else {
+            return Err(Error::PackageNotInstalled);
+        };
+        Ok(files
+            .iter()
+            .map(|file| self.config.root.join(file))
+            .collect())
+    }
+
+    /// Provide the names of the installed packages owning the given path,
+    /// which may be absolute (within the configured root) or relative to it.
+    pub fn owner_of(&self, path: &Path) -> Vec<String> {
+        let path = path.strip_prefix(&self.config.root).unwrap_or(path);
+        self.installed_names_matching(|file| file == path)
+    }
+
+    /// Provide the names of the installed packages with a file of the given
+    /// name, in any directory. This finds the owner of a file when only its
+    /// name is known, such as the package providing the `ls` command.
+    pub fn providers_of_file(&self, filename: &str) -> Vec<String> {
+        self.installed_names_matching(|file| {
+            file.file_name() == Some(std::ffi::OsStr::new(filename))
+        })
+    }
+
+    /// Provide the sorted names of the installed packages with any file matching the predicate.
+    fn installed_names_matching(&self, predicate: impl Fn(&Path) -> bool) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .iter()
+            .map(|package| package.borrow().clone())
+            .filter(|package| package.state != InstallState::Uninstalled)
+            .filter(|package| package.files.iter().any(|file| predicate(file)))
+            .map(|package| package.name)
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Change why the named package is installed. See
+    /// [Package::set_state](Package::set_state) for the allowed changes.
+    pub fn set_state(&self, name: &str, state: InstallState) -> crate::Result<()> {
+        let package = self.require_installed(name)?;
+        let result = package.borrow_mut().set_state(state);
+        result
+    }
+
+    /// Provide why the named package is installed: explicitly, with
+    /// [Manual](InstallState::Manual), or as a dependency, with
+    /// [Dependency](InstallState::Dependency).
+    /// ```
+    /// # use mix::{Database, InstallState, Package, Selections, SharedPackage, Version};
+    /// # let mut database = Database::new_empty(std::env::temp_dir().join("mix-install-reason"));
+    /// # let mut selections = Selections::default();
+    /// # selections.install.push(SharedPackage::new(Package::new("foo", Version::Unknown)));
+    /// # database.apply(selections)?;
+    /// if database.install_reason("foo")? == InstallState::Dependency {
+    ///     println!("foo can be removed once nothing depends on it");
+    /// }
+    /// # Ok::<(), mix::Error>(())
+    /// ```
+    pub fn install_reason(&self, name: &str) -> crate::Result<InstallState> {
+        let package = self.require_installed(name)?;
+        let state = package.borrow().state.clone();
+        Ok(state)
+    }
+
+    /// Change why the named package is installed, between
+    /// [Manual](InstallState::Manual) and [Dependency](InstallState::Dependency).
+    /// ```
+    /// # use mix::{Database, InstallState, Package, Selections, SharedPackage, Version};
+    /// # let mut database = Database::new_empty(std::env::temp_dir().join("mix-set-install-reason"));
+    /// # let mut selections = Selections::default();
+    /// # selections.install.push(SharedPackage::new(Package::new("foo", Version::Unknown)));
+    /// # database.apply(selections)?;
+    /// database.set_install_reason("foo", InstallState::Manual)?;
+    /// assert_eq!(database.install_reason("foo")?, InstallState::Manual);
+    /// // Uninstalling is not a reason, and goes through a removal instead.
+    /// assert!(database.set_install_reason("foo", InstallState::Uninstalled).is_err());
+    /// # Ok::<(), mix::Error>(())
+    /// ```
+    pub fn set_install_reason(&mut self, name: &str, reason: InstallState) -> crate::Result<()> {
+        self.set_state(name, reason)
+    }
+
+    /// Provide the files of the named installed package that are missing from
+    /// the filesystem, relative to the configured root.
+    pub fn missing_files(&self, name: &str) -> crate::Result<Vec<PathBuf>> {
+        let package = self.require_installed(name)?;
+        let missing = self.missing_files_of(&package.borrow());
+        Ok(missing)
+    }
+
+    /// Provide the files of the package missing from the filesystem the
+    /// database places files on.
+    fn missing_files_of(&self, package: &Package) -> Vec<PathBuf> {
+        package.missing_files(&self.config.root, &*self.filesystem)
+    }
+
+    /// Restore the files of the named installed package that are missing from
+    /// the filesystem, providing the files that were restored. See
+    /// [package::repair](package::repair).
+    pub fn repair(&self, name: &str) -> crate::Result<Vec<PathBuf>> {
+        let package = self.require_installed(name)?;
+        let missing = self.missing_files_of(&package.borrow());
+        if !missing.is_empty() {
+            package::repair(&package, self, &*self.filesystem, &missing)?;
+        }
+        Ok(missing)
+    }
+
+    /// Compare the installed files of the named package against its cached
+    /// tarball, providing every file that differs. See [package::audit](package::audit).
+    pub fn audit(&self, name: &str) -> crate::Result<Vec<package::FileDiscrepancy>> {
+        let package = self.require_installed(name)?;
+        let package = package.borrow();
+        let tarball = self.open_package_tarball(&package)?;
+        package::audit(&package, tarball, &self.config)
+    }
+
+    /// Get the installed version of the named package, or the error explaining why there isn't one.
+    fn require_installed(&self, name: &str) -> crate::Result<SharedPackage> {
+        self.installed_package(name)
+            .ok_or_else(|| match self.get_package(&name) {
+                Some(_) => Error::PackageNotInstalled,
+                None => Error::PackageNotFound(vec![String::from(name)]),
+            })
+    }
+
+    /// Provide the tree of everything the named package depends on, directly
+    /// or through other packages. The installed version of a package is
+    /// used when there is one, and the newest version otherwise.
+    pub fn dependency_tree(&self, name: &str) -> crate::Result<DependencyTree> {
+        if self.get_package(&name).is_none() {
+            return Err(Error::PackageNotFound(vec![String::from(name)]));
+        }
+        Ok(self.dependency_subtree(name, &mut HashSet::new()))
+    }
+
+    /// Build the tree of a package for [dependency_tree](Database::dependency_tree),
+    /// only expanding the packages that are not in `visited`.
+    fn dependency_subtree(&self, name: &str, visited: &mut HashSet<String>) -> DependencyTree {
+        let package = self
+            .iter()
+            .filter(|package| package.borrow().name == name)
+            .max_by_key(|package| {
+                let package = package.borrow();
+                (
+                    package.state != InstallState::Uninstalled,
+                    package.version.clone(),
+                )
+            })
+            .map(|package| package.borrow().clone());
+        let repeated = !visited.insert(String::from(name));
+        let dependencies = match &package {
+            Some(package) if !repeated => package
+                .dependencies
+                .iter()
+                .map(|dependency| self.dependency_subtree(dependency, visited))
+                .collect(),
+            _ => vec![],
+        };
+        DependencyTree {
+            name: String::from(name),
+            state: package.map(|package| package.state),
+            repeated,
+            dependencies,
+        }
+    }
+
+    /// Provide the newest version of the named package across every
+    /// repository, installed or not. [Unknown](Version::Unknown) versions
+    /// are only provided when no real version is known.
+    pub fn latest_version(&self, name: &str) -> Option<Version> {
+        // Unknown is smaller than every other version, so it's only ever the maximum on its own.
+        self.named(name)
+            .map(|package| package.borrow().version.clone())
+            .max()
+    }
+
+    /// Provide the changelog of the newest version of the named package, which
+    /// is downloaded if the package only links to it. Provides None if the
+    /// package has no changelog.
+    pub fn changelog(&self, name: &str) -> crate::Result<Option<String>> {
+        let newest = self
+            .named(name)
+            .max_by(|a, b| a.borrow().version.cmp(&b.borrow().version))
+            .ok_or_else(|| Error::PackageNotFound(vec![String::from(name)]))?;
+        let newest = newest.borrow();
+        match (&newest.changelog, &newest.changelog_url) {
+            (Some(changelog), _) => Ok(Some(changelog.clone())),
+            (None, Some(url)) => Ok(Some(net::get(url)?.text()?)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Provide every installed package that has a newer version available,
+    /// along with the newest available version of it.
+    pub fn outdated(&self) -> Vec<(SharedPackage, SharedPackage)> {
+        self.iter()
+            .filter(|package| package.borrow().state != InstallState::Uninstalled)
+            .filter_map(|installed| {
+                let (name, version) = {
+                    let installed = installed.borrow();
+                    (installed.name.clone(), installed.version.clone())
+                };
+                let latest = self.latest_version(&name)?;
+                if latest <= version {
+                    return None;
+                }
+                let newest = self
+                    .named(&name)
+                    .find(|candidate| candidate.borrow().version == latest)?;
+                Some((installed, newest))
+            })
+            .collect()
+    }
+
+    /// Check the database for logical problems between its packages. This
+    /// doesn't look at the filesystem, only at the recorded packages.
+    pub fn validate(&self) -> Vec<ConsistencyWarning> {
+        let packages = self.all_packages();
+        let installed: Vec<&Package> = packages
+            .iter()
+            .filter(|package| package.state != InstallState::Uninstalled)
+            .collect();
+        let mut warnings = vec![];
+        let mut duplicates = BTreeSet::new();
+        for name in self.by_name.keys() {
+            let versions: Vec<SharedPackage> = self.named(name).collect();
+            let is_duplicate = versions.iter().enumerate().any(|(index, package)| {
+                let package = package.borrow();
+                versions[..index].iter().any(|earlier| {
+                    let earlier = earlier.borrow();
+                    *earlier == *package
+                        || (earlier.state != InstallState::Uninstalled
+                            && package.state != InstallState::Uninstalled)
+                })
+            });
+            if is_duplicate {
+                duplicates.insert(name.clone());
+            }
+        }
+        warnings.extend(duplicates.into_iter().map(ConsistencyWarning::Duplicate));
+        for package in &installed {
+            for dependency in &package.dependencies {
+                if self.installed_package(dependency).is_none() {
+                    warnings.push(ConsistencyWarning::BrokenDependency {
+                        package: package.name.clone(),
+                        dependency: dependency.clone(),
+                    });
+                }
+            }
+            if package.state == InstallState::Dependency
+                && !installed
+                    .iter()
+                    .any(|other| other.dependencies.contains(&package.name))
+            {
+                warnings.push(ConsistencyWarning::Orphan(package.name.clone()));
+            }
+        }
+        warnings
+    }
+
+    /// Provide the changes that fix the broken installed packages: packages
+    /// with missing files are repaired, missing dependencies are installed
+    /// (from the newest package providing them), and packages with
+    /// dependencies that nothing available provides are removed.
+    pub fn compute_repairs(&self) -> Selections {
+        let mut selections = Selections::default();
+        let installed: Vec<SharedPackage> = self
+            .iter()
+            .filter(|package| package.borrow().state != InstallState::Uninstalled)
+            .collect();
+        // Packages that can't be installed, as their own dependencies can't be.
+        let mut unusable = HashSet::new();
+        loop {
+            let kept: Vec<SharedPackage> = installed
+                .iter()
+                .filter(|package| !selections.remove.contains(package))
+                .chain(&selections.install)
+                .cloned()
+                .collect();
+            let satisfied = |dependency: &str| {
+                kept.iter().any(|package| {
+                    let package = package.borrow();
+                    package.name == dependency
+                        || package
+                            .provides
+                            .iter()
+                            .any(|provided| provided == dependency)
+                })
+            };
+            let broken = kept.iter().find_map(|package| {
+                let dependency = package
+                    .borrow()
+                    .dependencies
+                    .iter()
+                    .find(|dependency| !satisfied(dependency))
+                    .cloned()?;
+                Some((package.clone(), dependency))
+            });
+            let (package, dependency) = match broken {
+                Some(broken) => broken,
+                None => break,
+            };
+            let candidate = self
+                .packages_providing(&dependency)
+                .into_iter()
+                .filter(|candidate| candidate.state == InstallState::Uninstalled)
+                .find(|candidate| !unusable.contains(&candidate.name))
+                .and_then(|candidate| {
+                    self.named(&candidate.name)
+                        .find(|known| *known.borrow() == candidate)
+                });
+            match candidate {
+                Some(candidate) => selections.install.push(candidate),
+                None if package.borrow().state == InstallState::Uninstalled => {
+                    unusable.insert(package.borrow().name.clone());
+                    selections.install.retain(|other| other != &package);
+                }
+                None => selections.remove.push(package),
+            }
+        }
+        selections.repair = installed
+            .into_iter()
+            .filter(|package| !selections.remove.contains(package))
+            .filter(|package| !self.missing_files_of(&package.borrow()).is_empty())
+            .collect();
+        selections
+    }
+
+    /// Provide every installed package that is not available from any synced
+    /// repository. These packages were installed from local files or were
+    /// [withdrawn](Package::withdrawn), and will never receive updates through mix.
+    pub fn foreign(&self) -> Vec<Package> {
+        let available: HashSet<String> = self
+            .iter()
+            .map(|package| package.borrow().clone())
+            .filter(|package| package.repository.is_some() && !package.withdrawn)
+            .map(|package| package.name)
+            .collect();
+        self.all_packages()
+            .into_iter()
+            .filter(|package| package.state != InstallState::Uninstalled)
+            .filter(|package| !available.contains(&package.name))
+            .collect()
+    }
+
+    /// Provide every package, installed or available, that is named the given
+    /// name or [provides](Package::provides) it, newest version first. These
+    /// are the candidates to choose from when a virtual package is needed.
+    pub fn packages_providing(&self, virtual_name: &str) -> Vec<Package> {
+        let mut providers: Vec<Package> = self
+            .packages()
+            .filter(|package| {
+                package.name == virtual_name
+                    || package
+                        .provides
+                        .iter()
+                        .any(|provided| provided == virtual_name)
+            })
+            .map(|package| package.clone())
+            .collect();
+        providers.sort_by(|a, b| b.version.cmp(&a.version));
+        providers
+    }
+
+    /// Compare the installed packages of this database against another database.
+    pub fn diff(&self, other: &Database) -> DatabaseDiff {
+        let installed = |database: &Database| -> BTreeMap<String, Version> {
+            database
+                .iter()
+                .map(|package| package.borrow().clone())
+                .filter(|package| package.state != InstallState::Uninstalled)
+                .map(|package| (package.name, package.version))
+                .collect()
+        };
+        let here = installed(self);
+        let there = installed(other);
+        let mut diff = DatabaseDiff::default();
+        for (name, version) in &here {
+            match there.get(name) {
+                None => diff.only_here.push(name.clone()),
+                Some(other_version) if other_version != version => diff.version_differs.push((
+                    name.clone(),
+                    version.clone(),
+                    other_version.clone(),
+                )),
+                Some(_) => (),
+            }
+        }
+        diff.only_there = there
+            .keys()
+            .filter(|name| !here.contains_key(*name))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    /// Download the package index of the repository, adding the packages it
+    /// provides for this architecture. An index that hasn't changed since the
+    /// last synchronization isn't downloaded again. Packages the repository
+    /// no longer provides are forgotten, unless they're installed, in which
+    /// case they're marked as [withdrawn](Package::withdrawn).
+    /// # Errors
+    /// The index must be signed by the [signing key](Config::signing_key) of
+    /// the repository. Repositories without a key are only synchronized if
+    /// [allow_unsigned](Config::allow_unsigned) is set.
+    pub fn synchronize(&mut self, repository: &dyn Repository) -> crate::Result<()> {
+        self.check_cancelled()?;
+        let name = repository.name().to_owned();
+        let validators = self
+            .index_validators
+            .get(&name)
+            .cloned()
+            .unwrap_or_default();
+        let subject = format!("the package index of {}", name);
+        let failed = |reason: &str| Error::SignatureVerificationFailed {
+            subject: subject.clone(),
+            reason: reason.to_owned(),
+        };
+        let fetched = match self.config.signing_key(&name) {
+            Some(key) => match repository.fetch_signed_index_if_changed(&validators)? {
+                Some((index, validators)) => {
+                    let signature = index
+                        .signature
+                        .ok_or_else(|| failed("the index isn't signed"))?;
+                    signature::verify_detached(
+                        &subject,
+                        key,
+                        &index.contents,
+                        &signature,
+                    )?;
+                    let packages = repository::parse_index(&name, &index.contents)?;
+                    Some((packages, validators))
+                }
+                None => None,
+            },
+            None if self.config.allow_unsigned => {
+                repository.fetch_index_if_changed(&validators)?
+            }
+            None => {
+                return Err(failed(
+                    "no signing key is configured for the repository, and unsigned repositories aren't allowed",
+                ))
+            }
+        };
+        if let Some((packages, validators)) = fetched {
+            let mut provided = vec![];
+            for mut package in packages {
+                if !package.supports_architecture(&self.config.architecture) {
+                    continue;
+                }
+                package.state = InstallState::Uninstalled;
+                package.local_path = None;
+                package.repository = Some(name.clone());
+                package.withdrawn = false;
+                provided.push(package.clone());
+                self.import_package(SharedPackage::new(package))?;
+            }
+            self.withdraw_missing(&name, &provided);
+            self.index_validators.insert(name, validators);
+        }
+        self.last_sync = Some(SystemTime::now());
+        Ok(())
+    }
+
+    /// Forget the packages from the repository that it no longer provides,
+    /// and mark the installed ones as withdrawn instead. Installed packages
+    /// are only withdrawn once the repository has no version of them at all,
+    /// so outdated packages aren't.
+    fn withdraw_missing(&mut self, repository: &str, provided: &[Package]) {
+        let from_repository = |package: &Package| package.repository.as_deref() == Some(repository);
+        for package in &self.packages {
+            let mut package = package.borrow_mut();
+            if from_repository(&package) {
+                package.withdrawn = !provided
+                    .iter()
+                    .any(|provided| provided.name == package.name);
+            }
+        }
+        let before = self.packages.len();
+        self.packages.retain(|package| {
+            let package = package.borrow();
+            !from_repository(&package)
+                || package.state != InstallState::Uninstalled
+                || provided.contains(&package)
+        });
+        if self.packages.len() != before {
+            self.index_packages();
+        }
+    }
+
+    /// Provide every configured repository.
+    pub fn repositories(&self) -> Vec<Box<dyn Repository>> {
+        self.config
+            .repositories
+            .iter()
+            .map(|repository| self.open_repository(repository))
+            .collect()
+    }
+
+    /// Provide the repository the package came from.
+    pub fn repository_of(&self, package: &Package) -> crate::Result<Box<dyn Repository>> {
+        package
+            .repository
+            .as_deref()
+            .and_then(|repository| self.config.repository(repository))
+            .map(|repository| self.open_repository(repository))
+            .ok_or_else(|| Error::NoMirrors(package.name.clone()))
+    }
+
+    /// Provide the backend for a configured repository. A repository whose
+    /// first mirror is a `file://` URL or a plain path is read from that directory.
+    fn open_repository(&self, repository: &RepositoryConfig) -> Box<dyn Repository> {
+        match repository
+            .mirrors
+            .first()
+            .and_then(|mirror| local_mirror(mirror))
+        {
+            Some(directory) => Box::new(DirectoryRepository::new(&repository.name, directory)),
+            None => Box::new(HttpRepository::from_config(
+                repository,
+                self.config.mirror_order,
+            )),
         }
     }
 
-    /// Handle the operation, using this database.
+    /// Provide how long ago the package lists were last synchronized, or None
+    /// if they never have been.
+    pub fn sync_age(&self) -> Option<Duration> {
+        self.last_sync.map(|last_sync| {
+            SystemTime::now()
+                .duration_since(last_sync)
+                .unwrap_or_default()
+        })
+    }
+
+    /// Provide the configuration in use.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Provide the flag that cancels the current operation once set. Operations
+    /// only stop between packages, so a cancelled operation never leaves a
+    /// package half installed or half removed.
+    pub fn cancellation_token(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+
+    /// Fail with [Aborted](Error::Aborted) if the operation was cancelled.
+    pub(crate) fn check_cancelled(&self) -> crate::Result<()> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(Error::Aborted);
+        }
+        Ok(())
+    }
+
+    /// Use the given configuration for future operations.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Provide the filesystem the files of packages are placed onto.
+    pub fn filesystem(&self) -> &Arc<dyn FileSystem> {
+        &self.filesystem
+    }
+
+    /// Place the files of packages onto the given filesystem instead of the
+    /// real one, such as an [InMemoryFileSystem](crate::filesystem::InMemoryFileSystem).
+    pub fn set_filesystem(&mut self, filesystem: Arc<dyn FileSystem>) {
+        self.filesystem = filesystem;
+    }
+
+    /// Handle the operation, using this database. The changes are recorded in
+    /// the history log next to the database file. Upgrades that aren't newer
+    /// than the installed version are [skipped](selection::skip_stale_upgrades)
+    /// and not recorded.
     pub fn apply(&mut self, selections: Selections) -> crate::Result<()> {
-        package::install(&selections.install, self)?;
-        package::remove(&selections.remove, self)?;
-        package::update(&selections.upgrade, self)?;
-        // TODO: Handle downgrades. For now, this is just warned on.
-        eprintln!(
-            "Not downgrading the following packages (Not yet implemented): {:?}",
-            &selections.downgrade
-        );
+        self.apply_with_progress(selections, &mut |_| {})
+    }
+
+    /// Handle the operation like [apply](Database::apply), calling `on_file`
+    /// with each file placed, relative to the root. This shows progress
+    /// within large packages, which take a while to install.
+    pub fn apply_with_progress(
+        &mut self,
+        mut selections: Selections,
+        on_file: &mut (dyn FnMut(&Path) + Send),
+    ) -> crate::Result<()> {
+        selection::skip_stale_upgrades(&mut selections, self);
+        let transaction = Transaction::from_selections(&selections);
+        let repairs: Vec<(&SharedPackage, Vec<PathBuf>)> = selections
+            .repair
+            .iter()
+            .map(|package| (package, self.missing_files_of(&package.borrow())))
+            .collect();
+        // Hooks need the files of removed packages, which are gone afterwards.
+        let touched_files: Vec<PathBuf> = selections
+            .install
+            .iter()
+            .chain(&selections.remove)
+            .chain(&selections.upgrade)
+            .flat_map(|package| package.borrow().files.clone())
+            .chain(repairs.iter().flat_map(|(_, missing)| missing.clone()))
+            .collect();
+        self.check_cancelled()?;
+        // Tarballs from repositories are downloaded before anything changes.
+        let downloads: Vec<SharedPackage> = selections
+            .install
+            .iter()
+            .chain(&selections.upgrade)
+            .filter(|package| {
+                let package = package.borrow();
+                package.repository.is_some()
+                    && package.local_path.is_none()
+                    && !package.files.is_empty()
+            })
+            .cloned()
+            .collect();
+        self.fetch_all(&downloads)?;
+        self.begin_transaction(&transaction)?;
+        let install = selection::install_order(&selections.install)?;
+        // Removing first frees the files of conflicting packages being replaced.
+        let filesystem = self.filesystem.clone();
+        package::remove(&selections.remove, self, &*filesystem)?;
+        package::install(&install, &selections.explicit, self, &*filesystem, on_file)?;
+        package::update(&selections.upgrade, self, &*filesystem, on_file)?;
+        for (package, missing) in &repairs {
+            package::repair(package, self, &*filesystem, missing)?;
+        }
+        for package in &selections.upgrade {
+            let name = package.borrow().name.clone();
+            if let Err(error) = self.prune_cache(&name) {
+                eprintln!(
+                    "Warning: failed to remove old versions of {} from the download cache: {}",
+                    name, error
+                );
+            }
+        }
+        // TODO: Handle downgrades. For now, the CLI warns about them.
+        self.finish_transaction(&transaction, &touched_files)
+    }
+
+    /// Record the transaction as pending before any of its changes are made,
+    /// so it can be resumed or rolled back if mix is interrupted.
+    pub(crate) fn begin_transaction(&self, transaction: &Transaction) -> crate::Result<()> {
+        if let Some(pending_path) = &self.pending_path {
+            if !transaction.changes.is_empty() {
+                history::write_pending(pending_path, transaction)?;
+            }
+        }
         Ok(())
     }
 
-    /// Get the path of the package within the package cache.
+    /// Log the transaction once its changes are made, run the hooks it
+    /// triggers, and forget that it was pending.
+    pub(crate) fn finish_transaction(
+        &self,
+        transaction: &Transaction,
+        touched_files: &[PathBuf],
+    ) -> crate::Result<()> {
+        self.log_transaction(transaction);
+        let touched_packages: Vec<String> = transaction
+            .changes
+            .iter()
+            .map(|change| change.name.clone())
+            .collect();
+        hooks::run_triggered(&self.config.hooks, &touched_packages, touched_files);
+        self.discard_pending()
+    }
+
+    /// Provide the transaction that was being applied when mix was
+    /// interrupted, if there is one.
+    pub fn pending_transaction(&self) -> crate::Result<Option<Transaction>> {
+        match &self.pending_path {
+            Some(pending_path) => history::read_pending(pending_path),
+            None => Ok(None),
+        }
+    }
+
+    /// Forget about the pending transaction, leaving its changes as they are.
+    pub fn discard_pending(&self) -> crate::Result<()> {
+        match &self.pending_path {
+            Some(pending_path) => history::clear_pending(pending_path),
+            None => Ok(()),
+        }
+    }
+
+    /// Compute the selections that finish the changes of an interrupted
+    /// transaction which were not made yet.
+    pub fn resume_selections(&self, transaction: &Transaction) -> crate::Result<Selections> {
+        let mut selections = Selections::default();
+        for change in &transaction.changes {
+            let package = self.find_change(change)?;
+            let installed = package.borrow().state != InstallState::Uninstalled;
+            match change.action {
+                Action::Install if !installed => {
+                    if change.state == InstallState::Manual {
+                        selections.explicit.push(package.clone());
+                    }
+                    selections.install.push(package)
+                }
+                Action::Remove if installed => selections.remove.push(package),
+                Action::Upgrade if !installed => selections.upgrade.push(package),
+                Action::Downgrade if !installed => selections.downgrade.push(package),
+                Action::Repair if installed => selections.repair.push(package),
+                _ => {}
+            }
+        }
+        Ok(selections)
+    }
+
+    /// Compute the selections that revert the changes an interrupted
+    /// transaction already made.
+    /// # Todo
+    /// Upgrades and downgrades can't be reverted until downgrades are applied.
+    pub fn rollback_selections(&self, transaction: &Transaction) -> crate::Result<Selections> {
+        let mut selections = Selections::default();
+        for change in &transaction.changes {
+            let package = self.find_change(change)?;
+            let installed = package.borrow().state != InstallState::Uninstalled;
+            match change.action {
+                Action::Install if installed => selections.remove.push(package),
+                Action::Remove if !installed => {
+                    if change.state == InstallState::Manual {
+                        selections.explicit.push(package.clone());
+                    }
+                    selections.install.push(package)
+                }
+                Action::Upgrade | Action::Downgrade if installed => {
+                    return Err(Error::CannotUndo(format!(
+                        "reverting the {} of {} is not supported",
+                        change.action, change.name
+                    )))
+                }
+                _ => {}
+            }
+        }
+        Ok(selections)
+    }
+
+    /// Find the exact package a change of a transaction was made to.
+    fn find_change(&self, change: &history::Change) -> crate::Result<SharedPackage> {
+        self.named(&change.name)
+            .find(|package| package.borrow().version == change.version)
+            .ok_or_else(|| Error::PackageNotFound(vec![change.name.clone()]))
+    }
+
+    /// Compute the selections that revert the most recent transaction in the
+    /// history log. Packages that were installed are removed, and packages
+    /// that were removed are installed again with their previous install
+    /// state.
+    /// # Errors
+    /// Nothing is changed if the transaction can't be fully reverted, such as
+    /// when a needed tarball is missing from the package cache.
+    /// # Todo
+    /// Upgrades and downgrades can't be reverted until downgrades are applied.
+    pub fn undo_selections(&self) -> crate::Result<Selections> {
+        let no_transaction = || Error::CannotUndo(String::from("there is no transaction to undo"));
+        let log_path = self.log_path.as_ref().ok_or_else(no_transaction)?;
+        let transaction = history::read(log_path)?.pop().ok_or_else(no_transaction)?;
+        let mut selections = Selections::default();
+        for change in transaction.changes {
+            let package = self
+                .get_package(&change.name)
+                .ok_or_else(|| Error::PackageNotFound(vec![change.name.clone()]))?;
+            if package.borrow().version != change.version {
+                return Err(Error::CannotUndo(format!(
+                    "{} is no longer at version {}",
+                    change.name, change.version
+                )));
+            }
+            match change.action {
+                Action::Install => selections.remove.push(package),
+                Action::Remove => {
+                    if change.state == InstallState::Manual {
+                        selections.explicit.push(package.clone());
+                    }
+                    selections.install.push(package);
+                }
+                // Restored files were missing before, so there's nothing to revert.
+                Action::Repair => {}
+                action => {
+                    return Err(Error::CannotUndo(format!(
+                        "reverting the {} of {} is not supported",
+                        action, change.name
+                    )))
+                }
+            }
+        }
+        for package in selections.install.iter().chain(&selections.remove) {
+            let tarball = self.cached_tarball(&package.borrow());
+            if !tarball.exists() {
+                return Err(Error::FileNotFound(tarball));
+            }
+        }
+        Ok(selections)
+    }
+
+    /// Revert the most recent transaction in the history log. See
+    /// [undo_selections](Database::undo_selections) for details.
+    pub fn undo(&mut self) -> crate::Result<()> {
+        let selections = self.undo_selections()?;
+        self.apply(selections)
+    }
+
+    /// Append the transaction to the history log. Failing to write the log
+    /// only produces a warning, as the changes have already been made.
+    fn log_transaction(&self, transaction: &Transaction) {
+        if transaction.changes.is_empty() {
+            return;
+        }
+        if let Some(log_path) = &self.log_path {
+            if let Err(error) = history::append(log_path, transaction) {
+                eprintln!(
+                    "Warning: failed to write to the history log {}: {}",
+                    log_path.display(),
+                    error
+                );
+            }
+        }
+    }
+
+    /// Create the package cache and download cache directories if they don't
+    /// exist yet.
+    pub fn ensure_cache_dir(&self) -> crate::Result<()> {
+        for cache in [&*self.package_cache, self.download_cache()] {
+            create_dir_all(cache).map_err(|source| Error::CacheDirectoryError {
+                path: cache.to_owned(),
+                source,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Use the given directory as the package cache.
+    pub fn set_package_cache(&mut self, package_cache: impl Into<PathBuf>) {
+        self.package_cache = package_cache.into();
+    }
+
+    /// Get the directory downloaded tarballs are stored in, which is the
+    /// package cache unless [configured](Config::download_cache) otherwise.
+    pub fn download_cache(&self) -> &Path {
+        self.config
+            .download_cache
+            .as_deref()
+            .unwrap_or(&self.package_cache)
+    }
+
+    /// Get the path the package's tarball has within the download cache.
+    pub(crate) fn downloaded_tarball(&self, package: &Package) -> PathBuf {
+        self.download_cache().join(package.get_filename())
+    }
+
+    /// Get the path of the package's tarball, which is in the download cache
+    /// if it was downloaded, and in the package cache otherwise.
+    pub(crate) fn cached_tarball(&self, package: &Package) -> PathBuf {
+        let downloaded = self.downloaded_tarball(package);
+        if downloaded.exists() {
+            downloaded
+        } else {
+            self.package_cache.join(package.get_filename())
+        }
+    }
+
+    /// Check if the package's tarball must be signed, which is when packages
+    /// are [verified](Config::verify_packages) and it's from a repository.
+    pub(crate) fn verifies_signature(&self, package: &Package) -> bool {
+        self.config.verify_packages && package.repository.is_some()
+    }
+
+    /// Check if the package's tarball is in the package or download cache,
+    /// and matches the package's checksum if it has one. Tarballs that must
+    /// be signed also need their signature.
+    pub(crate) fn has_cached_tarball(&self, package: &Package) -> crate::Result<bool> {
+        let tarball = self.cached_tarball(package);
+        if !tarball.exists()
+            || (self.verifies_signature(package) && !package::signature_path(&tarball).exists())
+        {
+            return Ok(false);
+        }
+        Ok(match &package.checksum {
+            Some(expected) => {
+                expected.eq_ignore_ascii_case(&package::checksum(File::open(&tarball)?)?)
+            }
+            None => true,
+        })
+    }
+
+    /// Download every package that isn't already in the package or download cache,
+    /// providing the packages that were downloaded. Cached tarballs that don't
+    /// match their checksum are downloaded again.
+    /// # Errors
+    /// When the tarballs must be [signed](Config::verify_packages), each one,
+    /// whether downloaded or already cached, is checked against its signature,
+    /// and a missing or bad signature gives a
+    /// [SignatureVerificationFailed](Error::SignatureVerificationFailed).
+    pub fn fetch_all(&self, packages: &[SharedPackage]) -> crate::Result<Vec<SharedPackage>> {
+        self.ensure_cache_dir()?;
+        let mut fetched = vec![];
+        for package in packages {
+            self.check_cancelled()?;
+            if !self.has_cached_tarball(&package.borrow())? {
+                let repository = self.repository_of(&package.borrow())?;
+                package::fetch(package, repository.as_ref(), self)?;
+                fetched.push(package.clone());
+            }
+            let package = package.borrow();
+            if self.verifies_signature(&package) {
+                self.verify_tarball_signature(&package, &self.cached_tarball(&package))?;
+            }
+        }
+        Ok(fetched)
+    }
+
+    /// Download a package tarball from a URL into the download cache, providing
+    /// the package it contains. If a checksum is given, the tarball must match it.
+    /// The package is not added to the database until it's installed.
+    pub fn fetch_url(&self, url: &str, checksum: Option<&str>) -> crate::Result<SharedPackage> {
+        self.fetch_reader(net::get(url)?, checksum)
+    }
+
+    /// Copy a package tarball from a reader, such as stdin, into the download
+    /// cache, and provide the package it contains. The tarball is only read
+    /// once, so it can come from a pipe. If a checksum is given, the tarball
+    /// must match it. The package is not added to the database until it's
+    /// installed.
+    pub fn fetch_reader(
+        &self,
+        mut tarball: impl std::io::Read,
+        checksum: Option<&str>,
+    ) -> crate::Result<SharedPackage> {
+        self.ensure_cache_dir()?;
+        // The name of the package isn't known until the tarball is read.
+        let partial = self
+            .download_cache()
+            .join(format!("incoming-{}.part", std::process::id()));
+        std::io::copy(&mut tarball, &mut File::create(&partial)?)?;
+        let found = package::checksum(File::open(&partial)?)?;
+        if let Some(expected) = checksum {
+            if !expected.eq_ignore_ascii_case(&found) {
+                remove_file(&partial)?;
+                return Err(Error::ChecksumMismatch {
+                    expected: expected.to_owned(),
+                    found,
+                });
+            }
+        }
+        let mut package = Package::from_tarball_with_manifest_name(
+            File::open(&partial)?,
+            self.config.manifest_filename(),
+        )?;
+        package.checksum = Some(found);
+        rename(&partial, self.downloaded_tarball(&package))?;
+        Ok(SharedPackage::new(package))
+    }
+
+    /// Open the tarball of the package from the download cache or the package cache.
+    /// # Errors
+    /// A tarball that doesn't match the package's checksum gives a
+    /// [ChecksumMismatch](Error::ChecksumMismatch) before anything is read
+    /// from it, so none of its files are placed. Signatures aren't checked
+    /// here, but once when the tarball is [fetched](Database::fetch_all).
     pub fn open_package_tarball(&self, package: &Package) -> crate::Result<impl std::io::Read> {
-        let filename = self.package_cache.join(package.get_filename());
-        if filename.exists() {
-            return Ok(File::open(filename)?);
+        let filename = self.cached_tarball(package);
+        if !filename.exists() {
+            return Err(Error::FileNotFound(filename));
+        }
+        if let Some(expected) = &package.checksum {
+            let found = package::checksum(File::open(&filename)?)?;
+            if !expected.eq_ignore_ascii_case(&found) {
+                return Err(Error::ChecksumMismatch {
+                    expected: expected.clone(),
+                    found,
+                });
+            }
+        }
+        Ok(File::open(filename)?)
+    }
+
+    /// Check the tarball of a package from a repository against the
+    /// signature next to it, using the signing key of the repository.
+    fn verify_tarball_signature(&self, package: &Package, tarball: &Path) -> crate::Result<()> {
+        let subject = format!("the tarball of {}", package.name);
+        let failed = |reason: String| Error::SignatureVerificationFailed {
+            subject: subject.clone(),
+            reason,
+        };
+        let repository = package.repository.as_deref().unwrap_or_default();
+        let key = self.config.signing_key(repository).ok_or_else(|| {
+            failed(format!(
+                "no signing key is configured for the repository {}",
+                repository
+            ))
+        })?;
+        let signature = package::signature_path(tarball);
+        if !signature.exists() {
+            return Err(failed(String::from("the tarball isn't signed")));
+        }
+        signature::verify_detached_file(&subject, key, tarball, &signature)
+    }
+
+    /// Remove the downloaded tarballs of a package beyond the newest
+    /// [keep_versions](Config::keep_versions), providing the number of bytes
+    /// freed. The tarball of the installed version is never removed.
+    pub fn prune_cache(&self, package_name: &str) -> crate::Result<u64> {
+        let installed = self
+            .installed_package(package_name)
+            .map(|package| self.downloaded_tarball(&package.borrow()));
+        let prefix = format!("{}-", package_name);
+        let mut cached = vec![];
+        let entries = match read_dir(self.download_cache()) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            let filename = path.file_name().unwrap_or_default().to_string_lossy();
+            if !filename.starts_with(&prefix) || !filename.ends_with(".tar.xz") {
+                continue;
+            }
+            // Another package's name may start with this one's, so check the
+            // manifest. Unreadable tarballs are left for the user to inspect.
+            let packages = match Package::all_from_tarball_with_manifest_name(
+                File::open(&path)?,
+                self.config.manifest_filename(),
+            ) {
+                Ok(packages) => packages,
+                Err(_) => continue,
+            };
+            if let Some(package) = packages
+                .into_iter()
+                .find(|package| package.name == package_name)
+            {
+                cached.push((package.version, path));
+            }
+        }
+        cached.sort_by(|(version1, _), (version2, _)| version2.cmp(version1));
+        let mut freed = 0;
+        for (_, path) in cached.into_iter().skip(self.config.keep_versions) {
+            if Some(&path) == installed.as_ref() {
+                continue;
+            }
+            freed += path.metadata()?.len();
+            remove_file(path)?;
         }
-        todo!()
+        Ok(freed)
     }
 
-    /// Provide a way to iterate over all packages.
-    /// # Todo:
-    /// This is not an ideal way to handle it, but this commit is large enough
-    /// that I'm going to use the path of least resistance. This should ideally
-    /// iterate over immutable references.
+    /// Remove old versions of every known package from the download cache,
+    /// following the same retention policy as [prune_cache](Database::prune_cache).
+    /// Provides the number of bytes freed.
+    pub fn clean_cache(&self) -> crate::Result<u64> {
+        let mut freed = 0;
+        for package in self.iter() {
+            let name = package.borrow().name.clone();
+            freed += self.prune_cache(&name)?;
+        }
+        Ok(freed)
+    }
+
+    /// Borrow every package in the database, without cloning them. Each
+    /// package stays borrowed until its guard is dropped, so hold on to them
+    /// only while reading.
+    pub fn packages(&self) -> impl Iterator<Item = RwLockReadGuard<'_, Package>> + '_ {
+        self.packages.iter().map(|package| package.borrow())
+    }
+
+    /// Provide a copy of every package. Prefer [packages](Database::packages)
+    /// when the packages only need to be read.
     pub fn all_packages(&self) -> Vec<Package> {
         self.packages
             .iter()
             .map(|package| package.borrow().clone())
             .collect()
     }
+
+    /// Write a lockfile pinning the version and tarball checksum of every
+    /// installed package.
+    pub fn write_lockfile(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let mut lockfile = Lockfile::default();
+        for package in self.iter() {
+            let package = package.borrow();
+            if package.state == InstallState::Uninstalled {
+                continue;
+            }
+            let checksum = match &package.checksum {
+                Some(checksum) => checksum.clone(),
+                None => package::checksum(self.open_package_tarball(&package)?)?,
+            };
+            lockfile.packages.push(LockedPackage {
+                name: package.name.clone(),
+                version: package.version.to_string(),
+                checksum,
+                state: package.state.clone(),
+            });
+        }
+        lockfile.save(path)
+    }
+
+    /// Install exactly the packages pinned by a lockfile, with the install
+    /// state each had when the lockfile was written. Every pinned package is
+    /// checked before anything is installed, so a package whose version isn't
+    /// available, whose tarball differs from the lockfile, or which is already
+    /// installed at another version aborts the whole install. Tarballs from
+    /// repositories are downloaded to be checked.
+    pub fn install_from_lockfile(&mut self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let lockfile = Lockfile::load(path)?;
+        let mut selections = Selections::default();
+        let mut packages_not_found = vec![];
+        for locked in &lockfile.packages {
+            let versions: Vec<SharedPackage> = self.named(&locked.name).collect();
+            if let Some(installed) = versions
+                .iter()
+                .find(|package| package.borrow().state != InstallState::Uninstalled)
+            {
+                let version = installed.borrow().version.to_string();
+                if version != locked.version {
+                    return Err(Error::LockedVersionInstalled {
+                        package: locked.name.clone(),
+                        expected: locked.version.clone(),
+                        installed: version,
+                    });
+                }
+            }
+            let package = match versions
+                .iter()
+                .find(|package| package.borrow().version.to_string() == locked.version)
+            {
+                Some(package) => package.clone(),
+                None => match versions.last() {
+                    Some(available) => {
+                        return Err(Error::LockfileMismatch {
+                            package: locked.name.clone(),
+                            expected: locked.version.clone(),
+                            found: available.borrow().version.to_string(),
+                        })
+                    }
+                    None => {
+                        packages_not_found.push(locked.name.clone());
+                        continue;
+                    }
+                },
+            };
+            let downloads = {
+                let package = package.borrow();
+                package.repository.is_some() && package.local_path.is_none()
+            };
+            if downloads {
+                self.fetch_all(std::slice::from_ref(&package))?;
+            }
+            let checksum = package::checksum(self.open_package_tarball(&package.borrow())?)?;
+            if checksum != locked.checksum {
+                return Err(Error::ChecksumMismatch {
+                    expected: locked.checksum.clone(),
+                    found: checksum,
+                });
+            }
+            if package.borrow().state == InstallState::Uninstalled {
+                if locked.state == InstallState::Manual {
+                    selections.explicit.push(package.clone());
+                }
+                selections.install.push(package);
+            }
+        }
+        if !packages_not_found.is_empty() {
+            return Err(Error::PackageNotFound(packages_not_found));
+        }
+        self.apply(selections)
+    }
+}
+
+/// Provide the path of the backup [save](Database::save) keeps of the
+/// previous version of the database at the given path.
+/// ```
+/// # use mix::database::backup_path;
+/// # use std::path::{Path, PathBuf};
+/// assert_eq!(
+///     backup_path(Path::new("/var/lib/mix/mix.db")),
+///     PathBuf::from("/var/lib/mix/.mix.db.bak")
+/// );
+/// ```
+pub fn backup_path(database: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(database.file_name().unwrap_or_default());
+    name.push(".bak");
+    database.with_file_name(name)
 }
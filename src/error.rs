@@ -1,3 +1,4 @@
+use crate::InstallState;
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -16,12 +17,33 @@ pub enum MixError {
     /// The requested file was not found.
     #[error("File not found: {0}")]
     FileNotFound(PathBuf),
+    /// The package cache directory could not be created.
+    #[error("Failed to create the package cache directory {path}")]
+    CacheDirectoryError {
+        /// The package cache directory.
+        path: PathBuf,
+        /// The reason the directory could not be created.
+        #[source]
+        source: std::io::Error,
+    },
+    /// The database file can't be written to, so changes couldn't be saved.
+    #[error("The database {path} is not writable")]
+    DatabaseNotWritable {
+        /// The database file.
+        path: PathBuf,
+        /// The reason the file can't be written to.
+        #[source]
+        source: std::io::Error,
+    },
     /// An IOError that does not receive special treatment occurred.
     #[error(transparent)]
     IOError(#[from] std::io::Error),
     /// An error when serializing or deserializing.
     #[error(transparent)]
     SerializationError(#[from] serde_cbor::error::Error),
+    /// An error when serializing or deserializing JSON.
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
     /// The manifest parsed successfully but contained invalid information.
     #[error("Invalid manifest type {0}.")]
     InvalidManifestError(toml::Value),
@@ -34,7 +56,149 @@ pub enum MixError {
     /// There was an error with a web request.
     #[error(transparent)]
     RequestError(#[from] reqwest::Error),
+    /// The configuration file failed to parse.
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    /// The lockfile failed to parse.
+    #[error("Invalid lockfile: {0}")]
+    InvalidLockfile(String),
+    /// The lockfile could not be serialized.
+    #[error(transparent)]
+    LockfileSerializeError(#[from] toml::ser::Error),
+    /// The available version of a package differs from the version pinned by the lockfile.
+    #[error("Package {package} is pinned to version {expected}, but version {found} is available")]
+    LockfileMismatch {
+        /// The name of the package.
+        package: String,
+        /// The version pinned by the lockfile.
+        expected: String,
+        /// The version that is available.
+        found: String,
+    },
+    /// A package is installed at a different version than the lockfile pins.
+    #[error(
+        "Package {package} is pinned to version {expected}, but version {installed} is installed"
+    )]
+    LockedVersionInstalled {
+        /// The name of the package.
+        package: String,
+        /// The version pinned by the lockfile.
+        expected: String,
+        /// The version that is installed.
+        installed: String,
+    },
+    /// The checksum of a package tarball did not match the expected checksum.
+    #[error("Checksum mismatch: expected {expected}, found {found}")]
+    ChecksumMismatch {
+        /// The expected checksum.
+        expected: String,
+        /// The checksum of the tarball.
+        found: String,
+    },
+    /// Two packages that conflict with each other would be installed together.
+    #[error("Packages {a} and {b} conflict and can't be installed together")]
+    ConflictingPackages {
+        /// The name of the first package.
+        a: String,
+        /// The name of the second package.
+        b: String,
+    },
+    /// An entry of a package tarball would be placed outside of the root.
+    #[error("Refusing to place {}, as it's outside of the root", .0.display())]
+    UnsafePath(PathBuf),
+    /// The external package builder failed to build a package.
+    #[error("Failed to build package {package}:\n{log}")]
+    BuildFailed {
+        /// The name of the package being built.
+        package: String,
+        /// The output of the builder.
+        log: String,
+    },
+    /// The most recent transaction could not be reverted.
+    #[error("Cannot undo: {0}")]
+    CannotUndo(String),
+    /// The packages depend on each other in a cycle. The first package is
+    /// repeated at the end of the cycle.
+    #[error("Dependency cycle: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+    /// A package can't be removed, as installed packages that aren't being
+    /// removed depend on it.
+    #[error("Cannot remove {package}, as it is required by {}", .dependents.join(", "))]
+    RequiredBy {
+        /// The name of the package.
+        package: String,
+        /// The names of the installed packages depending on it.
+        dependents: Vec<String>,
+    },
+    /// A package needs to be downloaded, but its repository has no mirrors configured.
+    #[error("No mirrors are configured for {0}")]
+    NoMirrors(String),
+    /// Every mirror failed to provide the file.
+    #[error("Every mirror failed to provide {0}")]
+    MirrorsFailed(String),
+    /// Some of the repositories couldn't be synchronized. The others were.
+    #[error("Failed to synchronize {}", .0.join(", "))]
+    SyncFailed(Vec<String>),
+    /// The install state of a package can't be changed this way.
+    #[error("Cannot change {package} from {from} to {to}")]
+    InvalidStateTransition {
+        /// The name of the package.
+        package: String,
+        /// The current state of the package.
+        from: InstallState,
+        /// The requested state.
+        to: InstallState,
+    },
+    /// The package was built for a different architecture than this system.
+    #[error("Package {package} is built for {found}, but this system is {expected}")]
+    ArchitectureMismatch {
+        /// The name of the package.
+        package: String,
+        /// The architecture of this system.
+        expected: String,
+        /// The architecture of the package.
+        found: String,
+    },
+    /// There isn't enough free space in the root to install the packages.
+    #[error("Not enough free space: {required} bytes are needed, but only {available} bytes are available")]
+    InsufficientSpace {
+        /// The number of bytes the packages need once installed.
+        required: u64,
+        /// The number of bytes available in the root.
+        available: u64,
+    },
+    /// A package tarball holds an entry of a type that can't be placed, such
+    /// as a device or a FIFO.
+    #[error("Unsupported entry {} of type {kind}", .path.display())]
+    UnsupportedEntry {
+        /// The path of the entry within the tarball.
+        path: PathBuf,
+        /// The type of the entry.
+        kind: String,
+    },
+    /// A version string could not be understood.
+    #[error("Invalid version {0}")]
+    InvalidVersion(String),
+    /// The package index of a repository could not be read.
+    #[error("Invalid package index for {repository}: {reason}")]
+    InvalidIndex {
+        /// The name of the repository.
+        repository: String,
+        /// Why the index is invalid.
+        reason: String,
+    },
+    /// A signature didn't match what it signs, or was missing when one is required.
+    #[error("Signature verification failed for {subject}: {reason}")]
+    SignatureVerificationFailed {
+        /// What was signed, such as the package index of a repository.
+        subject: String,
+        /// Why the signature isn't good.
+        reason: String,
+    },
     /// The user chose not to follow through with the operation.
     #[error("Aborting.")]
     Aborted,
+    /// The options given to the command can't be used together.
+    #[error("Invalid arguments: {0}")]
+    InvalidArguments(String),
 }
@@ -0,0 +1,345 @@
+use crate::util;
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    fs::{self, OpenOptions, Permissions},
+    io::{self, Read},
+    os::unix::{fs::symlink, prelude::*},
+    path::{Component, Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+/// The operations used to place and remove the files of packages. Packages
+/// are installed through this trait rather than straight onto the disk, so
+/// installing can be tried out without touching the real filesystem.
+/// ```
+/// # use mix::filesystem::{FileSystem, InMemoryFileSystem, Node};
+/// # use std::path::Path;
+/// let filesystem = InMemoryFileSystem::default();
+/// filesystem.create_dir(Path::new("/usr"))?;
+/// filesystem.write_file(Path::new("/usr/foo"), &mut &b"foo"[..])?;
+/// filesystem.set_permissions(Path::new("/usr/foo"), 0o755)?;
+/// assert_eq!(
+///     filesystem.get(Path::new("/usr/foo")),
+///     Some(Node::File { contents: b"foo".to_vec(), mode: 0o755 })
+/// );
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub trait FileSystem: Debug + Send + Sync {
+    /// Check if anything is at the path, following symlinks.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Check if the path is a directory, following symlinks.
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Check if the path is a symlink itself, without following it.
+    fn is_symlink(&self, path: &Path) -> bool;
+
+    /// Check if the directory has nothing in it.
+    fn is_empty_dir(&self, path: &Path) -> io::Result<bool>;
+
+    /// Provide the absolute path with every symlink along the way resolved.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Create a directory, which must not exist yet, within an existing one.
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+
+    /// Create a file with the given contents, which must not exist yet.
+    fn write_file(&self, path: &Path, contents: &mut dyn Read) -> io::Result<()>;
+
+    /// Create a symlink at the path leading to the target.
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()>;
+
+    /// Create a hardlink at the path to the existing file at the target.
+    fn hard_link(&self, target: &Path, path: &Path) -> io::Result<()>;
+
+    /// Set the permission bits of a file or directory.
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()>;
+
+    /// Remove a file or symlink.
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Remove an empty directory.
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+
+    /// Provide how many bytes can be written to the filesystem containing the path.
+    fn available_space(&self, path: &Path) -> io::Result<u64>;
+}
+
+/// The real filesystem of the operating system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsFileSystem;
+
+impl FileSystem for OsFileSystem {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        path.is_symlink()
+    }
+
+    fn is_empty_dir(&self, path: &Path) -> io::Result<bool> {
+        Ok(path.read_dir()?.next().is_none())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        path.canonicalize()
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn write_file(&self, path: &Path, contents: &mut dyn Read) -> io::Result<()> {
+        let mut file = OpenOptions::new().create_new(true).write(true).open(path)?;
+        io::copy(contents, &mut file)?;
+        Ok(())
+    }
+
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()> {
+        symlink(target, path)
+    }
+
+    fn hard_link(&self, target: &Path, path: &Path) -> io::Result<()> {
+        fs::hard_link(target, path)
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()> {
+        fs::set_permissions(path, Permissions::from_mode(mode))
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn available_space(&self, path: &Path) -> io::Result<u64> {
+        util::available_space(path)
+    }
+}
+
+/// Something in an [InMemoryFileSystem](InMemoryFileSystem).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    /// A directory, with its permission bits.
+    Directory {
+        /// The permission bits of the directory.
+        mode: u32,
+    },
+    /// A file, with its contents and permission bits.
+    File {
+        /// What the file holds.
+        contents: Vec<u8>,
+        /// The permission bits of the file.
+        mode: u32,
+    },
+    /// A symlink leading to the path it holds.
+    Symlink(PathBuf),
+}
+
+/// A filesystem kept entirely in memory, which starts out with only an empty
+/// `/` directory. Space on it never runs out.
+#[derive(Debug)]
+pub struct InMemoryFileSystem {
+    nodes: Mutex<BTreeMap<PathBuf, Node>>,
+}
+
+impl Default for InMemoryFileSystem {
+    fn default() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(PathBuf::from("/"), Node::Directory { mode: 0o755 });
+        Self {
+            nodes: Mutex::new(nodes),
+        }
+    }
+}
+
+/// Symlinks are only followed this many times within a path, like on Linux.
+const MAX_SYMLINKS: usize = 40;
+
+impl InMemoryFileSystem {
+    /// Provide what is at the path, without following a symlink at the end of it.
+    pub fn get(&self, path: &Path) -> Option<Node> {
+        let nodes = self.nodes();
+        let path = location(&nodes, path).ok()?;
+        nodes.get(&path).cloned()
+    }
+
+    /// Provide every path on the filesystem, in order.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.nodes().keys().cloned().collect()
+    }
+
+    fn nodes(&self) -> MutexGuard<'_, BTreeMap<PathBuf, Node>> {
+        self.nodes
+            .lock()
+            .expect("a thread panicked while using the filesystem")
+    }
+
+    /// Add a node at the path, which must not exist yet, within an existing directory.
+    fn insert(&self, path: &Path, node: Node) -> io::Result<()> {
+        let mut nodes = self.nodes();
+        let path = location(&nodes, path)?;
+        if nodes.contains_key(&path) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        nodes.insert(path, node);
+        Ok(())
+    }
+}
+
+impl FileSystem for InMemoryFileSystem {
+    fn exists(&self, path: &Path) -> bool {
+        self.canonicalize(path).is_ok()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        let nodes = self.nodes();
+        resolve(&nodes, path, 0)
+            .is_ok_and(|path| matches!(nodes.get(&path), Some(Node::Directory { .. })))
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        matches!(self.get(path), Some(Node::Symlink(_)))
+    }
+
+    fn is_empty_dir(&self, path: &Path) -> io::Result<bool> {
+        let nodes = self.nodes();
+        let path = resolve(&nodes, path, 0)?;
+        if !matches!(nodes.get(&path), Some(Node::Directory { .. })) {
+            return Err(not_a_directory());
+        }
+        // Whatever is in the directory comes straight after it.
+        Ok(!nodes
+            .range(path.clone()..)
+            .nth(1)
+            .is_some_and(|(child, _)| child.starts_with(&path)))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        resolve(&self.nodes(), path, 0)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        self.insert(path, Node::Directory { mode: 0o755 })
+    }
+
+    fn write_file(&self, path: &Path, contents: &mut dyn Read) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        contents.read_to_end(&mut buffer)?;
+        self.insert(
+            path,
+            Node::File {
+                contents: buffer,
+                mode: 0o644,
+            },
+        )
+    }
+
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()> {
+        self.insert(path, Node::Symlink(target.to_owned()))
+    }
+
+    fn hard_link(&self, target: &Path, path: &Path) -> io::Result<()> {
+        // Nodes don't share their contents, so the file is copied instead.
+        match self.get(target) {
+            Some(node @ Node::File { .. }) => self.insert(path, node),
+            Some(_) => Err(io::Error::other("hardlinks can only lead to files")),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let mut nodes = self.nodes();
+        let path = resolve(&nodes, path, 0)?;
+        match nodes.get_mut(&path) {
+            Some(Node::Directory { mode: old } | Node::File { mode: old, .. }) => *old = mode,
+            _ => return Err(io::ErrorKind::NotFound.into()),
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.nodes();
+        let path = location(&nodes, path)?;
+        match nodes.get(&path) {
+            Some(Node::Directory { .. }) => Err(io::Error::other("is a directory, not a file")),
+            Some(_) => {
+                nodes.remove(&path);
+                Ok(())
+            }
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        if !self.is_empty_dir(path)? {
+            return Err(io::Error::other("the directory is not empty"));
+        }
+        let mut nodes = self.nodes();
+        let path = location(&nodes, path)?;
+        nodes.remove(&path);
+        Ok(())
+    }
+
+    fn available_space(&self, _path: &Path) -> io::Result<u64> {
+        Ok(u64::MAX)
+    }
+}
+
+fn not_a_directory() -> io::Error {
+    io::Error::other("not a directory")
+}
+
+/// Provide where the path is with the symlinks leading to it resolved,
+/// without following a symlink at the end. The directory it's in must exist.
+fn location(nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> io::Result<PathBuf> {
+    let name = match path.file_name() {
+        Some(name) => name,
+        None => return resolve(nodes, path, 0),
+    };
+    let parent = resolve(nodes, path.parent().unwrap_or_else(|| Path::new("/")), 0)?;
+    if !matches!(nodes.get(&parent), Some(Node::Directory { .. })) {
+        return Err(not_a_directory());
+    }
+    Ok(parent.join(name))
+}
+
+/// Provide the absolute path with every symlink resolved. Relative paths are
+/// taken from `/`.
+fn resolve(nodes: &BTreeMap<PathBuf, Node>, path: &Path, depth: usize) -> io::Result<PathBuf> {
+    if depth > MAX_SYMLINKS {
+        return Err(io::Error::other("too many levels of symbolic links"));
+    }
+    let mut resolved = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved = PathBuf::from("/"),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+                match nodes.get(&resolved) {
+                    Some(Node::Symlink(target)) => {
+                        // An absolute target replaces the directory it's joined to.
+                        let target = resolved.parent().unwrap_or(&resolved).join(target);
+                        resolved = resolve(nodes, &target, depth + 1)?;
+                    }
+                    Some(_) => {}
+                    None => return Err(io::ErrorKind::NotFound.into()),
+                }
+            }
+        }
+    }
+    Ok(resolved)
+}
@@ -0,0 +1,173 @@
+use crate::{
+    package::{InstallState, SharedPackage},
+    Selections, Version,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    fs::{remove_file, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The kind of change a transaction made to a package.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    /// The package was installed.
+    Install,
+    /// The package was removed.
+    Remove,
+    /// The package was upgraded to a newer version.
+    Upgrade,
+    /// The package was downgraded to an older version.
+    Downgrade,
+    /// The missing files of the installed package were restored.
+    Repair,
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Install => "Install",
+                Self::Remove => "Remove",
+                Self::Upgrade => "Upgrade",
+                Self::Downgrade => "Downgrade",
+                Self::Repair => "Repair",
+            }
+        )
+    }
+}
+
+/// A single package changed by a transaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Change {
+    /// What happened to the package.
+    pub action: Action,
+    /// The package's name.
+    pub name: String,
+    /// The version of the package that was acted upon.
+    pub version: Version,
+    /// The installation state of the package before the change.
+    pub state: InstallState,
+}
+
+/// A record of every change made by one call to
+/// [Database::apply](crate::Database::apply).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Transaction {
+    /// When the transaction happened, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The packages changed by the transaction.
+    pub changes: Vec<Change>,
+}
+
+impl Transaction {
+    /// Record the changes the selections are about to make.
+    pub(crate) fn from_selections(selections: &Selections) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let mut changes = vec![];
+        let mut record = |action, packages: &[SharedPackage]| {
+            for package in packages {
+                // Packages installed explicitly are only marked once installed.
+                let state = match selections.explicit.contains(package) {
+                    true if action == Action::Install => InstallState::Manual,
+                    _ => package.borrow().state.clone(),
+                };
+                let package = package.borrow();
+                changes.push(Change {
+                    action,
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    state,
+                });
+            }
+        };
+        record(Action::Install, &selections.install);
+        record(Action::Remove, &selections.remove);
+        record(Action::Upgrade, &selections.upgrade);
+        record(Action::Repair, &selections.repair);
+        // Downgrades are not yet applied, so they are not recorded either.
+        Self { timestamp, changes }
+    }
+
+    /// Provide the time of the transaction.
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + std::time::Duration::from_secs(self.timestamp)
+    }
+}
+
+/// Provide the path of the history log belonging to the database at the given path.
+pub fn log_path(database: &Path) -> PathBuf {
+    let mut path = OsString::from(database);
+    path.push(".log");
+    PathBuf::from(path)
+}
+
+/// Append a transaction to the history log. Each transaction is stored as a
+/// single line of JSON.
+pub(crate) fn append(path: &Path, transaction: &Transaction) -> crate::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut line = serde_json::to_vec(transaction)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    Ok(())
+}
+
+/// Read every transaction in the history log, oldest first. A missing log is
+/// treated as an empty history.
+pub fn read(path: &Path) -> crate::Result<Vec<Transaction>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    let mut transactions = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        transactions.push(serde_json::from_str(&line)?);
+    }
+    Ok(transactions)
+}
+
+/// Provide the path of the pending transaction record belonging to the
+/// database at the given path.
+pub fn pending_path(database: &Path) -> PathBuf {
+    let mut path = OsString::from(database);
+    path.push(".pending");
+    PathBuf::from(path)
+}
+
+/// Record that the transaction is about to be applied, so it can be found
+/// again if it's interrupted.
+pub(crate) fn write_pending(path: &Path, transaction: &Transaction) -> crate::Result<()> {
+    let file = File::create(path)?;
+    serde_json::to_writer(file, transaction)?;
+    Ok(())
+}
+
+/// Read the record of a transaction that was never finished, if there is one.
+pub fn read_pending(path: &Path) -> crate::Result<Option<Transaction>> {
+    match File::open(path) {
+        Ok(file) => Ok(Some(serde_json::from_reader(BufReader::new(file))?)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Remove the record of a pending transaction, if there is one.
+pub(crate) fn clear_pending(path: &Path) -> crate::Result<()> {
+    match remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,90 @@
+use crate::Error;
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// A command to run after any transaction that touches matching packages or
+/// files. Hooks are configured in the `hooks` section of the
+/// [Config](crate::Config).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Hook {
+    /// The name of the hook, used when reporting failures.
+    pub name: String,
+    /// Glob patterns matched against the absolute paths (within the root)
+    /// of the files the transaction touched, such as `/usr/lib/*`.
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Glob patterns matched against the names of the packages the transaction touched.
+    #[serde(default)]
+    pub packages: Vec<String>,
+    /// The command to run, passed to `sh -c`.
+    pub command: String,
+}
+
+impl Hook {
+    /// Check that every pattern of the hook is a valid glob.
+    pub(crate) fn validate(&self) -> crate::Result<()> {
+        for pattern in self.paths.iter().chain(&self.packages) {
+            Pattern::new(pattern).map_err(|error| {
+                Error::InvalidConfig(format!(
+                    "hook {} has an invalid pattern {}: {}",
+                    self.name, pattern, error
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Check if the hook should run after a transaction touching the given
+    /// packages and files. Files are given relative to the root.
+    /// ```
+    /// # use mix::hooks::Hook;
+    /// # use std::path::PathBuf;
+    /// let hook = Hook {
+    ///     name: String::from("ldconfig"),
+    ///     paths: vec![String::from("/usr/lib/*")],
+    ///     packages: vec![],
+    ///     command: String::from("ldconfig"),
+    /// };
+    /// assert!(hook.is_triggered(&[], &[PathBuf::from("usr/lib/libfoo.so")]));
+    /// assert!(!hook.is_triggered(&[String::from("foo")], &[PathBuf::from("usr/bin/foo")]));
+    /// ```
+    pub fn is_triggered(&self, packages: &[String], files: &[PathBuf]) -> bool {
+        let patterns = |patterns: &[String]| -> Vec<Pattern> {
+            patterns
+                .iter()
+                .filter_map(|pattern| Pattern::new(pattern).ok())
+                .collect()
+        };
+        let package_patterns = patterns(&self.packages);
+        let path_patterns = patterns(&self.paths);
+        packages.iter().any(|package| {
+            package_patterns
+                .iter()
+                .any(|pattern| pattern.matches(package))
+        }) || files.iter().any(|file| {
+            let file = Path::new("/").join(file);
+            path_patterns
+                .iter()
+                .any(|pattern| pattern.matches_path(&file))
+        })
+    }
+}
+
+/// Run every hook triggered by a transaction once. Hooks that fail only
+/// produce warnings, as the transaction has already been made.
+pub(crate) fn run_triggered(hooks: &[Hook], packages: &[String], files: &[PathBuf]) {
+    for hook in hooks {
+        if !hook.is_triggered(packages, files) {
+            continue;
+        }
+        match Command::new("sh").arg("-c").arg(&hook.command).status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("Warning: hook {} failed with {}", hook.name, status),
+            Err(error) => eprintln!("Warning: hook {} could not be run: {}", hook.name, error),
+        }
+    }
+}
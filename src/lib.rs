@@ -15,11 +15,9 @@
 //! /// Load the database and use it to find the needed package metadata.
 //! let mut database = mix::Database::load("/var/lib/mix/mix.db")?;
 //! /// If the packages are found, mix::selection::install will provide every dependency needed to install the packages.
-//! let packages = mix::selection::install(&package_names, &database).unwrap();
-//! /// Select the operation to perform with the packages.
-//! let operation = mix::Operation::Install(packages);
-//! /// Perform the operation.
-//! database.handle_operation(operation)?;
+//! let selections = mix::selection::install(&package_names, &database).unwrap();
+//! /// Apply the selected changes.
+//! database.apply(selections)?;
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 //! To remove `foo`, it's a similar process:
@@ -27,48 +25,68 @@
 //! let package_names = vec!["foo"];
 //! let mut database = mix::Database::load("/var/lib/mix/mix.db")?;
 //! /// This won't include any dependencies that can't be removed with the given packages.
-//! let packages = mix::selection::remove(&package_names, &database).unwrap();
-//! let operation = mix::Operation::Remove(packages);
-//! database.handle_operation(operation)?;
+//! let selections = mix::selection::remove(&package_names, &database).unwrap();
+//! database.apply(selections)?;
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 //! Synchronizing the database lists is not automatically performed for crate consumers, but it can be done manually with:
 //! ```no_run
 //! # let mut database = mix::Database::load("/var/lib/mix/mix.db")?;
-//! let operation = mix::Operation::Synchronize;
-//! database.handle_operation(operation)?;
+//! /// Every repository in the configuration is synchronized in turn.
+//! for repository in database.repositories() {
+//!     database.synchronize(repository.as_ref())?;
+//! }
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
-//! Other operations can be found in [Operation](crate::Operation).
+//! Other repositories can be provided by implementing [Repository](crate::Repository).
 //! # Todo
 //! There is a few things that be be implemented still before a functional prerelease, presented here in a predicted order:
-//! - Provide package removal functionality.
-//! - Handle package dependencies as best as possible.
 //! - Restore the unit testing, and add some integration tests to the mix.
 //! - Most likely, also add some form of property based testing: it seems like some components in mix may benefit from it.
 //!
 //! Any other functionality is either not currently high priority or was overlooked: contact me if it's not listed below.
 //!
 //! The current list of features not needed for an alpha prerelease but wanted for a stable:
-//! - Provide an interface to mixpkg for local package builds.
-//! - Connect to an online package repository for online packages.
-//! - Sync the package lists with said online repository.
 //! - Allow checking for updates to packages.
 //! - Clean up the inevitable flaws in the dependency resolving.
 
 #![warn(missing_docs)] // To keep codebase familiarity possible, docs are required
 
+/// Building packages from PKGBUILDs with mixpkg.
+pub mod build;
+/// System options for mix.
+pub mod config;
 /// The package database. All functionality with storing the available packages
 /// and the state of the installed packages is here.
 pub mod database;
 /// Errors that can be raised by the package manager.
 pub mod error;
+/// Where the files of packages are placed, which is usually the real disk.
+pub mod filesystem;
+/// The history log of every change made to the database.
+pub mod history;
+/// Commands run after transactions.
+pub mod hooks;
+/// Pinning the exact versions and checksums of installed packages.
+pub mod lockfile;
+/// Downloading from repository mirrors.
+pub mod net;
 /// The packages database and structures.
 pub mod package;
+/// Where packages come from, such as HTTP mirrors.
+pub mod repository;
 /// Selecting packages from the database for operations.
 pub mod selection;
+/// Checking the signatures of what repositories provide.
+pub mod signature;
+/// Small helpers shared by the library and the command line.
+pub mod util;
 
-pub use database::Database;
+pub use config::Config;
+pub use database::{ConsistencyWarning, Database, DatabaseDiff, DependencyTree, Recovery};
 pub use error::{MixError as Error, Result};
-pub use package::{InstallState, Package, Version};
-pub use selection::{install, package_from_name, packages_from_names, remove, Selections};
+pub use package::{InstallState, Package, PackageInfo, PackageQuery, SharedPackage, Version};
+pub use repository::{DirectoryRepository, HttpRepository, Repository};
+pub use selection::{
+    install, package_from_name, packages_from_names, remove, SelectionSummary, Selections,
+};
@@ -0,0 +1,47 @@
+use crate::{package::InstallState, Error};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// A record of the exact packages installed on a system, used to reproduce
+/// the same set of packages on another machine.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// The pinned packages.
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+/// A single package pinned by a [Lockfile](Lockfile).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    /// The package's name.
+    pub name: String,
+    /// The package's version, as displayed by [Version](crate::Version).
+    pub version: String,
+    /// The SHA-256 checksum of the package tarball.
+    pub checksum: String,
+    /// The installation state the package had when the lockfile was written.
+    pub state: InstallState,
+}
+
+impl Lockfile {
+    /// Load a lockfile from disk.
+    pub fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) => match err.kind() {
+                std::io::ErrorKind::NotFound => {
+                    return Err(Error::FileNotFound(path.as_ref().to_owned()))
+                }
+                _ => return Err(Error::IOError(err)),
+            },
+        };
+        toml::from_str(&contents).map_err(|error| Error::InvalidLockfile(error.to_string()))
+    }
+
+    /// Save the lockfile to the disk.
+    pub fn save(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ mod cli;
 
 fn main() {
     match cli::run() {
-        Ok(_) => (),
+        Ok(code) => process::exit(code),
         Err(error) => {
             eprintln!("{:#?}", error);
             process::exit(1);
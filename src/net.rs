@@ -0,0 +1,205 @@
+use crate::Error;
+use rand::seq::SliceRandom;
+use reqwest::{
+    blocking::{Client, Response},
+    header::{CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::{fs::OpenOptions, path::Path, str::FromStr};
+
+/// How many times a request to a single server is attempted before giving up on it.
+const ATTEMPTS: usize = 3;
+
+/// The order mirrors are tried in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MirrorOrder {
+    /// Try the mirrors in the order they are configured in.
+    #[default]
+    Ordered,
+    /// Try the mirrors in a random order, spreading the load between them.
+    Random,
+}
+
+impl FromStr for MirrorOrder {
+    type Err = Error;
+
+    fn from_str(order: &str) -> crate::Result<Self> {
+        match order {
+            "ordered" => Ok(Self::Ordered),
+            "random" => Ok(Self::Random),
+            other => Err(Error::InvalidConfig(format!(
+                "unknown mirror order {}, expected ordered or random",
+                other
+            ))),
+        }
+    }
+}
+
+/// What a server said identifies the last version of a file it provided, so
+/// it can be asked for the file only if it has changed since.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Validators {
+    /// The `ETag` header of the last response.
+    #[serde(default)]
+    pub etag: Option<String>,
+    /// The `Last-Modified` header of the last response.
+    #[serde(default)]
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Read the validators out of a response.
+    fn from_response(response: &Response) -> Self {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+}
+
+/// Call the function until it succeeds, up to `attempts` times, providing the
+/// last error if every attempt failed.
+pub fn retry<T>(attempts: usize, mut f: impl FnMut() -> crate::Result<T>) -> crate::Result<T> {
+    let mut result = f();
+    for _ in 1..attempts {
+        if result.is_ok() {
+            break;
+        }
+        result = f();
+    }
+    result
+}
+
+/// Request the URL, retrying on connection errors. Any response other than
+/// 200 OK is an error.
+pub fn get(url: &str) -> crate::Result<Response> {
+    let response = retry(ATTEMPTS, || Ok(reqwest::blocking::get(url)?))?;
+    Ok(response.error_for_status()?)
+}
+
+/// Download the file at `path` on the first mirror able to provide it into
+/// the partial file at `destination`, providing the mirror that succeeded.
+/// Whatever the partial file already contains (such as from an interrupted
+/// download) is kept, and only the rest is requested from mirrors that
+/// support ranges. A mirror is skipped on a connection error or any response
+/// other than 200 OK or 206 Partial Content.
+pub fn download(
+    mirrors: &[String],
+    path: &str,
+    order: MirrorOrder,
+    destination: &Path,
+) -> crate::Result<String> {
+    let client = Client::new();
+    for mirror in ordered(mirrors, order) {
+        let url = format!("{}/{}", mirror.trim_end_matches('/'), path);
+        // Each attempt resumes from wherever the previous one stopped.
+        match retry(ATTEMPTS, || resume(&client, &url, destination)) {
+            Ok(()) => return Ok(mirror.clone()),
+            Err(error) => eprintln!(
+                "Warning: {} failed ({}), trying the next mirror.",
+                url, error
+            ),
+        }
+    }
+    Err(Error::MirrorsFailed(String::from(path)))
+}
+
+/// Continue downloading the URL into the partial file, restarting from the
+/// beginning if the server doesn't resume from where the file ends.
+fn resume(client: &Client, url: &str, destination: &Path) -> crate::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(destination)?;
+    let received = file.metadata()?.len();
+    let mut request = client.get(url);
+    if received > 0 {
+        request = request.header(RANGE, format!("bytes={}-", received));
+    }
+    let mut response = request.send()?;
+    match response.status() {
+        StatusCode::PARTIAL_CONTENT if resumes_from(&response, received) => {}
+        StatusCode::OK | StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE
+            if received > 0 =>
+        {
+            // The server can't continue this file, so start it over.
+            file.set_len(0)?;
+            return resume(client, url, destination);
+        }
+        _ => response = response.error_for_status()?,
+    }
+    response.copy_to(&mut file)?;
+    Ok(())
+}
+
+/// Check if a partial response continues a file of the given length.
+fn resumes_from(response: &Response, received: u64) -> bool {
+    response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|range| range.to_str().ok())
+        .and_then(|range| range.strip_prefix("bytes "))
+        .and_then(|range| range.split('-').next())
+        .and_then(|start| start.parse::<u64>().ok())
+        == Some(received)
+}
+
+/// Download the file at `path` on the first mirror able to provide it, unless
+/// it hasn't changed since the download the validators came from. Provides
+/// None if the file is unchanged, and otherwise its contents and validators.
+pub fn download_if_changed(
+    mirrors: &[String],
+    path: &str,
+    order: MirrorOrder,
+    validators: &Validators,
+) -> crate::Result<Option<(Vec<u8>, Validators)>> {
+    let client = Client::new();
+    for mirror in ordered(mirrors, order) {
+        let url = format!("{}/{}", mirror.trim_end_matches('/'), path);
+        let response = retry(ATTEMPTS, || {
+            let mut request = client.get(&url);
+            if let Some(etag) = &validators.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &validators.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+            Ok(request.send()?)
+        });
+        match response {
+            Ok(response) if response.status() == StatusCode::NOT_MODIFIED => return Ok(None),
+            Ok(response) if response.status() == StatusCode::OK => {
+                let validators = Validators::from_response(&response);
+                return Ok(Some((response.bytes()?.to_vec(), validators)));
+            }
+            Ok(response) => eprintln!(
+                "Warning: {} responded with {}, trying the next mirror.",
+                url,
+                response.status()
+            ),
+            Err(error) => eprintln!(
+                "Warning: {} failed ({}), trying the next mirror.",
+                url, error
+            ),
+        }
+    }
+    Err(Error::MirrorsFailed(String::from(path)))
+}
+
+/// Provide the mirrors in the order they should be tried.
+fn ordered(mirrors: &[String], order: MirrorOrder) -> Vec<&String> {
+    let mut mirrors: Vec<&String> = mirrors.iter().collect();
+    if order == MirrorOrder::Random {
+        mirrors.shuffle(&mut rand::thread_rng());
+    }
+    mirrors
+}
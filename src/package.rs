@@ -1,105 +1,600 @@
-use crate::{Database, Error};
+use crate::{
+    config::Config, filesystem::FileSystem, history::Transaction, repository::Repository,
+    util::format_bytes, Database, Error, Selections,
+};
+use glob::Pattern;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
-    cell::RefCell,
-    ffi::OsString,
-    fs::{
-        create_dir, metadata, remove_dir, remove_file, set_permissions, OpenOptions, Permissions,
-    },
+    collections::{HashMap, HashSet},
+    fs::{read_dir, read_to_string, remove_file, rename, File},
     io::{self, prelude::*},
-    os::unix::prelude::*,
-    path::PathBuf,
-    rc::Rc,
+    os::unix::fs::PermissionsExt,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard},
 };
 use tar::Archive;
-use xz2::read::XzDecoder;
+use xz2::{read::XzDecoder, write::XzEncoder};
+
+/// The entry of a package tarball (or file of a package tree) that holds its
+/// metadata, unless the [configuration](Config::manifest_name) names another.
+pub const MANIFEST_FILENAME: &str = ".MANIFEST";
+
+/// A package shared between the database and any selections made from it.
+/// It's an Arc<RwLock<Package>> so that packages can be handed to other threads
+/// (for parallel fetching or installing), but it keeps the borrowing interface
+/// of the Rc<RefCell<Package>> it replaced.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SharedPackage(Arc<RwLock<Package>>);
+
+impl SharedPackage {
+    /// Share a package.
+    pub fn new(package: Package) -> Self {
+        Self(Arc::new(RwLock::new(package)))
+    }
+
+    /// Immutably borrow the package. Blocks while another thread mutably borrows it.
+    pub fn borrow(&self) -> RwLockReadGuard<'_, Package> {
+        self.0
+            .read()
+            .expect("a thread panicked while modifying a package")
+    }
+
+    /// Check if both handles share the very same package.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Arc::ptr_eq(&this.0, &other.0)
+    }
 
-/// An Rc<RefCell<Package>>. Exists to silence a Clippy warning.
-/// It may not be needed to use a Rc and/or RefCell in the future.
-pub(crate) type RcRefCellPackage = Rc<RefCell<Package>>;
+    /// Mutably borrow the package. Blocks while any other borrow is held.
+    pub fn borrow_mut(&self) -> RwLockWriteGuard<'_, Package> {
+        self.0
+            .write()
+            .expect("a thread panicked while modifying a package")
+    }
+}
+
+impl PartialEq for SharedPackage {
+    fn eq(&self, other: &Self) -> bool {
+        // Borrowing the same package twice at once could deadlock.
+        Self::ptr_eq(self, other) || *self.borrow() == *other.borrow()
+    }
+}
 
 /// Install the given packages. This will place files onto the filesystem, and
-/// mark the packages as installed (either as a dependency if not installed, or
-/// leaving the state as dependency or manually installed.)
-pub fn install(packages: &[RcRefCellPackage], database: &mut Database) -> crate::Result<()> {
+/// mark the packages as installed: as manually installed if they are among
+/// `explicit` (or already marked so), and as a dependency otherwise. A
+/// package's state only changes once its files are placed.
+/// Packages are installed in the given order, unless
+/// [install_jobs](crate::Config::install_jobs) allows installing several at
+/// once, in which case only packages that don't depend on each other are
+/// installed at the same time. `on_file` is called with each file once it's
+/// placed, relative to the root, which can show the progress of large
+/// packages. The files are placed onto the given filesystem. If a package
+/// fails partway through, the files it already placed are removed again
+/// before the error is returned.
+pub fn install(
+    packages: &[SharedPackage],
+    explicit: &[SharedPackage],
+    database: &mut Database,
+    filesystem: &dyn FileSystem,
+    on_file: &mut (dyn FnMut(&Path) + Send),
+) -> crate::Result<()> {
+    // Nothing is installed unless every package can be.
+    for package in packages {
+        package
+            .borrow()
+            .check_architecture(&database.config().architecture)?;
+    }
+    let required = packages
+        .iter()
+        .map(|package| package.borrow().size.unwrap_or_default())
+        .sum();
+    check_space(required, database.config(), filesystem)?;
     for package in packages {
         // Make sure the package is known.
         database.import_package(package.clone())?;
+    }
+    let jobs = database.config().install_jobs;
+    if jobs <= 1 {
+        for package in packages {
+            let manual = explicit.contains(package);
+            extract(package, manual, database, filesystem, on_file)?;
+        }
+        return Ok(());
+    }
+    let database = &*database;
+    let on_file = Mutex::new(on_file);
+    for batch in crate::selection::install_batches(packages)? {
+        for chunk in batch.chunks(jobs) {
+            std::thread::scope(|scope| {
+                let jobs: Vec<_> = chunk
+                    .iter()
+                    .map(|package| {
+                        let on_file = &on_file;
+                        let manual = explicit.contains(package);
+                        scope.spawn(move || {
+                            extract(package, manual, database, filesystem, &mut |path| {
+                                (on_file.lock().expect("a thread panicked while installing"))(path)
+                            })
+                        })
+                    })
+                    .collect();
+                jobs.into_iter()
+                    .try_for_each(|job| job.join().expect("a thread panicked while installing"))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Check that the root has room for `required` more bytes.
+fn check_space(required: u64, config: &Config, filesystem: &dyn FileSystem) -> crate::Result<()> {
+    let available = filesystem.available_space(&config.root)?;
+    if required > available {
+        return Err(Error::InsufficientSpace {
+            required,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// Place the files of a single known package onto the filesystem, and mark it
+/// as installed, manually if `manual` is set.
+fn extract(
+    package: &SharedPackage,
+    manual: bool,
+    database: &Database,
+    filesystem: &dyn FileSystem,
+    on_file: &mut dyn FnMut(&Path),
+) -> crate::Result<()> {
+    database.check_cancelled()?;
+    // Packages without files (such as meta-packages) may not have a tarball at all.
+    if !package.borrow().files.is_empty() {
         // Open the package tarball for reading.
         let file = database.open_package_tarball(&package.borrow())?;
-        let file = XzDecoder::new(file);
-        let mut file = Archive::new(file);
         // Place the files into the filesystem.
-        for entry in file.entries()? {
+        let directories = place_entries(
+            file,
+            &package.borrow().files,
+            database.config(),
+            filesystem,
+            on_file,
+        )?;
+        // Directories the tarball only implies are removed along with the package.
+        package.borrow_mut().files.extend(directories);
+    }
+    // Flag the package as installed.
+    let package_state = match package.borrow().state {
+        _ if manual => InstallState::Manual,
+        InstallState::Manual => InstallState::Manual,
+        InstallState::Dependency | InstallState::Uninstalled => InstallState::Dependency,
+    };
+    package.borrow_mut().state = package_state;
+    Ok(())
+}
+
+/// Install a package straight from a tarball, reading its manifest in the
+/// same pass that places its files so the tarball is only decompressed once.
+/// The package is added to the database and marked as manually installed,
+/// and its files are placed onto the filesystem of the database. A package
+/// the database already knows, such as one from a repository, is the one
+/// marked as installed. Like [apply](Database::apply), the install is
+/// recorded in the history and triggers hooks.
+/// # Errors
+/// Like [install_tarballs](crate::selection::install_tarballs), dependencies
+/// that no installed package provides are [not found](Error::PackageNotFound),
+/// and the package must not [conflict](Error::ConflictingPackages) with an
+/// installed package. If placing the files fails or is cancelled, the files
+/// already placed are removed again and the install is not left pending.
+/// # Note
+/// The manifest is expected to be the first entry of the tarball. If it
+/// isn't, the tarball is read once for the manifest before any file is placed.
+pub fn install_tarball(path: &Path, database: &mut Database) -> crate::Result<SharedPackage> {
+    database.check_cancelled()?;
+    let config = database.config().clone();
+    let filesystem = database.filesystem().clone();
+    let mut archive = Archive::new(XzDecoder::new(File::open(path)?));
+    let mut entries = archive.entries()?;
+    let manifest = match entries.next() {
+        Some(entry) => {
             let mut entry = entry?;
-            match entry.path()?.to_str() {
-                Some(".MANIFEST") => continue,
-                _ => place_entry(&mut entry)?,
+            if is_manifest(&entry.path()?, config.manifest_filename()) {
+                let mut manifest = String::new();
+                entry.read_to_string(&mut manifest)?;
+                Some(manifest)
+            } else {
+                None
             }
         }
-        // Flag the package as installed.
-        let package_state = match package.borrow().state {
-            InstallState::Manual => InstallState::Manual,
-            InstallState::Dependency | InstallState::Uninstalled => InstallState::Dependency,
-        };
-        package.borrow_mut().state = package_state;
+        None => return Err(Error::InvalidPackageError),
+    };
+    let (placed, transaction) = match manifest {
+        Some(manifest) if !declares_sub_packages(&manifest) => {
+            let mut package = Package::all_from_manifest(&manifest, &[])?.remove(0);
+            check_installable(&package, database)?;
+            let transaction = begin_install(&package, database)?;
+            let mut created = vec![];
+            let mut links = vec![];
+            let mut recorded = HashSet::new();
+            let place = || -> crate::Result<()> {
+                for entry in entries {
+                    database.check_cancelled()?;
+                    let mut entry = entry?;
+                    let directories =
+                        place_tracked(&mut entry, &config, &*filesystem, &mut created, &mut links)?;
+                    let path = entry.path()?.into_owned();
+                    for file in directories.into_iter().chain([path]) {
+                        if recorded.insert(file.clone()) {
+                            package.files.push(file);
+                        }
+                    }
+                }
+                place_links(&links, &*filesystem, &mut created)
+            };
+            let placed = place();
+            if placed.is_err() {
+                roll_back(&created, &*filesystem);
+            }
+            (placed.map(|()| package), transaction)
+        }
+        // Sub-packages are told apart by their files, so every file has to be known first.
+        _ => {
+            let mut package = Package::from_tarball_with_manifest_name(
+                File::open(path)?,
+                config.manifest_filename(),
+            )?;
+            check_installable(&package, database)?;
+            let transaction = begin_install(&package, database)?;
+            let placed = File::open(path).map_err(Error::from).and_then(|file| {
+                place_entries(file, &package.files, &config, &*filesystem, &mut |_| {})
+            });
+            (
+                placed.map(|directories| {
+                    package.files.extend(directories);
+                    package
+                }),
+                transaction,
+            )
+        }
+    };
+    let mut package = match placed {
+        Ok(package) => package,
+        // The files are gone again, so there's nothing left to resume.
+        Err(error) => {
+            database.discard_pending()?;
+            return Err(error);
+        }
+    };
+    // The path is relative to the working directory, not the base directory.
+    package.local_path = Some(path.canonicalize()?);
+    package.state = InstallState::Manual;
+    let touched_files = package.files.clone();
+    let package = database.import_installed(SharedPackage::new(package))?;
+    database.finish_transaction(&transaction, &touched_files)?;
+    Ok(package)
+}
+
+/// Check that a package read from a tarball can be installed by itself: it
+/// suits this system, what it depends on is installed, it doesn't conflict
+/// with an installed package, and the root has room for it.
+fn check_installable(package: &Package, database: &Database) -> crate::Result<()> {
+    package.check_architecture(&database.config().architecture)?;
+    let missing = crate::selection::missing_dependencies(std::slice::from_ref(package), database);
+    if !missing.is_empty() {
+        return Err(Error::PackageNotFound(missing));
     }
-    Ok(())
+    let mut selections = Selections {
+        install: vec![SharedPackage::new(package.clone())],
+        ..Selections::default()
+    };
+    crate::selection::resolve_conflicts(&mut selections, database, false)?;
+    check_space(
+        package.size.unwrap_or_default(),
+        database.config(),
+        &**database.filesystem(),
+    )
+}
+
+/// Record that the package is about to be installed, so an interrupted
+/// install can be resumed or rolled back like one made by
+/// [apply](Database::apply).
+fn begin_install(package: &Package, database: &Database) -> crate::Result<Transaction> {
+    let transaction = Transaction::from_selections(&Selections {
+        install: vec![SharedPackage::new(package.clone())],
+        ..Selections::default()
+    });
+    database.begin_transaction(&transaction)?;
+    Ok(transaction)
 }
 
 /// Remove the given packages. This will remove any files of the package from
 /// the filesystem, as well as marking the package as not installed.
+/// Directories are only removed once they're empty, so those shared with
+/// other packages stay. Files that were already deleted are skipped with a warning.
 /// # Warning
 /// A call to this function that removes dependencies of installed packages but
 /// not those packages will place the package database into an an unsafe state.
-pub fn remove(packages: &[RcRefCellPackage], database: &mut Database) -> crate::Result<()> {
+pub fn remove(
+    packages: &[SharedPackage],
+    database: &mut Database,
+    filesystem: &dyn FileSystem,
+) -> crate::Result<()> {
     for package in packages {
-        package.borrow_mut().state = InstallState::Uninstalled;
-        let file = database.open_package_tarball(&package.borrow())?;
-        let file = XzDecoder::new(file);
-        let mut file = Archive::new(file);
-        for entry in file.entries()? {
-            let entry = entry?;
-            let path = PathBuf::from("/").join(entry.path()?);
-            if path.to_str() == Some("/.MANIFEST") {
-                // Don't try to remove the package manifest file
-                continue;
-            }
-            if !path.exists() {
-                eprintln!(
+        database.check_cancelled()?;
+        let mut files = package.borrow().files.clone();
+        // The contents of a directory sort after it, so going backwards
+        // empties each directory before it's reached.
+        files.sort();
+        for file in files.iter().rev() {
+            let path = destination(filesystem, &database.config().root, file)?;
+            // Symlinks to directories are removed like files.
+            let result = if filesystem.is_dir(&path) && !filesystem.is_symlink(&path) {
+                if !filesystem.is_empty_dir(&path)? {
+                    continue;
+                }
+                filesystem.remove_dir(&path)
+            } else {
+                filesystem.remove_file(&path)
+            };
+            match result {
+                Ok(()) => {}
+                Err(error) if error.kind() == io::ErrorKind::NotFound => eprintln!(
                     "Warning: {} was not able to be removed because it doesn't exist.",
                     path.display()
-                );
+                ),
+                Err(error) => return Err(error.into()),
+            }
+        }
+        package.borrow_mut().state = InstallState::Uninstalled;
+    }
+    Ok(())
+}
+
+/// Restore the given files of an installed package from its tarball, which
+/// is downloaded first if it isn't cached. Only the given files
+/// (relative to the root) are placed, and any that have reappeared since are
+/// left alone, so files that may have been modified are never overwritten.
+pub fn repair(
+    package: &SharedPackage,
+    database: &Database,
+    filesystem: &dyn FileSystem,
+    missing: &[PathBuf],
+) -> crate::Result<()> {
+    database.fetch_all(std::slice::from_ref(package))?;
+    let config = database.config();
+    let file = database.open_package_tarball(&package.borrow())?;
+    let mut archive = Archive::new(XzDecoder::new(file));
+    let mut links = vec![];
+    for entry in archive.entries()? {
+        database.check_cancelled()?;
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if missing.contains(&path) && !filesystem.exists(&config.root.join(&path)) {
+            create_parents(&path, config, filesystem, &mut vec![])?;
+            place_entry(&mut entry, config, filesystem, &mut links)?;
+        }
+    }
+    place_links(&links, filesystem, &mut vec![])
+}
+
+/// A way an installed file differs from the package tarball it came from, as
+/// found by [audit](audit). Paths are relative to the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileDiscrepancy {
+    /// The contents of the file differ from the tarball, or it's no longer
+    /// the same type of file.
+    Modified(PathBuf),
+    /// The permissions differ from the permissions the file was installed with.
+    WrongMode {
+        /// The file.
+        path: PathBuf,
+        /// The permission bits the file was installed with.
+        expected: u32,
+        /// The permission bits of the file now.
+        found: u32,
+    },
+    /// The file doesn't exist.
+    Missing(PathBuf),
+    /// The package lists the file, but the tarball doesn't contain it.
+    Extra(PathBuf),
+}
+
+impl std::fmt::Display for FileDiscrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Modified(path) => write!(f, "modified: {}", path.display()),
+            Self::WrongMode {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "wrong mode: {} (expected {:04o}, found {:04o})",
+                path.display(),
+                expected,
+                found
+            ),
+            Self::Missing(path) => write!(f, "missing: {}", path.display()),
+            Self::Extra(path) => write!(f, "extra: {}", path.display()),
+        }
+    }
+}
+
+/// Compare the installed files of a package against its tarball, providing
+/// every file that differs. Files are compared by their SHA-256
+/// [checksum](checksum), and their permissions against the permissions the
+/// configuration installs them with. Files of the tarball the package
+/// doesn't list (such as those of other sub-packages) are left out.
+pub fn audit(
+    package: &Package,
+    tarball: impl Read,
+    config: &Config,
+) -> crate::Result<Vec<FileDiscrepancy>> {
+    let mut unseen: HashSet<&Path> = package.files.iter().map(PathBuf::as_path).collect();
+    let mut discrepancies = vec![];
+    // Directories created for entries without one of their own aren't extra.
+    let mut parents = HashSet::new();
+    let mut archive = Archive::new(XzDecoder::new(tarball));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        parents.extend(path.ancestors().skip(1).map(Path::to_owned));
+        if !unseen.remove(path.as_path()) {
+            continue;
+        }
+        let installed = config.root.join(&path);
+        let metadata = match installed.symlink_metadata() {
+            Ok(metadata) => metadata,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                discrepancies.push(FileDiscrepancy::Missing(path));
                 continue;
             }
-            let metadata = metadata(&path)?;
-            if metadata.is_file() {
-                remove_file(&path)?;
-            // Is directory and empty
-            } else if metadata.is_dir() {
-                if path.read_dir()?.next().is_none() {
-                    remove_dir(&path)?;
+            Err(error) => return Err(error.into()),
+        };
+        let same = match entry.header().entry_type() {
+            tar::EntryType::Regular => {
+                metadata.is_file() && checksum(File::open(&installed)?)? == checksum(&mut entry)?
+            }
+            tar::EntryType::Directory => metadata.is_dir(),
+            tar::EntryType::Symlink => {
+                // The permissions of symlinks mean nothing, so only the target is compared.
+                let target = entry.link_name()?.map(|target| target.into_owned());
+                if !metadata.file_type().is_symlink() || Some(installed.read_link()?) != target {
+                    discrepancies.push(FileDiscrepancy::Modified(path));
                 }
-            } else {
-                unimplemented!("{} is not a file or directory!", path.display())
+                continue;
             }
+            _ => continue,
+        };
+        if !same {
+            discrepancies.push(FileDiscrepancy::Modified(path));
+            continue;
+        }
+        let expected = config.installed_mode(entry.header().mode()?) & 0o7777;
+        let found = metadata.permissions().mode() & 0o7777;
+        if expected != found {
+            discrepancies.push(FileDiscrepancy::WrongMode {
+                path,
+                expected,
+                found,
+            });
+        }
+    }
+    discrepancies.extend(
+        package
+            .files
+            .iter()
+            .filter(|file| unseen.contains(file.as_path()) && !parents.contains(file.as_path()))
+            .map(|file| FileDiscrepancy::Extra(file.clone())),
+    );
+    Ok(discrepancies)
+}
+
+/// Update the given packages to the latest version. Packages whose installed
+/// version is as new as the given one are skipped. The new version keeps the install state of
+/// the version it replaces, so manually installed packages stay manually
+/// installed. `on_file` is called with each file placed, like for
+/// [install](install).
+pub fn update(
+    packages: &[SharedPackage],
+    database: &mut Database,
+    filesystem: &dyn FileSystem,
+    on_file: &mut dyn FnMut(&Path),
+) -> crate::Result<()> {
+    for package in packages {
+        database.check_cancelled()?;
+        let name = package.borrow().name.clone();
+        let installed = database
+            .installed_package(&name)
+            .ok_or(Error::PackageNotInstalled)?;
+        if SharedPackage::ptr_eq(&installed, package)
+            || installed.borrow().version >= package.borrow().version
+        {
+            continue;
         }
+        database.replace_package(&installed, package)?;
+        // Removing the old version first deletes the files the new version doesn't have.
+        remove(std::slice::from_ref(&installed), database, filesystem)?;
+        extract(package, false, database, filesystem, on_file)?;
     }
     Ok(())
 }
 
-/// Update the given packages to the latest version. This may skip over packages
-/// that are already up to date.
-pub fn update(packages: &[RcRefCellPackage], _database: &mut Database) -> crate::Result<()> {
-    for _package in packages {
-        todo!()
+/// Download the tarball of the given package into the download cache from the
+/// repository, unless a copy matching the package's checksum is already
+/// cached. An interrupted download is resumed where it stopped, and the
+/// finished tarball must match the package's checksum. When packages are
+/// [verified](crate::Config::verify_packages), the signature of the tarball
+/// is downloaded along with it.
+pub fn fetch(
+    package: &SharedPackage,
+    repository: &dyn Repository,
+    database: &Database,
+) -> crate::Result<()> {
+    let package = package.borrow();
+    if database.has_cached_tarball(&package)? {
+        return Ok(());
     }
+    let destination = database.downloaded_tarball(&package);
+    // Download next to the destination so a failed download never looks complete.
+    let mut partial = destination.clone().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+    repository.fetch_package(&package, &partial)?;
+    if let Some(expected) = &package.checksum {
+        let found = checksum(File::open(&partial)?)?;
+        if !expected.eq_ignore_ascii_case(&found) {
+            // A corrupt download can't be resumed, so the next attempt starts over.
+            remove_file(&partial)?;
+            return Err(Error::ChecksumMismatch {
+                expected: expected.clone(),
+                found,
+            });
+        }
+    }
+    rename(&partial, &destination)?;
+    if database.verifies_signature(&package) {
+        repository.fetch_package_signature(&package, &signature_path(&destination))?;
+    }
+    Ok(())
+}
+
+/// Provide where the detached signature of a tarball is kept.
+pub(crate) fn signature_path(tarball: &Path) -> PathBuf {
+    let mut signature = tarball.to_owned().into_os_string();
+    signature.push(".sig");
+    PathBuf::from(signature)
+}
+
+/// Pack the given files of an unpacked package tree into a tarball, with the
+/// `.MANIFEST` first, so the package can be installed like any other.
+/// Symlinks are packed as they are rather than followed.
+pub(crate) fn pack_directory(
+    root: &Path,
+    files: &[PathBuf],
+    tarball: impl Write,
+) -> crate::Result<()> {
+    let mut builder = tar::Builder::new(XzEncoder::new(tarball, 6));
+    builder.follow_symlinks(false);
+    builder.append_path_with_name(root.join(MANIFEST_FILENAME), MANIFEST_FILENAME)?;
+    for file in files {
+        builder.append_path_with_name(root.join(file), file)?;
+    }
+    builder.into_inner()?.finish()?;
     Ok(())
 }
 
-/// Download the files of the given package.
-pub fn fetch(_package: RcRefCellPackage) -> crate::Result<()> {
-    todo!()
+/// Compute the SHA-256 checksum of a package tarball, as a lowercase hex string.
+pub fn checksum(mut tarball: impl Read) -> crate::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut tarball, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
 }
 
 /// A singular package. A package is a name, list of files, and some metadata.
@@ -111,52 +606,313 @@ pub struct Package {
     /// The package's version.
     pub version: Version,
     /// The installation state of the package.
+    #[serde(default)]
     pub state: InstallState,
-    /// The files included in the package.
+    /// The files included in the package. A package without files, such as a
+    /// meta-package grouping its dependencies, doesn't need a tarball.
+    #[serde(default)]
     pub files: Vec<PathBuf>,
-    /// The local path of the package, either relative to the package directory or absolute.
+    /// The local path of the package, either absolute or relative to the
+    /// database's [base directory](crate::Database::set_base_dir).
     pub local_path: Option<PathBuf>,
+    /// The SHA-256 checksum of the package tarball, as a lowercase hex string.
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// The names of the packages this package depends on.
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Packages that enable extra features of this package, with the reason
+    /// for each. These are never installed automatically.
+    #[serde(default)]
+    pub optional_dependencies: Vec<(String, String)>,
+    /// A short description of the package.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// The names of other packages this package can stand in for.
+    #[serde(default)]
+    pub provides: Vec<String>,
+    /// The names of the packages that can't be installed alongside this package.
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+    /// The total size of the package's files once installed, in bytes.
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// The repository the package came from, or None for local packages.
+    #[serde(default)]
+    pub repository: Option<String>,
+    /// The repository the package came from no longer provides it. Only
+    /// installed packages are kept once they're withdrawn.
+    #[serde(default)]
+    pub withdrawn: bool,
+    /// The architecture the package was built for, or None if it runs on any architecture.
+    #[serde(default)]
+    pub architecture: Option<String>,
+    /// What changed in this version of the package.
+    #[serde(default)]
+    pub changelog: Option<String>,
+    /// Where the changelog of the package can be downloaded from, for
+    /// packages without an inline [changelog](Package::changelog).
+    #[serde(default)]
+    pub changelog_url: Option<String>,
 }
 
 impl Package {
-    /// Provide a package from a tarball
+    /// Provide an uninstalled package with the given name and version, and no
+    /// files or other metadata.
+    pub fn new(name: impl Into<String>, version: Version) -> Self {
+        Self {
+            name: name.into(),
+            version,
+            state: InstallState::Uninstalled,
+            files: vec![],
+            local_path: None,
+            checksum: None,
+            dependencies: vec![],
+            optional_dependencies: vec![],
+            description: None,
+            provides: vec![],
+            conflicts: vec![],
+            size: None,
+            repository: None,
+            withdrawn: false,
+            architecture: None,
+            changelog: None,
+            changelog_url: None,
+        }
+    }
+
+    /// Provide a package from a tarball. For a tarball of several
+    /// sub-packages, this provides the first; see
+    /// [all_from_tarball](Package::all_from_tarball).
     pub fn from_tarball(file: impl Read) -> crate::Result<Self> {
+        Self::from_tarball_with_manifest_name(file, MANIFEST_FILENAME)
+    }
+
+    /// Provide a package from a tarball like [from_tarball](Package::from_tarball),
+    /// with its metadata in the entry of the given name instead of `.MANIFEST`.
+    pub fn from_tarball_with_manifest_name(
+        file: impl Read,
+        manifest_name: &str,
+    ) -> crate::Result<Self> {
+        first_package(Self::all_from_tarball_with_manifest_name(
+            file,
+            manifest_name,
+        )?)
+    }
+
+    /// Provide every package a tarball contains. A single build can produce
+    /// several sub-packages, like a PKGBUILD with a `pkgname` array, which
+    /// are declared by a `[[packages]]` table each in the manifest:
+    /// ```toml
+    /// version = "1.0.0"
+    ///
+    /// [[packages]]
+    /// name = "foo"
+    ///
+    /// [[packages]]
+    /// name = "foo-docs"
+    /// files = ["usr/share/doc/*"]
+    /// ```
+    /// The keys of a sub-package override those shared by every sub-package.
+    /// Each file belongs to the first sub-package with a `files` pattern
+    /// matching it, or else to the first sub-package, and each sub-package
+    /// also gets the directories its files are in. The manifest may be
+    /// anywhere in the tarball.
+    pub fn all_from_tarball(file: impl Read) -> crate::Result<Vec<Self>> {
+        Self::all_from_tarball_with_manifest_name(file, MANIFEST_FILENAME)
+    }
+
+    /// Provide every package a tarball contains like
+    /// [all_from_tarball](Package::all_from_tarball), with their metadata in
+    /// the entry of the given name instead of `.MANIFEST`.
+    pub fn all_from_tarball_with_manifest_name(
+        file: impl Read,
+        manifest_name: &str,
+    ) -> crate::Result<Vec<Self>> {
         let file = XzDecoder::new(file);
         let mut archive = Archive::new(file);
         let mut files = vec![];
+        let mut sizes = HashMap::new();
         let mut manifest = None;
         for entry in archive.entries()? {
             let mut entry = entry?;
-            if entry.path()? == OsString::from(".MANIFEST") {
+            if is_manifest(&entry.path()?, manifest_name) {
                 let mut buf = String::new();
                 entry.read_to_string(&mut buf)?;
-                manifest = Some(buf.parse::<toml::Value>());
+                manifest = Some(buf);
             } else {
-                files.push(PathBuf::from(entry.path()?))
+                let path = PathBuf::from(entry.path()?);
+                sizes.insert(path.clone(), entry.header().size()?);
+                files.push(path)
             }
         }
-        let manifest = match manifest {
-            Some(manifest) => manifest,
+        let mut packages = match manifest {
+            Some(manifest) => Self::all_from_manifest(&manifest, &files)?,
             None => return Err(Error::InvalidPackageError),
         };
-        let metadata = match manifest {
-            Ok(toml::Value::Table(metadata)) => metadata,
-            Ok(value) => return Err(Error::InvalidManifestError(value)),
-            Err(error) => return Err(Error::ManifestParseError(error)),
+        for package in &mut packages {
+            let size = package.files.iter().map(|file| sizes[file]).sum();
+            // The manifest's size is trusted if it has one.
+            package.size.get_or_insert(size);
+        }
+        Ok(packages)
+    }
+
+    /// Provide a package from an unpacked package tree. The `.MANIFEST` at the
+    /// root describes the package, and every other entry in the tree is
+    /// recorded as one of its files, relative to the root. Symlinks are
+    /// recorded as files and are not followed. Once the package is installed,
+    /// its files are packed into a tarball in the package cache. For a tree
+    /// of several sub-packages, this provides the first; see
+    /// [all_from_directory](Package::all_from_directory).
+    pub fn from_directory(root: &Path) -> crate::Result<Self> {
+        first_package(Self::all_from_directory(root)?)
+    }
+
+    /// Provide every package an unpacked package tree contains, split up like
+    /// [all_from_tarball](Package::all_from_tarball) does.
+    pub fn all_from_directory(root: &Path) -> crate::Result<Vec<Self>> {
+        let manifest = match read_to_string(root.join(MANIFEST_FILENAME)) {
+            Ok(manifest) => manifest,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Err(Error::InvalidPackageError)
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let mut files = vec![];
+        let mut pending = vec![root.to_owned()];
+        while let Some(directory) = pending.pop() {
+            for entry in read_dir(directory)? {
+                let entry = entry?;
+                let path = entry.path();
+                let relative = path
+                    .strip_prefix(root)
+                    .expect("walked outside of the package root")
+                    .to_owned();
+                if is_manifest(&relative, MANIFEST_FILENAME) {
+                    continue;
+                }
+                // DirEntry::file_type does not follow symlinks.
+                if entry.file_type()?.is_dir() {
+                    pending.push(path);
+                }
+                files.push(relative);
+            }
+        }
+        files.sort();
+        let mut packages = Self::all_from_manifest(&manifest, &files)?;
+        for package in &mut packages {
+            package.local_path = Some(root.to_owned());
+        }
+        Ok(packages)
+    }
+
+    /// Provide every package declared by a manifest, splitting the files
+    /// between the sub-packages if there are several.
+    fn all_from_manifest(manifest: &str, files: &[PathBuf]) -> crate::Result<Vec<Self>> {
+        let mut shared = parse_manifest(manifest)?;
+        let sub_packages = match shared.remove("packages") {
+            // A manifest without sub-packages declares a single package.
+            None => vec![toml::value::Table::new()],
+            Some(toml::Value::Array(sub_packages)) if !sub_packages.is_empty() => sub_packages
+                .into_iter()
+                .map(|sub_package| match sub_package {
+                    toml::Value::Table(sub_package) => Ok(sub_package),
+                    value => Err(Error::InvalidManifestError(value)),
+                })
+                .collect::<crate::Result<_>>()?,
+            Some(value) => return Err(Error::InvalidManifestError(value)),
         };
+        let patterns = sub_packages
+            .iter()
+            .map(|sub_package| {
+                manifest_strings(sub_package, "files")?
+                    .into_iter()
+                    .map(|pattern| {
+                        Pattern::new(&pattern)
+                            .map_err(|_| Error::InvalidManifestError(toml::Value::String(pattern)))
+                    })
+                    .collect::<crate::Result<Vec<_>>>()
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        let owners: Vec<usize> = files
+            .iter()
+            .map(|file| {
+                patterns
+                    .iter()
+                    .position(|patterns| patterns.iter().any(|pattern| pattern.matches_path(file)))
+                    .unwrap_or(0)
+            })
+            .collect();
+        sub_packages
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut sub_package)| {
+                let owned = |owner: &usize| *owner == index;
+                let directories: HashSet<&Path> = files
+                    .iter()
+                    .zip(&owners)
+                    .filter(|(_, owner)| owned(owner))
+                    .flat_map(|(file, _)| file.ancestors().skip(1))
+                    .collect();
+                let files = files
+                    .iter()
+                    .zip(&owners)
+                    .filter(|(file, owner)| owned(owner) || directories.contains(file.as_path()))
+                    .map(|(file, _)| file.clone())
+                    .collect();
+                sub_package.remove("files");
+                let mut metadata = shared.clone();
+                metadata.extend(sub_package);
+                Self::from_metadata(metadata, files)
+            })
+            .collect()
+    }
+
+    /// Provide a package from the contents of its manifest and its list of files.
+    fn from_metadata(metadata: toml::value::Table, files: Vec<PathBuf>) -> crate::Result<Self> {
         let name = if let toml::Value::String(name) = metadata["name"].clone() {
             name
         } else {
             return Err(Error::InvalidManifestError(metadata["name"].clone()));
         };
-        // TODO: Read a version out of the file.
-        let version = Version::Unknown;
+        let version = match manifest_string(&metadata, "version")? {
+            Some(version) => Version::parse(&version)?,
+            None => Version::Unknown,
+        };
+        let dependencies = manifest_strings(&metadata, "dependencies")?;
+        // Optional dependencies are written like PKGBUILD optdepends: "name: reason".
+        let optional_dependencies = manifest_strings(&metadata, "optional_dependencies")?
+            .into_iter()
+            .map(|entry| match entry.split_once(':') {
+                Some((name, reason)) => (name.trim().to_owned(), reason.trim().to_owned()),
+                None => (entry.trim().to_owned(), String::new()),
+            })
+            .collect();
+        let description = manifest_string(&metadata, "description")?;
+        let architecture = manifest_string(&metadata, "architecture")?
+            .filter(|architecture| architecture != "any");
+        let provides = manifest_strings(&metadata, "provides")?;
+        let conflicts = manifest_strings(&metadata, "conflicts")?;
+        let changelog = manifest_string(&metadata, "changelog")?;
+        let changelog_url = manifest_string(&metadata, "changelog_url")?;
+        let size = match metadata.get("size") {
+            None => None,
+            Some(toml::Value::Integer(size)) if *size >= 0 => Some(*size as u64),
+            Some(value) => return Err(Error::InvalidManifestError(value.clone())),
+        };
         Ok(Self {
-            name,
-            version,
-            state: InstallState::Uninstalled,
             files,
-            local_path: None,
+            dependencies,
+            optional_dependencies,
+            description,
+            provides,
+            conflicts,
+            size,
+            architecture,
+            changelog,
+            changelog_url,
+            ..Self::new(name, version)
         })
     }
 
@@ -165,10 +921,127 @@ impl Package {
         PathBuf::from(format!("{}-{}.tar.xz", self.name, self.version))
     }
 
+    /// Check if two packages are the same package from the same repository.
+    /// Unlike `==`, this tells apart identical versions published by
+    /// different repositories, which may not have the same contents.
+    pub fn same_source(&self, other: &Self) -> bool {
+        self == other && self.repository == other.repository
+    }
+
+    /// Check if the name of the package matches a query. Every kind of query is
+    /// case sensitive, and invalid patterns match nothing.
+    /// ```
+    /// # use mix::{Package, PackageQuery, Version};
+    /// let package = Package::new("libfoo", Version::Unknown);
+    /// assert!(package.matches(&PackageQuery::Exact(String::from("libfoo"))));
+    /// assert!(!package.matches(&PackageQuery::Exact(String::from("libf"))));
+    /// assert!(!package.matches(&PackageQuery::Exact(String::from("LibFoo"))));
+    /// assert!(package.matches(&PackageQuery::Glob(String::from("lib*"))));
+    /// assert!(!package.matches(&PackageQuery::Glob(String::from("foo*"))));
+    /// assert!(!package.matches(&PackageQuery::Glob(String::from("LIB*"))));
+    /// assert!(package.matches(&PackageQuery::Regex(String::from("^lib"))));
+    /// assert!(package.matches(&PackageQuery::Regex(String::from("fo+"))));
+    /// assert!(!package.matches(&PackageQuery::Regex(String::from("^foo"))));
+    /// assert!(!package.matches(&PackageQuery::Regex(String::from("FOO"))));
+    /// assert!(!package.matches(&PackageQuery::Regex(String::from("("))));
+    /// ```
+    pub fn matches(&self, query: &PackageQuery) -> bool {
+        match query {
+            PackageQuery::Exact(name) => &self.name == name,
+            PackageQuery::Glob(pattern) => Pattern::new(pattern)
+                .map(|pattern| pattern.matches(&self.name))
+                .unwrap_or(false),
+            PackageQuery::Regex(pattern) => regex::Regex::new(pattern)
+                .map(|pattern| pattern.is_match(&self.name))
+                .unwrap_or(false),
+        }
+    }
+
+    /// Check if the packages can't be installed together. Either package
+    /// declaring a conflict with the other is enough.
+    /// ```
+    /// # use mix::{Package, Version};
+    /// let mut foo = Package::new("foo", Version::Unknown);
+    /// foo.conflicts.push(String::from("bar"));
+    /// let bar = Package::new("bar", Version::Unknown);
+    /// assert!(foo.conflicts_with(&bar));
+    /// assert!(bar.conflicts_with(&foo));
+    /// ```
+    pub fn conflicts_with(&self, other: &Self) -> bool {
+        self.conflicts.contains(&other.name) || other.conflicts.contains(&self.name)
+    }
+
+    /// Provide the files of the package that don't exist within the given root
+    /// of the filesystem.
+    pub fn missing_files(&self, root: &Path, filesystem: &dyn FileSystem) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|file| !filesystem.exists(&root.join(file)))
+            .cloned()
+            .collect()
+    }
+
+    /// Check if the package can be installed on a system of the given architecture.
+    /// ```
+    /// # use mix::{Package, Version};
+    /// let mut package = Package::new("foo", Version::Unknown);
+    /// assert!(package.supports_architecture("x86_64"));
+    /// package.architecture = Some(String::from("aarch64"));
+    /// assert!(package.supports_architecture("aarch64"));
+    /// assert!(!package.supports_architecture("x86_64"));
+    /// ```
+    pub fn supports_architecture(&self, architecture: &str) -> bool {
+        self.architecture
+            .as_deref()
+            .is_none_or(|supported| supported == architecture)
+    }
+
+    /// Provide an error if the package can't be installed on a system of the given architecture.
+    pub fn check_architecture(&self, architecture: &str) -> crate::Result<()> {
+        match &self.architecture {
+            Some(found) if !self.supports_architecture(architecture) => {
+                Err(Error::ArchitectureMismatch {
+                    package: self.name.clone(),
+                    expected: architecture.to_owned(),
+                    found: found.clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Mark the package as manually installed. This does *not* install it.
     pub fn mark_as_manually_installed(&mut self) {
         self.state = InstallState::Manual;
     }
+
+    /// Change why an installed package is installed, between
+    /// [Manual](InstallState::Manual) and [Dependency](InstallState::Dependency).
+    /// Installing and removing are not state changes, and produce errors.
+    /// ```
+    /// # use mix::{InstallState, Package, Version};
+    /// let mut package = Package::new("foo", Version::Unknown);
+    /// assert!(package.set_state(InstallState::Manual).is_err());
+    /// package.state = InstallState::Dependency;
+    /// package.set_state(InstallState::Manual)?;
+    /// assert_eq!(package.state, InstallState::Manual);
+    /// assert!(package.set_state(InstallState::Uninstalled).is_err());
+    /// # Ok::<(), mix::Error>(())
+    /// ```
+    pub fn set_state(&mut self, state: InstallState) -> crate::Result<()> {
+        match (&self.state, &state) {
+            (InstallState::Uninstalled, _) => Err(Error::PackageNotInstalled),
+            (_, InstallState::Uninstalled) => Err(Error::InvalidStateTransition {
+                package: self.name.clone(),
+                from: self.state.clone(),
+                to: state,
+            }),
+            _ => {
+                self.state = state;
+                Ok(())
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for Package {
@@ -178,14 +1051,110 @@ impl std::fmt::Display for Package {
 }
 
 /// Some fields are allowed to differ between two packages, such as the path.
+/// The repository is also ignored: to users, a package is its name and
+/// version, wherever it came from. Use [same_source](Package::same_source)
+/// when the origin matters.
 impl PartialEq for Package {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name && self.version == other.version
     }
 }
 
+/// A summary of a package's metadata, for showing a single package in detail.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackageInfo {
+    /// The package's name.
+    pub name: String,
+    /// The package's version.
+    pub version: Version,
+    /// The installation state of the package.
+    pub state: InstallState,
+    /// A short description of the package.
+    pub description: Option<String>,
+    /// The names of the packages this package depends on.
+    pub dependencies: Vec<String>,
+    /// Packages that enable extra features of this package, with the reason for each.
+    pub optional_dependencies: Vec<(String, String)>,
+    /// The names of other packages this package can stand in for.
+    pub provides: Vec<String>,
+    /// The names of the packages that can't be installed alongside this package.
+    pub conflicts: Vec<String>,
+    /// The total size of the package's files once installed, in bytes.
+    pub installed_size: Option<u64>,
+    /// How many files the package contains.
+    pub file_count: usize,
+    /// The repository the package came from, or None for local packages.
+    pub repository: Option<String>,
+}
+
+impl From<&Package> for PackageInfo {
+    fn from(package: &Package) -> Self {
+        Self {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            state: package.state.clone(),
+            description: package.description.clone(),
+            dependencies: package.dependencies.clone(),
+            optional_dependencies: package.optional_dependencies.clone(),
+            provides: package.provides.clone(),
+            conflicts: package.conflicts.clone(),
+            installed_size: package.size,
+            file_count: package.files.len(),
+            repository: package.repository.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for PackageInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /// Show an empty list as "None" rather than as nothing.
+        fn list(items: &[String]) -> String {
+            if items.is_empty() {
+                String::from("None")
+            } else {
+                items.join(" ")
+            }
+        }
+        writeln!(f, "Name           : {}", self.name)?;
+        writeln!(f, "Version        : {}", self.version)?;
+        match self.state {
+            InstallState::Uninstalled => writeln!(f, "State          : Not installed")?,
+            ref state => writeln!(f, "State          : {}", state)?,
+        }
+        writeln!(
+            f,
+            "Description    : {}",
+            self.description.as_deref().unwrap_or("None")
+        )?;
+        writeln!(f, "Depends On     : {}", list(&self.dependencies))?;
+        if self.optional_dependencies.is_empty() {
+            writeln!(f, "Optional Deps  : None")?;
+        }
+        for (index, dependency) in self.optional_dependencies.iter().enumerate() {
+            let label = if index == 0 {
+                "Optional Deps  :"
+            } else {
+                "                "
+            };
+            writeln!(f, "{} {}", label, format_optional_dependency(dependency))?;
+        }
+        writeln!(f, "Provides       : {}", list(&self.provides))?;
+        writeln!(f, "Conflicts With : {}", list(&self.conflicts))?;
+        match self.installed_size {
+            Some(size) => writeln!(f, "Installed Size : {}", format_bytes(size))?,
+            None => writeln!(f, "Installed Size : Unknown")?,
+        }
+        writeln!(f, "Files          : {}", self.file_count)?;
+        write!(
+            f,
+            "Repository     : {}",
+            self.repository.as_deref().unwrap_or("None (local package)")
+        )
+    }
+}
+
 /// The current state of the package.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum InstallState {
     /// The package was installed intentionally, and can not be automatically removed.
     Manual,
@@ -193,6 +1162,7 @@ pub enum InstallState {
     /// It can be removed if and only if no other packages depend on it.
     Dependency,
     /// The package is not currently installed.
+    #[default]
     Uninstalled,
 }
 
@@ -210,6 +1180,18 @@ impl std::fmt::Display for InstallState {
     }
 }
 
+/// A query for packages by name, used by [Package::matches].
+#[derive(Clone, Debug, PartialEq)]
+pub enum PackageQuery {
+    /// The name is exactly the given string.
+    Exact(String),
+    /// The name matches a glob pattern, such as `lib*`.
+    Glob(String),
+    /// Some part of the name matches a regular expression. Use `^` and `$`
+    /// to match the whole name.
+    Regex(String),
+}
+
 /// A package's version.
 /// # Examples:
 /// ```rust
@@ -230,36 +1212,305 @@ impl std::fmt::Display for InstallState {
 /// assert!(Version::SemVer(0, 0, 1) > Version::SemVer(0, 0, 0));
 /// assert!(Version::SemVer(1, 0, 0) < Version::SemVer(2, 1, 0));
 /// ```
-#[derive(Clone, Debug, Eq, Serialize, Deserialize)]
+/// Numeric versions are compared component by component, and a version that
+/// the other starts with is older, so `1.2` is older than `1.2.0`. Semantic
+/// versions are compared with them as three components.
+/// ```rust
+/// # use mix::package::Version;
+/// let version = |version| Version::parse(version).unwrap();
+/// assert!(version("1.2.3.4") > version("1.2.3"));
+/// assert!(version("1.2.3.4") < version("1.2.4"));
+/// assert!(version("1.2") < version("1.2.0"));
+/// assert!(version("1.2") < version("1.2.3.4"));
+/// assert!(version("1.10") > version("1.9.9"));
+/// assert!(version("20231005") > version("20230930"));
+/// assert!(version("2023.10.05") > version("2023.9.30"));
+/// assert!(version("1") > Version::Unknown);
+/// ```
+/// Pre-releases follow SemVer precedence: they're older than the release,
+/// and compared identifier by identifier, with numeric identifiers compared
+/// as numbers and older than alphanumeric ones. Build metadata is ignored.
+/// ```rust
+/// # use mix::package::Version;
+/// let version = |version| Version::parse(version).unwrap();
+/// assert!(version("1.0.0-rc1") < version("1.0.0"));
+/// assert!(version("1.0.0-rc1") > version("0.9.9"));
+/// assert!(version("1.0.0-alpha") < version("1.0.0-alpha.1"));
+/// assert!(version("1.0.0-alpha.1") < version("1.0.0-alpha.beta"));
+/// assert!(version("1.0.0-beta.2") < version("1.0.0-beta.11"));
+/// assert!(version("1.0.0-beta.11") < version("1.0.0-rc.1"));
+/// assert_eq!(version("1.0.0+build5"), version("1.0.0"));
+/// assert_eq!(version("1.0.0-rc1+build5"), version("1.0.0-rc1+build6"));
+/// assert!(version("0.0.0-rc1") > Version::Unknown);
+/// ```
+/// Like with PKGBUILDs, the epoch outweighs everything else, and the pkgrel
+/// is only compared between otherwise equal versions. A version without a
+/// pkgrel is older than one with it.
+/// ```rust
+/// # use mix::package::Version;
+/// let version = |version| Version::parse(version).unwrap();
+/// assert!(version("2:1.0.0-1") > version("1:9.9.9-9"));
+/// assert!(version("1:0.1") > version("9.9.9"));
+/// assert!(version("1.0.0-2") > version("1.0.0-1"));
+/// assert!(version("1.0.1-1") > version("1.0.0-2"));
+/// assert!(version("1.0.0-1") > version("1.0.0"));
+/// assert!(version("1.0.0-rc1-1") < version("1.0.0-1"));
+/// assert_eq!(version("0:1.0.0"), version("1.0.0"));
+/// ```
+#[derive(Clone, Debug, Eq)]
 pub enum Version {
     /// A semantic version.
     SemVer(u32, u32, u32),
+    /// A version of any other number of numeric components, such as
+    /// `1.2.3.4` or a date like `20231005`.
+    Numeric(Vec<u32>),
+    /// A version with any of an epoch, a pre-release identifier, build
+    /// metadata, or a pkgrel, such as `1.0.0-rc1`, `1.0.0+build5`, or `1:1.0.0-2`.
+    Tagged {
+        /// The epoch, before the `:`, which is 0 when it's left out.
+        epoch: u32,
+        /// The numeric components of the release the version leads up to.
+        release: Vec<u32>,
+        /// The pre-release identifier, after the `-`.
+        pre_release: Option<String>,
+        /// The build metadata, after the `+`. It doesn't affect ordering.
+        build: Option<String>,
+        /// The release number of the package itself, after the final `-`.
+        pkgrel: Option<u32>,
+    },
     /// The version is unknown and/or doesn't matter. It's always smaller than any other version.
     Unknown,
 }
 
-impl Ord for Version {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        use std::cmp::Ordering;
-        match (self, other) {
-            (Self::SemVer(maj1, min1, rev1), Self::SemVer(maj2, min2, rev2)) => {
-                if maj1 != maj2 {
-                    maj1.cmp(maj2)
-                } else if min1 != min2 {
-                    min1.cmp(min2)
-                } else if rev1 == rev2 {
-                    Ordering::Equal
-                } else {
-                    rev1.cmp(rev2)
-                }
+impl Version {
+    /// Parse a version from a string: `major.minor.patch` or any other number
+    /// of numeric components, optionally preceded by an `epoch:` and followed
+    /// by a `-pre-release`, `+build` metadata, and a `-pkgrel`, or `unknown`.
+    /// A final `-` followed only by digits is always a pkgrel.
+    /// ```
+    /// # use mix::Version;
+    /// assert_eq!(Version::parse("1.2.3")?, Version::SemVer(1, 2, 3));
+    /// assert_eq!(Version::parse("1.2")?, Version::Numeric(vec![1, 2]));
+    /// assert_eq!(Version::parse("1.2.3.4")?, Version::Numeric(vec![1, 2, 3, 4]));
+    /// assert_eq!(Version::parse("20231005")?, Version::Numeric(vec![20231005]));
+    /// assert_eq!(Version::parse("unknown")?, Version::Unknown);
+    /// assert_eq!(Version::parse("1.2.3")?.to_string(), "1.2.3");
+    /// assert!(Version::parse("1..2").is_err());
+    /// assert!(Version::parse("1.2.x").is_err());
+    /// assert_eq!(
+    ///     Version::parse("1.0.0-rc.1+build5")?,
+    ///     Version::Tagged {
+    ///         epoch: 0,
+    ///         release: vec![1, 0, 0],
+    ///         pre_release: Some(String::from("rc.1")),
+    ///         build: Some(String::from("build5")),
+    ///         pkgrel: None,
+    ///     }
+    /// );
+    /// assert_eq!(Version::parse("1.0.0-rc.1+build5")?.to_string(), "1.0.0-rc.1+build5");
+    /// assert_eq!(
+    ///     Version::parse("2:1.0.0-3")?,
+    ///     Version::Tagged {
+    ///         epoch: 2,
+    ///         release: vec![1, 0, 0],
+    ///         pre_release: None,
+    ///         build: None,
+    ///         pkgrel: Some(3),
+    ///     }
+    /// );
+    /// assert_eq!(Version::parse("2:1.0.0-rc1-3")?.to_string(), "2:1.0.0-rc1-3");
+    /// assert!(Version::parse("x:1.0.0").is_err());
+    /// assert!(Version::parse("1.0.0-").is_err());
+    /// assert!(Version::parse("1.0.0-rc..1").is_err());
+    /// assert!(Version::parse("1.0.0+build_5").is_err());
+    /// assert!(Version::parse("").is_err());
+    /// # Ok::<(), mix::Error>(())
+    /// ```
+    pub fn parse(version: &str) -> crate::Result<Self> {
+        if version == "unknown" {
+            return Ok(Self::Unknown);
+        }
+        let invalid = || Error::InvalidVersion(version.to_owned());
+        let (epoch, rest) = match version.split_once(':') {
+            Some((epoch, rest)) => (epoch.parse().map_err(|_| invalid())?, rest),
+            None => (0, version),
+        };
+        let (rest, pkgrel) = match rest.rsplit_once('-') {
+            Some((rest, pkgrel)) if pkgrel.bytes().all(|byte| byte.is_ascii_digit()) => {
+                (rest, Some(pkgrel.parse().map_err(|_| invalid())?))
+            }
+            _ => (rest, None),
+        };
+        let (rest, build) = match rest.split_once('+') {
+            Some((rest, build)) => (rest, Some(build)),
+            None => (rest, None),
+        };
+        let (release, pre_release) = match rest.split_once('-') {
+            Some((release, pre_release)) => (release, Some(pre_release)),
+            None => (rest, None),
+        };
+        // Tags are dot separated identifiers of ASCII letters, digits, and `-`.
+        let valid_tag = |tag: &str| {
+            tag.split('.').all(|identifier| {
+                !identifier.is_empty()
+                    && identifier
+                        .bytes()
+                        .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+            })
+        };
+        if !pre_release.into_iter().chain(build).all(valid_tag) {
+            return Err(invalid());
+        }
+        let parts = release
+            .split('.')
+            .map(|part| part.parse().map_err(|_| invalid()))
+            .collect::<crate::Result<Vec<u32>>>()?;
+        match (parts.as_slice(), epoch, pre_release, build, pkgrel) {
+            ([major, minor, patch], 0, None, None, None) => {
+                Ok(Self::SemVer(*major, *minor, *patch))
+            }
+            (_, 0, None, None, None) => Ok(Self::Numeric(parts)),
+            _ => Ok(Self::Tagged {
+                epoch,
+                release: parts,
+                pre_release: pre_release.map(str::to_owned),
+                build: build.map(str::to_owned),
+                pkgrel,
+            }),
+        }
+    }
+
+    /// Provide the numeric components of the version, or None if it's unknown.
+    fn components(&self) -> Option<Vec<u32>> {
+        match self {
+            Self::SemVer(major, minor, patch) => Some(vec![*major, *minor, *patch]),
+            Self::Numeric(components) => Some(components.clone()),
+            Self::Tagged { release, .. } => Some(release.clone()),
+            Self::Unknown => None,
+        }
+    }
+
+    /// Provide the epoch of the version, which is 0 unless it's given.
+    fn epoch(&self) -> u32 {
+        match self {
+            Self::Tagged { epoch, .. } => *epoch,
+            _ => 0,
+        }
+    }
+
+    /// Provide the pkgrel of the version, if it has one.
+    fn pkgrel(&self) -> Option<u32> {
+        match self {
+            Self::Tagged { pkgrel, .. } => *pkgrel,
+            _ => None,
+        }
+    }
+
+    /// Provide the pre-release identifier of the version, if it has one.
+    fn pre_release(&self) -> Option<&str> {
+        match self {
+            Self::Tagged { pre_release, .. } => pre_release.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Compare the pre-release identifiers of two versions of the same release.
+/// Numeric identifiers are older than alphanumeric ones, and a pre-release
+/// the other starts with is older.
+fn compare_pre_releases(a: &str, b: &str) -> std::cmp::Ordering {
+    // Ok sorts before Err, putting numbers before alphanumeric identifiers.
+    let identifier = |identifier: &str| {
+        if identifier.bytes().all(|byte| byte.is_ascii_digit()) {
+            identifier.parse::<u64>().map_err(|_| identifier.to_owned())
+        } else {
+            Err(identifier.to_owned())
+        }
+    };
+    a.split('.')
+        .map(identifier)
+        .cmp(b.split('.').map(identifier))
+}
+
+/// Versions are stored as strings, such as `"1.2.3"` or `"unknown"`.
+/// ```
+/// # use mix::Version;
+/// for version in &[
+///     Version::SemVer(1, 2, 3),
+///     Version::Numeric(vec![1, 2, 3, 4]),
+///     Version::parse("1.0.0-rc1+build5").unwrap(),
+///     Version::parse("1:1.0.0-2").unwrap(),
+///     Version::Unknown,
+/// ] {
+///     let json = serde_json::to_string(version).unwrap();
+///     assert_eq!(&serde_json::from_str::<Version>(&json).unwrap(), version);
+/// }
+/// assert_eq!(serde_json::to_string(&Version::SemVer(1, 2, 3)).unwrap(), "\"1.2.3\"");
+/// assert_eq!(serde_json::to_string(&Version::Unknown).unwrap(), "\"unknown\"");
+/// ```
+/// Databases saved before then stored them as an enum, which still loads.
+/// ```
+/// # use mix::Version;
+/// let semver: Version = serde_json::from_str(r#"{"SemVer":[1,2,3]}"#).unwrap();
+/// assert_eq!(semver, Version::SemVer(1, 2, 3));
+/// let unknown: Version = serde_json::from_str(r#""Unknown""#).unwrap();
+/// assert_eq!(unknown, Version::Unknown);
+/// ```
+impl Serialize for Version {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::SemVer(_, _, _) | Self::Numeric(_) | Self::Tagged { .. } => {
+                serializer.collect_str(self)
             }
-            (Self::SemVer(_, _, _), Self::Unknown) => Ordering::Greater,
-            (Self::Unknown, Self::SemVer(_, _, _)) => Ordering::Less,
-            (Self::Unknown, Self::Unknown) => Ordering::Equal,
+            Self::Unknown => serializer.serialize_str("unknown"),
         }
     }
 }
 
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// How versions were stored when they were only semantic versions.
+        #[derive(Deserialize)]
+        enum Legacy {
+            SemVer(u32, u32, u32),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Stored {
+            Text(String),
+            Legacy(Legacy),
+        }
+
+        match Stored::deserialize(deserializer)? {
+            // The old unit variant is stored as its name.
+            Stored::Text(version) if version == "Unknown" => Ok(Self::Unknown),
+            Stored::Text(version) => Self::parse(&version).map_err(serde::de::Error::custom),
+            Stored::Legacy(Legacy::SemVer(major, minor, patch)) => {
+                Ok(Self::SemVer(major, minor, patch))
+            }
+        }
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // Unknown versions have no components, and None is less than any Some.
+        // Vec compares lexicographically, with a prefix ordered first.
+        self.epoch()
+            .cmp(&other.epoch())
+            .then_with(|| self.components().cmp(&other.components()))
+            .then_with(|| match (self.pre_release(), other.pre_release()) {
+                (None, None) => std::cmp::Ordering::Equal,
+                // A release is newer than its pre-releases.
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (Some(a), Some(b)) => compare_pre_releases(a, b),
+            })
+            .then_with(|| self.pkgrel().cmp(&other.pkgrel()))
+    }
+}
+
 impl PartialOrd for Version {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -268,16 +1519,7 @@ impl PartialOrd for Version {
 
 impl PartialEq for Version {
     fn eq(&self, other: &Self) -> bool {
-        match self {
-            Self::SemVer(maj1, min1, rev1) => match other {
-                Self::SemVer(maj2, min2, rev2) => maj1 == maj2 && min1 == min2 && rev1 == rev2,
-                Self::Unknown => false,
-            },
-            Self::Unknown => match other {
-                Self::SemVer(_, _, _) => false,
-                Self::Unknown => true,
-            },
-        }
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
@@ -285,44 +1527,360 @@ impl std::fmt::Display for Version {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::SemVer(x, y, z) => write!(f, "{}.{}.{}", x, y, z),
+            Self::Numeric(components) => {
+                let components: Vec<String> = components.iter().map(u32::to_string).collect();
+                write!(f, "{}", components.join("."))
+            }
+            Self::Tagged {
+                epoch,
+                release,
+                pre_release,
+                build,
+                pkgrel,
+            } => {
+                if *epoch != 0 {
+                    write!(f, "{}:", epoch)?;
+                }
+                let release: Vec<String> = release.iter().map(u32::to_string).collect();
+                write!(f, "{}", release.join("."))?;
+                if let Some(pre_release) = pre_release {
+                    write!(f, "-{}", pre_release)?;
+                }
+                if let Some(build) = build {
+                    write!(f, "+{}", build)?;
+                }
+                if let Some(pkgrel) = pkgrel {
+                    write!(f, "-{}", pkgrel)?;
+                }
+                Ok(())
+            }
             Self::Unknown => write!(f, "Unknown version"),
         }
     }
 }
 
+/// Format an optional dependency as its name followed by the reason, if there is one.
+/// ```
+/// # use mix::package::format_optional_dependency;
+/// let dependency = (String::from("bar"), String::from("enables X"));
+/// assert_eq!(format_optional_dependency(&dependency), "bar (enables X)");
+/// ```
+pub fn format_optional_dependency((name, reason): &(String, String)) -> String {
+    if reason.is_empty() {
+        name.clone()
+    } else {
+        format!("{} ({})", name, reason)
+    }
+}
+
+/// Check if the entry of a package is its manifest, which is only ever at the
+/// root of the package. Tarballs made from within a directory can have a
+/// leading `./` on every entry.
+fn is_manifest(entry: &Path, manifest_name: &str) -> bool {
+    entry
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .eq(Path::new(manifest_name).components())
+}
+
+/// Parse a package manifest. Manifests are TOML, but JSON manifests (starting
+/// with `{`) are also accepted and read into the same table.
+fn parse_manifest(manifest: &str) -> crate::Result<toml::value::Table> {
+    let metadata = match manifest.parse::<toml::Value>() {
+        Err(error) if manifest.trim_start().starts_with('{') => {
+            serde_json::from_str::<toml::Value>(manifest).map_err(|_| error)
+        }
+        metadata => metadata,
+    };
+    match metadata {
+        Ok(toml::Value::Table(metadata)) => Ok(metadata),
+        Ok(value) => Err(Error::InvalidManifestError(value)),
+        Err(error) => Err(Error::ManifestParseError(error)),
+    }
+}
+
+/// Check if a manifest declares several sub-packages.
+fn declares_sub_packages(manifest: &str) -> bool {
+    parse_manifest(manifest).is_ok_and(|metadata| metadata.contains_key("packages"))
+}
+
+/// Provide the first of the packages read from a tarball or package tree.
+fn first_package(packages: Vec<Package>) -> crate::Result<Package> {
+    packages
+        .into_iter()
+        .next()
+        .ok_or(Error::InvalidPackageError)
+}
+
+/// Read an optional string out of a package manifest.
+fn manifest_string(metadata: &toml::value::Table, key: &str) -> crate::Result<Option<String>> {
+    match metadata.get(key) {
+        None => Ok(None),
+        Some(toml::Value::String(value)) => Ok(Some(value.clone())),
+        Some(value) => Err(Error::InvalidManifestError(value.clone())),
+    }
+}
+
+/// Read an optional array of strings out of a package manifest.
+fn manifest_strings(metadata: &toml::value::Table, key: &str) -> crate::Result<Vec<String>> {
+    match metadata.get(key) {
+        None => Ok(vec![]),
+        Some(toml::Value::Array(values)) => values
+            .iter()
+            .map(|value| match value {
+                toml::Value::String(value) => Ok(value.clone()),
+                value => Err(Error::InvalidManifestError(value.clone())),
+            })
+            .collect(),
+        Some(value) => Err(Error::InvalidManifestError(value.clone())),
+    }
+}
+
+/// Provide where an entry of a package tarball belongs within the root.
+/// # Errors
+/// Entries that would end up outside of the root are an
+/// [unsafe path](Error::UnsafePath), whether through `..`, an absolute path,
+/// or a symlink within the root leading out of it.
+/// ```
+/// # use mix::{filesystem::OsFileSystem, package::destination};
+/// # use std::path::{Path, PathBuf};
+/// let root = Path::new("/");
+/// assert_eq!(
+///     destination(&OsFileSystem, root, Path::new("usr/bin/foo"))?,
+///     PathBuf::from("/usr/bin/foo")
+/// );
+/// assert!(destination(&OsFileSystem, root, Path::new("../../etc/passwd")).is_err());
+/// assert!(destination(&OsFileSystem, root, Path::new("/etc/passwd")).is_err());
+/// # Ok::<(), mix::Error>(())
+/// ```
+pub fn destination(
+    filesystem: &dyn FileSystem,
+    root: &Path,
+    entry: &Path,
+) -> crate::Result<PathBuf> {
+    let unsafe_path = || Error::UnsafePath(entry.to_owned());
+    if !entry
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(unsafe_path());
+    }
+    let path = root.join(entry);
+    // The entry itself may not exist yet, but the directory it's placed in
+    // does, and symlinks on the way there must not lead out of the root.
+    if let Some(parent) = path.parent().filter(|parent| filesystem.exists(parent)) {
+        if !filesystem
+            .canonicalize(parent)?
+            .starts_with(filesystem.canonicalize(root)?)
+        {
+            return Err(unsafe_path());
+        }
+    }
+    Ok(path)
+}
+
+/// Place the given files of a package tarball into the root. A tarball of
+/// several sub-packages holds files that belong to the other sub-packages,
+/// which are left out. The manifest is never one of the files. The
+/// directories created for files that have no entries of their own are
+/// provided, relative to the root, so they can be removed with the package.
+fn place_entries(
+    tarball: impl Read,
+    files: &[PathBuf],
+    config: &Config,
+    filesystem: &dyn FileSystem,
+    on_file: &mut dyn FnMut(&Path),
+) -> crate::Result<Vec<PathBuf>> {
+    let files: HashSet<&Path> = files.iter().map(PathBuf::as_path).collect();
+    let mut archive = Archive::new(XzDecoder::new(tarball));
+    let mut created = vec![];
+    let mut links = vec![];
+    let mut directories = vec![];
+    let mut implied = HashSet::new();
+    let mut place = || -> crate::Result<()> {
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            if files.contains(path.as_path()) {
+                let placed =
+                    place_tracked(&mut entry, config, filesystem, &mut created, &mut links)?;
+                for directory in placed {
+                    if !files.contains(directory.as_path()) && implied.insert(directory.clone()) {
+                        directories.push(directory);
+                    }
+                }
+                on_file(&path);
+            }
+        }
+        place_links(&links, filesystem, &mut created)
+    };
+    if let Err(error) = place() {
+        roll_back(&created, filesystem);
+        return Err(error);
+    }
+    Ok(directories)
+}
+
+/// Place an entry like [place_entry](place_entry) along with any missing
+/// [parents](create_parents), recording its path in `created` if nothing was
+/// there before, so a failed install can be [rolled back](roll_back).
+fn place_tracked(
+    entry: &mut tar::Entry<impl Read>,
+    config: &Config,
+    filesystem: &dyn FileSystem,
+    created: &mut Vec<PathBuf>,
+    links: &mut Vec<(PathBuf, PathBuf)>,
+) -> crate::Result<Vec<PathBuf>> {
+    // Unsafe paths are refused before anything is created for them.
+    let path = destination(filesystem, &config.root, &entry.path()?)?;
+    let directories = create_parents(&entry.path()?, config, filesystem, created)?;
+    let present = |path: &Path| filesystem.exists(path) || filesystem.is_symlink(path);
+    let existed = present(&path);
+    let result = place_entry(entry, config, filesystem, links);
+    // A file that was only partly written is removed as well.
+    if !existed && present(&path) {
+        created.push(path);
+    }
+    result.map(|()| directories)
+}
+
+/// Create the directories leading to a file that the tarball has no entries
+/// for, like tar does. Each directory created is added to `created`, and is
+/// provided relative to the root. Directories that were already there are
+/// left out, as they belong to the system or to other packages.
+fn create_parents(
+    file: &Path,
+    config: &Config,
+    filesystem: &dyn FileSystem,
+    created: &mut Vec<PathBuf>,
+) -> crate::Result<Vec<PathBuf>> {
+    let mut directories = vec![];
+    let mut parents: Vec<&Path> = file
+        .ancestors()
+        .skip(1)
+        .filter(|parent| parent.file_name().is_some())
+        .collect();
+    // The outermost directory has to be created first.
+    parents.reverse();
+    for parent in parents {
+        let path = destination(filesystem, &config.root, parent)?;
+        if filesystem.exists(&path) {
+            continue;
+        }
+        match filesystem.create_dir(&path) {
+            Ok(()) => {
+                filesystem.set_permissions(&path, config.installed_mode(0o755))?;
+                directories.push(parent.to_owned());
+                created.push(path);
+            }
+            // Another package being installed at the same time created it first.
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(directories)
+}
+
+/// Place the hardlinks that were read before their targets, once every other
+/// entry has been placed. Each one placed is added to `created`.
+fn place_links(
+    links: &[(PathBuf, PathBuf)],
+    filesystem: &dyn FileSystem,
+    created: &mut Vec<PathBuf>,
+) -> crate::Result<()> {
+    for (target, path) in links {
+        filesystem.hard_link(target, path)?;
+        created.push(path.clone());
+    }
+    Ok(())
+}
+
+/// Remove the paths placed by an install that failed, newest first so
+/// directories are emptied before they're reached. Directories that other
+/// packages have since placed files in are left alone.
+fn roll_back(created: &[PathBuf], filesystem: &dyn FileSystem) {
+    for path in created.iter().rev() {
+        let result = if filesystem.is_dir(path) && !filesystem.is_symlink(path) {
+            match filesystem.is_empty_dir(path) {
+                Ok(true) => filesystem.remove_dir(path),
+                Ok(false) => continue,
+                Err(error) => Err(error),
+            }
+        } else {
+            filesystem.remove_file(path)
+        };
+        if let Err(error) = result {
+            eprintln!(
+                "Warning: {} was not able to be removed: {}",
+                path.display(),
+                error
+            );
+        }
+    }
+}
+
 /// The tar crate has been reported to not be designed for unpacking tar files,
 /// opting for support of creating them instead. This will handle placing files
 /// on disk, as well as ensuring permissions work out. If there's a way to do
 /// this transparently through tar, feel free to open a PR with this replaced.
-fn place_entry(entry: &mut tar::Entry<impl Read>) -> crate::Result<()> {
-    let path = PathBuf::from("/").join(entry.path()?);
+/// Hardlink entries carry no contents, so those read before their target are
+/// added to `links` as the target and path, to be [placed](place_links) later.
+fn place_entry(
+    entry: &mut tar::Entry<impl Read>,
+    config: &Config,
+    filesystem: &dyn FileSystem,
+    links: &mut Vec<(PathBuf, PathBuf)>,
+) -> crate::Result<()> {
+    let path = destination(filesystem, &config.root, &entry.path()?)?;
+    let mode = config.installed_mode(entry.header().mode()?);
     match entry.header().entry_type() {
         tar::EntryType::Directory => {
-            if !path.exists() {
-                let result = create_dir(&path);
+            if !filesystem.exists(&path) {
+                let result = filesystem.create_dir(&path);
                 match result {
-                    Ok(_) => {
-                        // Set the permissions of the new directory
-                        let mode = entry.header().mode()?;
-                        let permissions = Permissions::from_mode(mode);
-                        set_permissions(path, permissions)?;
-                    }
+                    Ok(_) => filesystem.set_permissions(&path, mode)?,
+                    // Another package being installed at the same time created it first.
+                    Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
                     Err(error) => return Err(error.into()),
                 }
             }
         }
         tar::EntryType::Regular => {
-            let result = OpenOptions::new().create_new(true).write(true).open(path);
-            match result {
-                Ok(mut file) => {
-                    io::copy(entry, &mut file)?;
+            filesystem.write_file(&path, entry)?;
+            // The file was created with the process umask, not the policy.
+            filesystem.set_permissions(&path, mode)?;
+        }
+        tar::EntryType::Link => {
+            let target = entry.link_name()?.ok_or(Error::InvalidPackageError)?;
+            let target = destination(filesystem, &config.root, &target)?;
+            if filesystem.exists(&target) {
+                filesystem.hard_link(&target, &path)?;
+            } else {
+                links.push((target, path));
+            }
+        }
+        tar::EntryType::Symlink => {
+            let target = entry.link_name()?.ok_or(Error::InvalidPackageError)?;
+            match filesystem.symlink(&target, &path) {
+                Ok(()) => {}
+                // Symlinks can't be overwritten, so an old one is replaced.
+                // Anything else there is left alone, like a file in the way.
+                Err(error)
+                    if error.kind() == io::ErrorKind::AlreadyExists
+                        && filesystem.is_symlink(&path) =>
+                {
+                    filesystem.remove_file(&path)?;
+                    filesystem.symlink(&target, &path)?;
                 }
                 Err(error) => return Err(error.into()),
             }
         }
-        tar::EntryType::Link => todo!(),
-        tar::EntryType::Symlink => todo!(),
-        other_type => unimplemented!("{:?}", other_type),
+        other_type => {
+            return Err(Error::UnsupportedEntry {
+                path: entry.path()?.into_owned(),
+                kind: format!("{:?}", other_type),
+            })
+        }
     }
     Ok(())
 }
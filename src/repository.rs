@@ -0,0 +1,329 @@
+use crate::{
+    config::RepositoryConfig,
+    net::{self, MirrorOrder, Validators},
+    Error, Package,
+};
+use serde::Deserialize;
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The file a repository provides its package index as.
+pub const INDEX_FILENAME: &str = "index.toml";
+/// The file a repository provides the detached signature of its package index as.
+pub const SIGNATURE_FILENAME: &str = "index.toml.sig";
+
+/// A package index as a repository stores it, along with its signature.
+#[derive(Clone, Debug, Default)]
+pub struct SignedIndex {
+    /// The contents of the index file.
+    pub contents: Vec<u8>,
+    /// The detached signature of the contents, or None if the repository
+    /// doesn't sign its index.
+    pub signature: Option<Vec<u8>>,
+}
+
+/// A source of packages, such as a set of HTTP mirrors. The database only
+/// talks to repositories through this trait, so it doesn't need to know how
+/// the packages are transported.
+/// ```
+/// # use mix::{Config, Database, Package, Repository, Version};
+/// # use std::path::Path;
+/// struct Fake;
+///
+/// impl Repository for Fake {
+///     fn name(&self) -> &str {
+///         "fake"
+///     }
+///
+///     fn fetch_index(&self) -> mix::Result<Vec<Package>> {
+///         Ok(vec![Package::new("foo", Version::SemVer(1, 0, 0))])
+///     }
+///
+///     fn fetch_package(&self, package: &Package, _destination: &Path) -> mix::Result<()> {
+///         // The fake repository only lists its packages, without their tarballs.
+///         Err(mix::Error::PackageNotFound(vec![package.name.clone()]))
+///     }
+/// }
+///
+/// let mut database = Database::new_empty(std::env::temp_dir().join("mix-fake-repository"));
+/// // Without a signing key, the fake repository has to be trusted as it is.
+/// database.set_config(Config {
+///     allow_unsigned: true,
+///     ..Config::default()
+/// });
+/// database.synchronize(&Fake)?;
+/// let info = database.info("foo")?;
+/// assert_eq!(info.repository.as_deref(), Some("fake"));
+/// # Ok::<(), mix::Error>(())
+/// ```
+pub trait Repository {
+    /// The name of the repository, as recorded by the packages from it.
+    fn name(&self) -> &str;
+
+    /// Provide every package the repository has available.
+    fn fetch_index(&self) -> crate::Result<Vec<Package>>;
+
+    /// Provide every package the repository has available, unless the index
+    /// hasn't changed since the fetch the validators came from. Backends that
+    /// can't tell always fetch the index, with empty validators.
+    fn fetch_index_if_changed(
+        &self,
+        _validators: &Validators,
+    ) -> crate::Result<Option<(Vec<Package>, Validators)>> {
+        Ok(Some((self.fetch_index()?, Validators::default())))
+    }
+
+    /// Provide the index file as the repository stores it along with its
+    /// signature, unless the index hasn't changed since the fetch the
+    /// validators came from. This is used instead of
+    /// [fetch_index_if_changed](Repository::fetch_index_if_changed) when a
+    /// [signing key](crate::Config::signing_keys) is configured for the
+    /// repository. Backends without an index file can't be signed.
+    fn fetch_signed_index_if_changed(
+        &self,
+        _validators: &Validators,
+    ) -> crate::Result<Option<(SignedIndex, Validators)>> {
+        Err(Error::SignatureVerificationFailed {
+            subject: format!("the package index of {}", self.name()),
+            reason: String::from("the repository can't provide a signed index"),
+        })
+    }
+
+    /// Download the tarball of the package to the destination. If the
+    /// destination already holds the start of the tarball, backends may
+    /// continue from where it ends.
+    fn fetch_package(&self, package: &Package, destination: &Path) -> crate::Result<()>;
+
+    /// Download the detached signature of the package's tarball to the
+    /// destination. This is only used when
+    /// [verify_packages](crate::Config::verify_packages) is set. The
+    /// signature is stored next to the tarball with `.sig` added to its name.
+    fn fetch_package_signature(&self, package: &Package, _destination: &Path) -> crate::Result<()> {
+        Err(Error::SignatureVerificationFailed {
+            subject: format!("the tarball of {}", package.name),
+            reason: String::from("the repository can't provide package signatures"),
+        })
+    }
+}
+
+/// A repository served over HTTP by one or more mirrors.
+#[derive(Clone, Debug)]
+pub struct HttpRepository {
+    name: String,
+    mirrors: Vec<String>,
+    order: MirrorOrder,
+}
+
+impl HttpRepository {
+    /// Provide the repository with the given mirrors, tried in the given order.
+    pub fn new(name: impl Into<String>, mirrors: Vec<String>, order: MirrorOrder) -> Self {
+        Self {
+            name: name.into(),
+            mirrors,
+            order,
+        }
+    }
+
+    /// Provide the repository described by the configuration.
+    pub fn from_config(config: &RepositoryConfig, order: MirrorOrder) -> Self {
+        Self::new(config.name.clone(), config.mirrors.clone(), order)
+    }
+}
+
+impl Repository for HttpRepository {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_index(&self) -> crate::Result<Vec<Package>> {
+        Ok(self
+            .fetch_index_if_changed(&Validators::default())?
+            .map(|(packages, _)| packages)
+            .unwrap_or_default())
+    }
+
+    fn fetch_index_if_changed(
+        &self,
+        validators: &Validators,
+    ) -> crate::Result<Option<(Vec<Package>, Validators)>> {
+        if self.mirrors.is_empty() {
+            return Err(Error::NoMirrors(self.name.clone()));
+        }
+        match net::download_if_changed(&self.mirrors, INDEX_FILENAME, self.order, validators)? {
+            Some((index, validators)) => Ok(Some((parse_index(&self.name, &index)?, validators))),
+            None => Ok(None),
+        }
+    }
+
+    fn fetch_signed_index_if_changed(
+        &self,
+        validators: &Validators,
+    ) -> crate::Result<Option<(SignedIndex, Validators)>> {
+        if self.mirrors.is_empty() {
+            return Err(Error::NoMirrors(self.name.clone()));
+        }
+        let (contents, validators) = match net::download_if_changed(
+            &self.mirrors,
+            INDEX_FILENAME,
+            self.order,
+            validators,
+        )? {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        // A signature no mirror provides is the same as an unsigned index.
+        let signature = net::download_if_changed(
+            &self.mirrors,
+            SIGNATURE_FILENAME,
+            self.order,
+            &Validators::default(),
+        )
+        .ok()
+        .flatten()
+        .map(|(signature, _)| signature);
+        Ok(Some((
+            SignedIndex {
+                contents,
+                signature,
+            },
+            validators,
+        )))
+    }
+
+    fn fetch_package(&self, package: &Package, destination: &Path) -> crate::Result<()> {
+        if self.mirrors.is_empty() {
+            return Err(Error::NoMirrors(package.name.clone()));
+        }
+        let filename = package.get_filename().to_string_lossy().into_owned();
+        let mirror = net::download(&self.mirrors, &filename, self.order, destination)?;
+        eprintln!("Downloaded {} from {}", filename, mirror);
+        Ok(())
+    }
+
+    fn fetch_package_signature(&self, package: &Package, destination: &Path) -> crate::Result<()> {
+        if self.mirrors.is_empty() {
+            return Err(Error::NoMirrors(package.name.clone()));
+        }
+        let mut filename = package.get_filename().into_os_string();
+        filename.push(".sig");
+        net::download(
+            &self.mirrors,
+            &filename.to_string_lossy(),
+            self.order,
+            destination,
+        )?;
+        Ok(())
+    }
+}
+
+/// A repository in a local directory, holding its index and the tarballs of
+/// its packages. This serves air-gapped systems and local mirrors.
+#[derive(Clone, Debug)]
+pub struct DirectoryRepository {
+    name: String,
+    directory: PathBuf,
+}
+
+impl DirectoryRepository {
+    /// Provide the repository in the given directory.
+    pub fn new(name: impl Into<String>, directory: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            directory: directory.into(),
+        }
+    }
+}
+
+impl Repository for DirectoryRepository {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch_index(&self) -> crate::Result<Vec<Package>> {
+        let index = fs::read(self.directory.join(INDEX_FILENAME))?;
+        parse_index(&self.name, &index)
+    }
+
+    fn fetch_signed_index_if_changed(
+        &self,
+        _validators: &Validators,
+    ) -> crate::Result<Option<(SignedIndex, Validators)>> {
+        let contents = fs::read(self.directory.join(INDEX_FILENAME))?;
+        let signature = match fs::read(self.directory.join(SIGNATURE_FILENAME)) {
+            Ok(signature) => Some(signature),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Some((
+            SignedIndex {
+                contents,
+                signature,
+            },
+            Validators::default(),
+        )))
+    }
+
+    fn fetch_package(&self, package: &Package, destination: &Path) -> crate::Result<()> {
+        // Copying is fast enough that a partial copy is simply started over.
+        let mut tarball = File::open(self.directory.join(package.get_filename()))?;
+        io::copy(&mut tarball, &mut File::create(destination)?)?;
+        Ok(())
+    }
+
+    fn fetch_package_signature(&self, package: &Package, destination: &Path) -> crate::Result<()> {
+        let mut filename = package.get_filename().into_os_string();
+        filename.push(".sig");
+        match fs::copy(self.directory.join(filename), destination) {
+            Ok(_) => Ok(()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                Err(Error::SignatureVerificationFailed {
+                    subject: format!("the tarball of {}", package.name),
+                    reason: String::from("the tarball isn't signed"),
+                })
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+/// Provide the directory a mirror refers to, if it's a `file://` URL or a
+/// plain path rather than a remote URL.
+/// ```
+/// # use mix::repository::local_mirror;
+/// # use std::path::PathBuf;
+/// assert_eq!(local_mirror("file:///srv/mix"), Some(PathBuf::from("/srv/mix")));
+/// assert_eq!(local_mirror("/srv/mix"), Some(PathBuf::from("/srv/mix")));
+/// assert_eq!(local_mirror("https://example.com/mix"), None);
+/// ```
+pub fn local_mirror(mirror: &str) -> Option<PathBuf> {
+    match mirror.strip_prefix("file://") {
+        Some(path) => Some(PathBuf::from(path)),
+        None if !mirror.contains("://") => Some(PathBuf::from(mirror)),
+        None => None,
+    }
+}
+
+/// The packages available from a repository, as listed in its index.
+#[derive(Debug, Default, Deserialize)]
+struct Index {
+    #[serde(default)]
+    packages: Vec<Package>,
+}
+
+/// Read the packages out of the index of the named repository. The index is
+/// TOML, or CBOR for large repositories, which is never valid UTF-8.
+pub(crate) fn parse_index(repository: &str, index: &[u8]) -> crate::Result<Vec<Package>> {
+    let invalid = |reason: String| Error::InvalidIndex {
+        repository: repository.to_owned(),
+        reason,
+    };
+    let index: Index = if std::str::from_utf8(index).is_ok() {
+        toml::from_slice(index).map_err(|error| invalid(error.to_string()))?
+    } else {
+        serde_cbor::from_slice(index).map_err(|error| invalid(error.to_string()))?
+    };
+    Ok(index.packages)
+}
@@ -2,8 +2,13 @@
 //! This will see some revamp, separating dependencies from the manual targets
 
 use crate::{
-    package::{InstallState, RcRefCellPackage},
-    Database, Error,
+    package::{InstallState, Package, SharedPackage},
+    Database, Error, Version,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::Path,
 };
 
 /// The todo list for any given operation. For example, the list of packages
@@ -11,24 +16,120 @@ use crate::{
 #[derive(Debug, Default)]
 pub struct Selections {
     /// Packages that will be installed by the operation.
-    pub install: Vec<RcRefCellPackage>,
+    pub install: Vec<SharedPackage>,
     /// Packages that will be removed by the operation.
-    pub remove: Vec<RcRefCellPackage>,
+    pub remove: Vec<SharedPackage>,
     /// Packages that will be upgraded by the operation.
-    pub upgrade: Vec<RcRefCellPackage>,
+    pub upgrade: Vec<SharedPackage>,
     /// Packages that will be downgraded by the operation.
-    pub downgrade: Vec<RcRefCellPackage>,
+    pub downgrade: Vec<SharedPackage>,
+    /// Installed packages whose missing files will be restored from their tarballs.
+    pub repair: Vec<SharedPackage>,
+    /// Packages among those to install that will be marked as
+    /// [manually installed](InstallState::Manual) once the selections are
+    /// applied, rather than as dependencies.
+    pub explicit: Vec<SharedPackage>,
+    /// The names of the requested packages that were left out because they
+    /// are already installed, so nothing needs to be done for them.
+    pub already_installed: Vec<String>,
+}
+
+impl Selections {
+    /// Summarize the changes, so they can be shown to the user.
+    /// ```
+    /// # use mix::{Package, Selections, SharedPackage, Version};
+    /// let mut foo = Package::new("foo", Version::SemVer(1, 0, 0));
+    /// foo.size = Some(3000);
+    /// let mut bar = Package::new("bar", Version::Unknown);
+    /// bar.size = Some(1000);
+    /// let mut selections = Selections::default();
+    /// selections.install.push(SharedPackage::new(foo));
+    /// selections.remove.push(SharedPackage::new(bar));
+    /// let summary = selections.describe();
+    /// assert_eq!(summary.install, vec![(String::from("foo"), Version::SemVer(1, 0, 0))]);
+    /// assert_eq!(summary.remove, vec![(String::from("bar"), Version::Unknown)]);
+    /// assert!(summary.upgrade.is_empty() && summary.downgrade.is_empty());
+    /// assert_eq!(summary.count(), 2);
+    /// assert_eq!(summary.size_change, Some(2000));
+    /// ```
+    pub fn describe(&self) -> SelectionSummary {
+        let entries = |packages: &[SharedPackage]| -> Vec<(String, Version)> {
+            packages
+                .iter()
+                .map(|package| {
+                    let package = package.borrow();
+                    (package.name.clone(), package.version.clone())
+                })
+                .collect()
+        };
+        let total_size = |packages: &[SharedPackage]| -> Option<i64> {
+            packages
+                .iter()
+                .map(|package| package.borrow().size.map(|size| size as i64))
+                .sum()
+        };
+        SelectionSummary {
+            install: entries(&self.install),
+            upgrade: entries(&self.upgrade),
+            downgrade: entries(&self.downgrade),
+            remove: entries(&self.remove),
+            repair: entries(&self.repair),
+            size_change: total_size(&self.install)
+                .and_then(|installed| Some(installed - total_size(&self.remove)?)),
+        }
+    }
+}
+
+/// The changes made by a [Selections], as provided by
+/// [Selections::describe]. Each package is listed as its name and version.
+#[derive(Debug, Default, PartialEq)]
+pub struct SelectionSummary {
+    /// Packages that will be installed.
+    pub install: Vec<(String, Version)>,
+    /// Packages that will be upgraded, at the version they are upgraded to.
+    pub upgrade: Vec<(String, Version)>,
+    /// Packages that will be downgraded, at the version they are downgraded to.
+    pub downgrade: Vec<(String, Version)>,
+    /// Packages that will be removed.
+    pub remove: Vec<(String, Version)>,
+    /// Packages whose missing files will be restored.
+    pub repair: Vec<(String, Version)>,
+    /// How many bytes the installed and removed packages add to the root,
+    /// which is negative if they free space. This is None if any of them
+    /// has an unknown size. Upgrades and downgrades aren't counted, as the
+    /// sizes of the versions they replace aren't known.
+    pub size_change: Option<i64>,
+}
+
+impl SelectionSummary {
+    /// Provide how many packages are changed.
+    pub fn count(&self) -> usize {
+        self.install.len()
+            + self.upgrade.len()
+            + self.downgrade.len()
+            + self.remove.len()
+            + self.repair.len()
+    }
+
+    /// Provide each kind of change with the packages it applies to, in the
+    /// order they are made: install, upgrade, downgrade, remove, then repair.
+    pub fn changes(&self) -> [(&'static str, &[(String, Version)]); 5] {
+        [
+            ("install", &self.install),
+            ("upgrade", &self.upgrade),
+            ("downgrade", &self.downgrade),
+            ("remove", &self.remove),
+            ("repair", &self.repair),
+        ]
+    }
 }
 
 /// Get a single package by name.
 pub fn package_from_name(
     package_name: &impl AsRef<str>,
     database: &Database,
-) -> crate::Result<RcRefCellPackage> {
-    match database
-        .iter()
-        .find(|package| package.borrow().name == package_name.as_ref())
-    {
+) -> crate::Result<SharedPackage> {
+    match database.get_package(package_name) {
         Some(package) => Ok(package),
         None => Err(Error::PackageNotFound(vec![String::from(
             package_name.as_ref(),
@@ -49,7 +150,7 @@ pub fn package_from_name(
 pub fn packages_from_names(
     package_names: &[impl AsRef<str>],
     database: &Database,
-) -> Result<Vec<RcRefCellPackage>, (Error, Vec<RcRefCellPackage>)> {
+) -> Result<Vec<SharedPackage>, (Error, Vec<SharedPackage>)> {
     let mut packages_found = Vec::new();
     let mut packages_not_found = Vec::new();
     package_names
@@ -68,35 +169,708 @@ pub fn packages_from_names(
     Ok(packages_found)
 }
 
+/// Read a list of package names, one per line, such as a saved set of
+/// packages to install on a new system. Whitespace around the names, blank
+/// lines, and comments starting with `#` are ignored.
+/// ```
+/// # use mix::selection::parse_package_list;
+/// let list = "# The basics\nfoo\n\n  bar  # for baz\n";
+/// assert_eq!(parse_package_list(list), vec!["foo", "bar"]);
+/// ```
+pub fn parse_package_list(list: &str) -> Vec<String> {
+    list.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
 /// Select the packages required for an installation of a package. This means
-/// dependencies and resolution of package names to objects.
+/// dependencies and resolution of package names to objects. Every dependency
+/// that isn't installed is selected too, down the whole dependency tree, and
+/// each only once. A dependency that no package is named after is satisfied
+/// by the newest package [providing](Database::packages_providing) it. The
+/// named packages are marked as
+/// [manually installed](InstallState::Manual), and the dependencies are
+/// installed as [dependencies](InstallState::Dependency).
 /// # Todo
 /// This currently has the same error type as [packages_from_names](packages_from_names).
 /// Once that function is updated, this function needs the same update.
 ///
-/// This function needs to handle dependencies once packages support this.
-///
 /// Write similar functions once it makes sense at all to have them.
+/// # Errors
+/// Packages that [conflict](Package::conflicts_with) with each other or with
+/// an installed package can't be selected; see
+/// [install_replacing](install_replacing) to remove the installed ones instead.
+/// Packages depending on each other in a cycle produce a
+/// [DependencyCycle](crate::error::MixError::DependencyCycle) error.
+/// Dependencies that nothing in the database provides are
+/// [not found](crate::error::MixError::PackageNotFound), all at once from
+/// across the whole dependency tree.
 pub fn install(
     package_names: &[impl AsRef<str>],
     database: &Database,
-) -> Result<Selections, (Error, Vec<RcRefCellPackage>)> {
+) -> Result<Selections, (Error, Vec<SharedPackage>)> {
+    select_install(package_names, database, false)
+}
+
+/// Select the packages required for an installation of a package like
+/// [install](install), but remove the installed packages that conflict with
+/// them rather than failing. The conflicting packages are removed before
+/// anything is installed, so they stay removed if installing fails; rolling
+/// back the interrupted transaction (see
+/// [rollback_selections](Database::rollback_selections)) installs them again.
+pub fn install_replacing(
+    package_names: &[impl AsRef<str>],
+    database: &Database,
+) -> Result<Selections, (Error, Vec<SharedPackage>)> {
+    select_install(package_names, database, true)
+}
+
+/// Select the packages required for an installation of a package like
+/// [install](install), without checking them for conflicts. More packages,
+/// such as those from [tarballs](install_tarballs), can then be added to the
+/// selections before they are all checked at once with
+/// [resolve_conflicts](resolve_conflicts).
+pub fn install_unchecked(
+    package_names: &[impl AsRef<str>],
+    database: &Database,
+) -> Result<Selections, (Error, Vec<SharedPackage>)> {
     let packages = packages_from_names(package_names, database)?;
+    select_dependencies(&packages, package_names, database).map_err(|error| (error, packages))
+}
+
+fn select_install(
+    package_names: &[impl AsRef<str>],
+    database: &Database,
+    replace: bool,
+) -> Result<Selections, (Error, Vec<SharedPackage>)> {
+    let packages = packages_from_names(package_names, database)?;
+    let mut selections = select_dependencies(&packages, package_names, database)
+        .map_err(|error| (error, packages.clone()))?;
+    resolve_conflicts(&mut selections, database, replace).map_err(|error| (error, packages))?;
+    Ok(selections)
+}
+
+/// Select the given packages to install, along with their dependencies,
+/// marking the named ones as manually installed.
+fn select_dependencies(
+    packages: &[SharedPackage],
+    package_names: &[impl AsRef<str>],
+    database: &Database,
+) -> crate::Result<Selections> {
     let mut selections = Selections::default();
+    let mut selected = HashSet::new();
+    let mut missing = vec![];
     for package in packages {
         if package.borrow().state != InstallState::Uninstalled {
+            selections
+                .already_installed
+                .push(package.borrow().name.clone());
             continue;
         }
-        selections.install.push(package.clone());
+        select_with_dependencies(
+            package,
+            database,
+            &mut vec![],
+            &mut selected,
+            &mut missing,
+            &mut selections.install,
+        )?;
     }
+    // Installing only part of what's needed would leave the packages broken.
+    if !missing.is_empty() {
+        return Err(Error::PackageNotFound(missing));
+    }
+    set_install_reason(&mut selections, package_names, InstallState::Manual);
     Ok(selections)
 }
 
+/// Select a package to install after the dependencies it needs that aren't
+/// installed. Dependencies the database doesn't know about are added to
+/// `missing` instead. `visiting` holds the chain of packages currently being
+/// selected, which is how cycles are found, and `selected` the names of the
+/// packages already in `install`.
+fn select_with_dependencies(
+    package: &SharedPackage,
+    database: &Database,
+    visiting: &mut Vec<String>,
+    selected: &mut HashSet<String>,
+    missing: &mut Vec<String>,
+    install: &mut Vec<SharedPackage>,
+) -> crate::Result<()> {
+    let (name, dependencies) = {
+        let package = package.borrow();
+        (package.name.clone(), package.dependencies.clone())
+    };
+    if selected.contains(&name) {
+        return Ok(());
+    }
+    visiting.push(name.clone());
+    for dependency in &dependencies {
+        if let Some(start) = visiting.iter().position(|visited| visited == dependency) {
+            let mut cycle = visiting.split_off(start);
+            cycle.push(dependency.clone());
+            return Err(Error::DependencyCycle(cycle));
+        }
+        if is_provided(dependency, database) {
+            continue;
+        }
+        // A dependency no package is named after may be virtual, so the
+        // newest package providing it is installed instead.
+        let provider = database.get_package(dependency).or_else(|| {
+            let provider = database.packages_providing(dependency).into_iter().next()?;
+            database.iter().find(|known| *known.borrow() == provider)
+        });
+        match provider {
+            Some(dependency) => select_with_dependencies(
+                &dependency,
+                database,
+                visiting,
+                selected,
+                missing,
+                install,
+            )?,
+            None if !missing.contains(dependency) => missing.push(dependency.clone()),
+            None => {}
+        }
+    }
+    visiting.pop();
+    selected.insert(name);
+    install.push(package.clone());
+    Ok(())
+}
+
+/// Check if an installed package is or provides the named package.
+fn is_provided(name: &str, database: &Database) -> bool {
+    database.packages().any(|installed| {
+        installed.state != InstallState::Uninstalled && satisfies(&installed, name)
+    })
+}
+
+/// Check that none of the packages to install conflict with each other, or
+/// with the installed packages that stay installed. If `replace` is set, the
+/// conflicting installed packages are removed instead; packages being
+/// installed together that conflict are always an error.
+pub fn resolve_conflicts(
+    selections: &mut Selections,
+    database: &Database,
+    replace: bool,
+) -> crate::Result<()> {
+    let conflict = |a: &Package, b: &Package| Error::ConflictingPackages {
+        a: a.name.clone(),
+        b: b.name.clone(),
+    };
+    let incoming: Vec<SharedPackage> = selections
+        .install
+        .iter()
+        .chain(&selections.upgrade)
+        .cloned()
+        .collect();
+    for (index, package) in incoming.iter().enumerate() {
+        for other in &incoming[index + 1..] {
+            if package.borrow().conflicts_with(&other.borrow()) {
+                return Err(conflict(&package.borrow(), &other.borrow()));
+            }
+        }
+    }
+    for installed in database.iter() {
+        let (name, is_installed) = {
+            let installed = installed.borrow();
+            (
+                installed.name.clone(),
+                installed.state != InstallState::Uninstalled,
+            )
+        };
+        // Installed packages being removed or replaced by a new version don't stay.
+        if !is_installed
+            || selections.remove.contains(&installed)
+            || incoming.iter().any(|package| package.borrow().name == name)
+        {
+            continue;
+        }
+        for package in &incoming {
+            if !package.borrow().conflicts_with(&installed.borrow()) {
+                continue;
+            }
+            if !replace {
+                return Err(conflict(&package.borrow(), &installed.borrow()));
+            }
+            selections.remove.push(installed.clone());
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Select the packages in the given tarballs for installation. Their
+/// dependencies are resolved among the tarballs themselves, as well as the
+/// installed packages, so a set of tarballs can be installed without
+/// synchronizing with a repository first. They aren't checked for conflicts,
+/// as they're often installed along with other packages; check everything
+/// being installed at once with [resolve_conflicts](resolve_conflicts).
+/// # Errors
+/// Dependencies that none of the tarballs or installed packages provide are
+/// [not found](Error::PackageNotFound), all at once.
+pub fn install_tarballs(
+    paths: &[impl AsRef<Path>],
+    database: &Database,
+) -> crate::Result<Selections> {
+    let mut packages = vec![];
+    for path in paths {
+        let mut package = Package::from_tarball_with_manifest_name(
+            File::open(path.as_ref())?,
+            database.config().manifest_filename(),
+        )?;
+        // The path is relative to the working directory, not the base directory.
+        package.local_path = Some(path.as_ref().canonicalize()?);
+        packages.push(package);
+    }
+    let missing = missing_dependencies(&packages, database);
+    if !missing.is_empty() {
+        return Err(Error::PackageNotFound(missing));
+    }
+    Ok(Selections {
+        install: packages.into_iter().map(SharedPackage::new).collect(),
+        ..Selections::default()
+    })
+}
+
+/// Provide the dependencies of the packages that neither the packages
+/// themselves nor the installed packages provide, each named once.
+pub(crate) fn missing_dependencies(packages: &[Package], database: &Database) -> Vec<String> {
+    let provides = |package: &Package, name: &str| {
+        package.name == name || package.provides.iter().any(|provided| provided == name)
+    };
+    let mut missing = vec![];
+    for package in packages {
+        for dependency in &package.dependencies {
+            let provided = packages.iter().any(|package| provides(package, dependency))
+                || database.packages().any(|installed| {
+                    installed.state != InstallState::Uninstalled && provides(&installed, dependency)
+                });
+            if !provided && !missing.contains(dependency) {
+                missing.push(dependency.clone());
+            }
+        }
+    }
+    missing
+}
+
+/// Set why the named packages of an install are installed: explicitly, with
+/// [Manual](InstallState::Manual), or as a dependency, with
+/// [Dependency](InstallState::Dependency). Packages that aren't named are
+/// left alone, so they are installed as dependencies unless they are
+/// already marked. The reason is kept in the [explicit](Selections::explicit)
+/// packages of the selections, so nothing changes until they are applied.
+pub fn set_install_reason(
+    selections: &mut Selections,
+    package_names: &[impl AsRef<str>],
+    reason: InstallState,
+) {
+    let named: Vec<SharedPackage> = selections
+        .install
+        .iter()
+        .filter(|package| {
+            package_names
+                .iter()
+                .any(|name| name.as_ref() == package.borrow().name)
+        })
+        .cloned()
+        .collect();
+    selections
+        .explicit
+        .retain(|package| !named.contains(package));
+    if reason == InstallState::Manual {
+        selections.explicit.extend(named);
+    }
+}
+
+/// Select the upgrades for the given installed packages, or for every
+/// installed package if no names are given. Packages that are already up to
+/// date are skipped.
 /// # Todo
-/// Write this.
+/// This currently has the same error type as [packages_from_names](packages_from_names).
+/// Once that function is updated, this function needs the same update.
+pub fn update(
+    package_names: &[impl AsRef<str>],
+    database: &Database,
+) -> Result<Selections, (Error, Vec<SharedPackage>)> {
+    let targets = packages_from_names(package_names, database)?;
+    let mut selections = Selections::default();
+    for (installed, newest) in database.outdated() {
+        if package_names.is_empty() || targets.contains(&installed) {
+            selections.upgrade.push(newest);
+        }
+    }
+    Ok(selections)
+}
+
+/// Take the named packages out of the selections, providing the packages that
+/// were taken out. This holds packages back for a single operation.
+pub fn exclude(
+    selections: &mut Selections,
+    package_names: &[impl AsRef<str>],
+) -> Vec<SharedPackage> {
+    let is_excluded = |package: &SharedPackage| {
+        package_names
+            .iter()
+            .any(|name| name.as_ref() == package.borrow().name)
+    };
+    let mut excluded = vec![];
+    for list in [
+        &mut selections.install,
+        &mut selections.remove,
+        &mut selections.upgrade,
+        &mut selections.downgrade,
+    ] {
+        excluded.extend(list.iter().filter(|package| is_excluded(package)).cloned());
+        list.retain(|package| !is_excluded(package));
+    }
+    excluded
+}
+
+/// Take the packages to install out of the selections if the same version of
+/// them is already installed, providing the packages that were taken out.
+/// This makes installing a list of packages idempotent. Upgrades that aren't
+/// newer than what's installed are taken out as well, as with
+/// [skip_stale_upgrades](skip_stale_upgrades).
+pub fn skip_up_to_date(selections: &mut Selections, database: &Database) -> Vec<SharedPackage> {
+    let is_up_to_date = |package: &SharedPackage| {
+        let package = package.borrow();
+        database
+            .installed_package(&package.name)
+            .is_some_and(|installed| installed.borrow().version == package.version)
+    };
+    let mut skipped: Vec<_> = selections
+        .install
+        .iter()
+        .filter(|package| is_up_to_date(package))
+        .cloned()
+        .collect();
+    selections.install.retain(|package| !is_up_to_date(package));
+    skipped.extend(skip_stale_upgrades(selections, database));
+    skipped
+}
+
+/// Take the packages to upgrade out of the selections if they aren't newer
+/// than the installed version of the package, providing the packages that
+/// were taken out. Upgrading to them would do nothing.
+pub fn skip_stale_upgrades(selections: &mut Selections, database: &Database) -> Vec<SharedPackage> {
+    let is_stale = |package: &SharedPackage| {
+        let package = package.borrow();
+        database
+            .installed_package(&package.name)
+            .is_some_and(|installed| installed.borrow().version >= package.version)
+    };
+    let skipped: Vec<_> = selections
+        .upgrade
+        .iter()
+        .filter(|package| is_stale(package))
+        .cloned()
+        .collect();
+    selections.upgrade.retain(|package| !is_stale(package));
+    skipped
+}
+
+/// Select the packages to remove. Packages that are not installed are skipped.
+/// # Errors
+/// A package that installed packages outside of the selection depend on
+/// can't be removed, giving a [RequiredBy](crate::error::MixError::RequiredBy)
+/// error along with those dependents. A package providing a dependency can
+/// be removed as long as another installed package still provides it.
+/// # Todo
+/// This currently has the same error type as [packages_from_names](packages_from_names).
+/// Once that function is updated, this function needs the same update.
 pub fn remove(
-    _package_names: &[impl AsRef<str>],
-    _database: &Database,
-) -> Result<Selections, (Error, Vec<RcRefCellPackage>)> {
-    todo!()
+    package_names: &[impl AsRef<str>],
+    database: &Database,
+) -> Result<Selections, (Error, Vec<SharedPackage>)> {
+    let selections = remove_unchecked(package_names, database)?;
+    let removed: HashSet<String> = selections
+        .remove
+        .iter()
+        .map(|package| package.borrow().name.clone())
+        .collect();
+    let kept: Vec<SharedPackage> = database
+        .iter()
+        .filter(|package| {
+            let package = package.borrow();
+            package.state != InstallState::Uninstalled && !removed.contains(&package.name)
+        })
+        .collect();
+    // Only dependencies that nothing staying installed satisfies are lost.
+    let lost = |package: &Package, dependency: &str| {
+        satisfies(package, dependency)
+            && !kept
+                .iter()
+                .any(|kept| satisfies(&kept.borrow(), dependency))
+    };
+    for package in &selections.remove {
+        let name = package.borrow().name.clone();
+        let blocking: Vec<_> = dependents(&name, database)
+            .into_iter()
+            .filter(|dependent| {
+                let dependent = dependent.borrow();
+                !removed.contains(&dependent.name)
+                    && dependent
+                        .dependencies
+                        .iter()
+                        .any(|dependency| lost(&package.borrow(), dependency))
+            })
+            .collect();
+        if !blocking.is_empty() {
+            let error = Error::RequiredBy {
+                package: name,
+                dependents: blocking
+                    .iter()
+                    .map(|dependent| dependent.borrow().name.clone())
+                    .collect(),
+            };
+            return Err((error, blocking));
+        }
+    }
+    Ok(selections)
+}
+
+/// Select the installed packages among those named, without checking if
+/// anything depends on them.
+fn remove_unchecked(
+    package_names: &[impl AsRef<str>],
+    database: &Database,
+) -> Result<Selections, (Error, Vec<SharedPackage>)> {
+    let packages = packages_from_names(package_names, database)?;
+    let mut selections = Selections::default();
+    for package in packages {
+        if package.borrow().state == InstallState::Uninstalled {
+            continue;
+        }
+        selections.remove.push(package.clone());
+    }
+    Ok(selections)
+}
+
+/// Select the packages to remove, along with every installed package that
+/// depends on them, directly or transitively. Dependency cycles are handled by
+/// visiting each package only once.
+pub fn remove_cascade(
+    package_names: &[impl AsRef<str>],
+    database: &Database,
+) -> Result<Selections, (Error, Vec<SharedPackage>)> {
+    let mut selections = remove_unchecked(package_names, database)?;
+    let mut visited: HashSet<String> = selections
+        .remove
+        .iter()
+        .map(|package| package.borrow().name.clone())
+        .collect();
+    let mut pending = selections.remove.clone();
+    while let Some(package) = pending.pop() {
+        let name = package.borrow().name.clone();
+        for dependent in dependents(&name, database) {
+            if visited.insert(dependent.borrow().name.clone()) {
+                selections.remove.push(dependent.clone());
+                pending.push(dependent);
+            }
+        }
+    }
+    Ok(selections)
+}
+
+/// Get every package installed as a [dependency](InstallState::Dependency)
+/// that no installed package depends on anymore.
+pub fn orphans(database: &Database) -> Vec<SharedPackage> {
+    database
+        .iter()
+        .filter(|package| {
+            let (name, state) = {
+                let package = package.borrow();
+                (package.name.clone(), package.state.clone())
+            };
+            state == InstallState::Dependency && dependents(&name, database).is_empty()
+        })
+        .collect()
+}
+
+/// Select every package installed as a dependency that the manually
+/// installed packages no longer need, directly or through other dependencies.
+/// This covers the orphans, the dependencies only they need, and dependencies
+/// that only depend on each other.
+pub fn autoremove(database: &Database) -> Selections {
+    let installed: Vec<SharedPackage> = database
+        .iter()
+        .filter(|package| package.borrow().state != InstallState::Uninstalled)
+        .collect();
+    let mut pending: Vec<SharedPackage> = installed
+        .iter()
+        .filter(|package| package.borrow().state != InstallState::Dependency)
+        .cloned()
+        .collect();
+    let mut needed: HashSet<String> = pending
+        .iter()
+        .map(|package| package.borrow().name.clone())
+        .collect();
+    while let Some(package) = pending.pop() {
+        let dependencies = package.borrow().dependencies.clone();
+        for dependency in dependencies {
+            // Any of the packages satisfying the dependency may be the one relied on.
+            for provider in installed
+                .iter()
+                .filter(|installed| satisfies(&installed.borrow(), &dependency))
+            {
+                if needed.insert(provider.borrow().name.clone()) {
+                    pending.push(provider.clone());
+                }
+            }
+        }
+    }
+    Selections {
+        remove: installed
+            .into_iter()
+            .filter(|package| !needed.contains(&package.borrow().name))
+            .collect(),
+        ..Selections::default()
+    }
+}
+
+/// Get every installed package that directly depends on the named package,
+/// either by its name or by something the installed package provides.
+pub fn dependents(package_name: &str, database: &Database) -> Vec<SharedPackage> {
+    let provides = database
+        .installed_package(package_name)
+        .map(|package| package.borrow().provides.clone())
+        .unwrap_or_default();
+    database
+        .iter()
+        .filter(|package| {
+            let package = package.borrow();
+            package.state != InstallState::Uninstalled
+                && package
+                    .dependencies
+                    .iter()
+                    .any(|dependency| dependency == package_name || provides.contains(dependency))
+        })
+        .collect()
+}
+
+/// Check if the package is or provides the named dependency.
+fn satisfies(package: &Package, dependency: &str) -> bool {
+    package.name == dependency
+        || package
+            .provides
+            .iter()
+            .any(|provided| provided == dependency)
+}
+
+/// Order packages so that every package comes after the packages it depends
+/// on, keeping the given order where dependencies allow it. Only dependencies
+/// between the given packages are considered.
+/// # Errors
+/// Packages that depend on each other in a cycle can't be ordered, and produce
+/// a [DependencyCycle](crate::error::MixError::DependencyCycle) error.
+/// # Examples:
+/// ```rust
+/// # use mix::{selection::install_order, Package, SharedPackage, Version};
+/// // a depends on b, which depends on c.
+/// let mut a = Package::new("a", Version::Unknown);
+/// a.dependencies = vec![String::from("b")];
+/// let mut b = Package::new("b", Version::Unknown);
+/// b.dependencies = vec![String::from("c")];
+/// let c = Package::new("c", Version::Unknown);
+/// let packages: Vec<_> = vec![a, b, c].into_iter().map(SharedPackage::new).collect();
+/// let order: Vec<_> = install_order(&packages)?
+///     .iter()
+///     .map(|package| package.borrow().name.clone())
+///     .collect();
+/// assert_eq!(order, vec!["c", "b", "a"]);
+/// # Ok::<(), mix::Error>(())
+/// ```
+pub fn install_order(packages: &[SharedPackage]) -> crate::Result<Vec<SharedPackage>> {
+    let by_name: HashMap<String, SharedPackage> = packages
+        .iter()
+        .map(|package| (package.borrow().name.clone(), package.clone()))
+        .collect();
+    let mut order = Vec::with_capacity(packages.len());
+    let mut placed = HashSet::new();
+    let mut visiting = vec![];
+    for package in packages {
+        visit(package, &by_name, &mut visiting, &mut placed, &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Group the packages into batches that can each be installed at once. Every
+/// package comes in a later batch than all of its dependencies, so the
+/// packages within a batch never depend on each other.
+/// ```
+/// # use mix::{selection::install_batches, Package, SharedPackage, Version};
+/// let mut a = Package::new("a", Version::Unknown);
+/// a.dependencies = vec![String::from("b")];
+/// let packages = vec![
+///     SharedPackage::new(a),
+///     SharedPackage::new(Package::new("b", Version::Unknown)),
+///     SharedPackage::new(Package::new("c", Version::Unknown)),
+/// ];
+/// let names: Vec<Vec<String>> = install_batches(&packages)?
+///     .iter()
+///     .map(|batch| batch.iter().map(|package| package.borrow().name.clone()).collect())
+///     .collect();
+/// assert_eq!(names, vec![vec!["b", "c"], vec!["a"]]);
+/// # Ok::<(), mix::Error>(())
+/// ```
+pub fn install_batches(packages: &[SharedPackage]) -> crate::Result<Vec<Vec<SharedPackage>>> {
+    let mut depths: HashMap<String, usize> = HashMap::new();
+    let mut batches: Vec<Vec<SharedPackage>> = vec![];
+    // Dependencies always come first in the install order, so their depth is known.
+    for package in install_order(packages)? {
+        let (name, dependencies) = {
+            let package = package.borrow();
+            (package.name.clone(), package.dependencies.clone())
+        };
+        let depth = dependencies
+            .iter()
+            .filter_map(|dependency| depths.get(dependency))
+            .map(|depth| depth + 1)
+            .max()
+            .unwrap_or(0);
+        depths.insert(name, depth);
+        if batches.len() <= depth {
+            batches.push(vec![]);
+        }
+        batches[depth].push(package);
+    }
+    Ok(batches)
+}
+
+/// Place a package into the install order after its dependencies, for
+/// [install_order](install_order). `visiting` holds the chain of packages
+/// currently being placed, which is how cycles are found.
+fn visit(
+    package: &SharedPackage,
+    by_name: &HashMap<String, SharedPackage>,
+    visiting: &mut Vec<String>,
+    placed: &mut HashSet<String>,
+    order: &mut Vec<SharedPackage>,
+) -> crate::Result<()> {
+    let (name, dependencies) = {
+        let package = package.borrow();
+        (package.name.clone(), package.dependencies.clone())
+    };
+    if placed.contains(&name) {
+        return Ok(());
+    }
+    if let Some(start) = visiting.iter().position(|visited| *visited == name) {
+        let mut cycle = visiting.split_off(start);
+        cycle.push(name);
+        return Err(Error::DependencyCycle(cycle));
+    }
+    visiting.push(name.clone());
+    for dependency in &dependencies {
+        if let Some(dependency) = by_name.get(dependency) {
+            visit(dependency, by_name, visiting, placed, order)?;
+        }
+    }
+    visiting.pop();
+    placed.insert(name);
+    order.push(package.clone());
+    Ok(())
 }
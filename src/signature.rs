@@ -0,0 +1,100 @@
+use crate::Error;
+use std::{
+    fs::{self, DirBuilder},
+    os::unix::fs::DirBuilderExt,
+    path::Path,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Tells apart the directories of checks made at the same time.
+static CHECKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Check the detached signature of the contents against a public key, using
+/// `gpgv`. The key is an OpenPGP public key as exported by `gpg --export`,
+/// without ASCII armor. The subject names what was signed for the
+/// [error](Error::SignatureVerificationFailed) when the signature isn't good.
+pub fn verify_detached(
+    subject: &str,
+    key: &Path,
+    contents: &[u8],
+    signature: &[u8],
+) -> crate::Result<()> {
+    with_home_directory(|directory| {
+        fs::write(directory.join("contents"), contents)?;
+        fs::write(directory.join("contents.sig"), signature)?;
+        run_gpgv(
+            subject,
+            key,
+            directory,
+            &directory.join("contents.sig"),
+            &directory.join("contents"),
+        )
+    })
+}
+
+/// Check the detached signature in one file against the contents of another,
+/// like [verify_detached](verify_detached). The files are read by `gpgv`
+/// where they are, so large files aren't copied or held in memory.
+pub fn verify_detached_file(
+    subject: &str,
+    key: &Path,
+    contents: &Path,
+    signature: &Path,
+) -> crate::Result<()> {
+    with_home_directory(|directory| run_gpgv(subject, key, directory, signature, contents))
+}
+
+/// Run the check in a fresh, private directory, which is removed afterwards.
+/// gpgv is given it as its home directory, so it's kept away from the
+/// keyrings of the user.
+fn with_home_directory(check: impl FnOnce(&Path) -> crate::Result<()>) -> crate::Result<()> {
+    let directory = std::env::temp_dir().join(format!(
+        "mix-verify-{}-{}",
+        std::process::id(),
+        CHECKS.fetch_add(1, Ordering::Relaxed)
+    ));
+    DirBuilder::new().mode(0o700).create(&directory)?;
+    let result = check(&directory);
+    let _ = fs::remove_dir_all(&directory);
+    result
+}
+
+/// Have `gpgv` check the signature file against the contents file.
+fn run_gpgv(
+    subject: &str,
+    key: &Path,
+    home: &Path,
+    signature: &Path,
+    contents: &Path,
+) -> crate::Result<()> {
+    let failed = |reason: String| Error::SignatureVerificationFailed {
+        subject: subject.to_owned(),
+        reason,
+    };
+    // gpgv takes a key without a slash to be in its home directory.
+    let key = key.canonicalize().map_err(|error| {
+        failed(format!(
+            "the key {} can't be read: {}",
+            key.display(),
+            error
+        ))
+    })?;
+    let output = Command::new("gpgv")
+        .arg("--homedir")
+        .arg(home)
+        .arg("--keyring")
+        .arg(&key)
+        .arg(signature)
+        .arg(contents)
+        .output()
+        .map_err(|error| failed(format!("gpgv could not be run: {}", error)))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let log = String::from_utf8_lossy(&output.stderr);
+    Err(failed(match log.lines().last() {
+        Some(line) => line.trim_start_matches("gpgv: ").to_owned(),
+        None => format!("gpgv failed with {}", output.status),
+    }))
+}
@@ -0,0 +1,48 @@
+use std::{ffi::CString, io, os::unix::ffi::OsStrExt, path::Path};
+
+/// The binary units used by [format_bytes](format_bytes), after plain bytes.
+const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Format a number of bytes for humans, using binary units with one decimal place.
+/// # Examples:
+/// ```rust
+/// # use mix::util::format_bytes;
+/// assert_eq!(format_bytes(0), "0 B");
+/// assert_eq!(format_bytes(1023), "1023 B");
+/// assert_eq!(format_bytes(1024), "1.0 KiB");
+/// assert_eq!(format_bytes(1536), "1.5 KiB");
+/// assert_eq!(format_bytes(1024 * 1024 - 1), "1.0 MiB");
+/// assert_eq!(format_bytes(1024 * 1024), "1.0 MiB");
+/// assert_eq!(format_bytes(1258291), "1.2 MiB");
+/// assert_eq!(format_bytes(1024 * 1024 * 1024), "1.0 GiB");
+/// assert_eq!(format_bytes(u64::MAX), "16.0 EiB");
+/// ```
+pub fn format_bytes(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    // Anything that would round up to 1024.0 is shown in the next unit instead.
+    while value >= 1023.95 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Provide how many bytes can be written to the filesystem containing the
+/// path by an unprivileged user.
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // Safety: the path is a valid C string, and statvfs only writes to stats.
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // Safety: statvfs succeeded, so it filled in stats.
+    let stats = unsafe { stats.assume_init() };
+    // The field types differ between platforms, so the casts aren't always needed.
+    #[allow(clippy::unnecessary_cast)]
+    Ok((stats.f_bavail as u64).saturating_mul(stats.f_frsize as u64))
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{
+    empty_database, listed_package, load_package, scratch_directory, write_tarball,
+    ListedRepository,
+};
+use mix::{Selections, SharedPackage, Version};
+use std::fs;
+
+#[test]
+fn installing_again_reports_already_installed() {
+    let directory = scratch_directory("already-installed");
+    let mut database = empty_database(&directory);
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let mut selections = Selections::default();
+    selections
+        .install
+        .push(SharedPackage::new(load_package(&tarball)));
+    database.apply(selections).unwrap();
+
+    let selections = mix::selection::install(&["foo"], &database)
+        .map_err(|(error, _)| error)
+        .unwrap();
+
+    assert_eq!(selections.already_installed, vec![String::from("foo")]);
+    assert_eq!(selections.describe().count(), 0);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn needed_only_skips_tarball_targets() {
+    let directory = scratch_directory("already-installed-needed");
+    let mut database = empty_database(&directory);
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let mut installed = load_package(&tarball);
+    installed.version = Version::SemVer(1, 0, 0);
+    let mut selections = Selections::default();
+    selections.install.push(SharedPackage::new(installed));
+    database.apply(selections).unwrap();
+    let mut newer = listed_package("foo", &[]);
+    newer.version = Version::SemVer(2, 0, 0);
+    database
+        .synchronize(&ListedRepository::new("listing", vec![newer]))
+        .unwrap();
+
+    // A named target is skipped once installed, even with a newer version
+    // available, whether or not up to date targets are skipped.
+    let mut selections = mix::selection::install(&["foo"], &database)
+        .map_err(|(error, _)| error)
+        .unwrap();
+    assert_eq!(selections.already_installed, vec![String::from("foo")]);
+    assert!(mix::selection::skip_up_to_date(&mut selections, &database).is_empty());
+    assert_eq!(selections.describe().count(), 0);
+
+    // A tarball of the installed version is only skipped when asked to.
+    let mut again = load_package(&tarball);
+    again.version = Version::SemVer(1, 0, 0);
+    let mut selections = Selections::default();
+    selections.install.push(SharedPackage::new(again));
+    assert_eq!(
+        mix::selection::skip_up_to_date(&mut selections, &database).len(),
+        1
+    );
+    assert!(selections.install.is_empty());
+    fs::remove_dir_all(&directory).unwrap();
+}
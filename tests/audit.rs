@@ -0,0 +1,48 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{package::FileDiscrepancy, Selections, SharedPackage};
+use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+#[test]
+fn audit_reports_changed_files() {
+    let directory = scratch_directory("audit");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"foo\"",
+        &[
+            "usr/",
+            "usr/edited",
+            "usr/chmodded",
+            "usr/deleted",
+            "usr/intact",
+        ],
+    );
+    let mut foo = load_package(&tarball);
+    foo.mark_as_manually_installed();
+    let mut selections = Selections::default();
+    selections.install.push(SharedPackage::new(foo));
+    database.apply(selections).unwrap();
+    assert!(database.audit("foo").unwrap().is_empty());
+
+    fs::write(root.join("usr/edited"), "something else").unwrap();
+    fs::set_permissions(root.join("usr/chmodded"), fs::Permissions::from_mode(0o700)).unwrap();
+    fs::remove_file(root.join("usr/deleted")).unwrap();
+
+    assert_eq!(
+        database.audit("foo").unwrap(),
+        vec![
+            FileDiscrepancy::Modified(PathBuf::from("usr/edited")),
+            FileDiscrepancy::WrongMode {
+                path: PathBuf::from("usr/chmodded"),
+                expected: 0o755,
+                found: 0o700,
+            },
+            FileDiscrepancy::Missing(PathBuf::from("usr/deleted")),
+        ]
+    );
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{Error, InstallState, Selections, SharedPackage};
+use std::fs;
+
+#[test]
+fn mismatched_tarballs_place_no_files() {
+    let directory = scratch_directory("checksum");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let mut package = load_package(&tarball);
+    package.checksum = Some("0".repeat(64));
+    let package = SharedPackage::new(package);
+    let mut selections = Selections::default();
+    selections.install.push(package.clone());
+
+    let error = database.apply(selections).unwrap_err();
+    assert!(
+        matches!(error, Error::ChecksumMismatch { expected, .. } if expected == "0".repeat(64))
+    );
+    assert!(!root.join("usr").exists());
+    assert_eq!(package.borrow().state, InstallState::Uninstalled);
+
+    let checksum = mix::package::checksum(fs::File::open(&tarball).unwrap()).unwrap();
+    package.borrow_mut().checksum = Some(checksum.to_uppercase());
+    let mut selections = Selections::default();
+    selections.install.push(package);
+    database.apply(selections).unwrap();
+    assert!(root.join("usr/foo").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,237 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use mix::{Config, Database, Package, Repository, Version};
+use std::{
+    fs::{self, DirBuilder, File},
+    io,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use xz2::write::XzEncoder;
+
+/// Create an empty directory for the test to work in.
+pub fn scratch_directory(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("mix-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// Provide an empty database installing into `root` within the directory,
+/// with its package cache in `cache`. The made up repositories of the tests
+/// aren't signed, so unsigned repositories are allowed.
+pub fn empty_database(directory: &Path) -> Database {
+    fs::create_dir_all(directory.join("root")).unwrap();
+    let mut database = Database::new_empty(directory.join("cache"));
+    database.set_config(Config {
+        root: directory.join("root"),
+        allow_unsigned: true,
+        ..Config::default()
+    });
+    database
+}
+
+/// How [write_tarball_with](write_tarball_with) lays out a tarball.
+pub struct TarballLayout<'a> {
+    /// The name of the manifest entry.
+    pub manifest_name: &'a str,
+    /// Whether the manifest comes after the other entries instead of first.
+    pub manifest_last: bool,
+    /// Put in front of the path of every entry, such as `./` for a tarball
+    /// made from within its directory.
+    pub prefix: &'a str,
+    /// The mode of the entries that don't give their own.
+    pub mode: u32,
+}
+
+impl Default for TarballLayout<'_> {
+    fn default() -> Self {
+        Self {
+            manifest_name: ".MANIFEST",
+            manifest_last: false,
+            prefix: "",
+            mode: 0o755,
+        }
+    }
+}
+
+/// Write a package tarball with the manifest first. Entries ending with `/`
+/// are directories, entries like `link -> target` are symlinks, entries like
+/// `link => target` are hardlinks, entries ending with `|` are FIFOs, and
+/// every other entry is a file containing its own path.
+pub fn write_tarball(path: &Path, manifest: &str, entries: &[&str]) {
+    write_tarball_with(path, manifest, entries, &TarballLayout::default());
+}
+
+/// Write a package tarball like [write_tarball](write_tarball), laid out as
+/// given. Entries like `file @ 444` have that octal mode.
+pub fn write_tarball_with(path: &Path, manifest: &str, entries: &[&str], layout: &TarballLayout) {
+    let mut builder = tar::Builder::new(XzEncoder::new(File::create(path).unwrap(), 6));
+    let mut append = |path: &str, entry_type, contents: &[u8], target: Option<&str>| {
+        let (path, mode) = match path.split_once(" @ ") {
+            Some((path, mode)) => (path, u32::from_str_radix(mode, 8).unwrap()),
+            None => (path, layout.mode),
+        };
+        let path = format!("{}{}", layout.prefix, path);
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_size(contents.len() as u64);
+        match target {
+            Some(target) => builder.append_link(&mut header, path, target).unwrap(),
+            None => {
+                header.set_cksum();
+                builder.append_data(&mut header, path, contents).unwrap();
+            }
+        }
+    };
+    // None stands for the manifest.
+    let mut order: Vec<Option<&str>> = entries.iter().copied().map(Some).collect();
+    if layout.manifest_last {
+        order.push(None);
+    } else {
+        order.insert(0, None);
+    }
+    for entry in order {
+        let Some(entry) = entry else {
+            append(
+                layout.manifest_name,
+                tar::EntryType::Regular,
+                manifest.as_bytes(),
+                None,
+            );
+            continue;
+        };
+        if let Some((path, target)) = entry.split_once(" -> ") {
+            append(path, tar::EntryType::Symlink, b"", Some(target));
+        } else if let Some((path, target)) = entry.split_once(" => ") {
+            append(path, tar::EntryType::Link, b"", Some(target));
+        } else if let Some(path) = entry.strip_suffix('|') {
+            append(path, tar::EntryType::Fifo, b"", None);
+        } else if entry.ends_with('/') {
+            append(entry, tar::EntryType::Directory, b"", None);
+        } else {
+            let file = entry.split(" @ ").next().unwrap();
+            append(entry, tar::EntryType::Regular, file.as_bytes(), None);
+        }
+    }
+    builder.into_inner().unwrap().finish().unwrap();
+}
+
+/// Load the package from a tarball, ready to be installed.
+pub fn load_package(path: &Path) -> Package {
+    let mut package = Package::from_tarball(File::open(path).unwrap()).unwrap();
+    package.local_path = Some(path.to_owned());
+    package
+}
+
+/// Provide `count` made up packages, as a repository would list them. Each
+/// package has a few files and depends on the package before it.
+pub fn synthetic_packages(count: usize) -> Vec<Package> {
+    (0..count)
+        .map(|index| {
+            let version = Version::SemVer(index as u32 % 10, index as u32 % 7, index as u32 % 3);
+            let mut package = Package::new(format!("package-{}", index), version);
+            package.description = Some(format!("Synthetic package number {}", index));
+            package.files = vec![
+                PathBuf::from("usr/"),
+                PathBuf::from("usr/bin/"),
+                PathBuf::from(format!("usr/bin/package-{}", index)),
+                PathBuf::from(format!("usr/share/doc/package-{}/README", index)),
+            ];
+            if index > 0 {
+                package.dependencies = vec![format!("package-{}", index - 1)];
+            }
+            package.size = Some(4096);
+            package
+        })
+        .collect()
+}
+
+/// A repository that only lists the given packages. They have no tarballs,
+/// so fetching one fails.
+pub struct ListedRepository {
+    pub name: &'static str,
+    pub packages: Vec<Package>,
+}
+
+impl ListedRepository {
+    pub fn new(name: &'static str, packages: Vec<Package>) -> Self {
+        Self { name, packages }
+    }
+}
+
+impl Repository for ListedRepository {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn fetch_index(&self) -> mix::Result<Vec<Package>> {
+        Ok(self.packages.clone())
+    }
+
+    fn fetch_package(&self, package: &Package, _destination: &Path) -> mix::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is only listed, and has no tarball", package.name),
+        )
+        .into())
+    }
+}
+
+/// Provide a package at version 1.0.0 with the given dependencies, as a
+/// repository would list it.
+pub fn listed_package(name: &str, dependencies: &[&str]) -> Package {
+    let mut package = Package::new(name, Version::SemVer(1, 0, 0));
+    package.dependencies = dependencies.iter().map(|name| name.to_string()).collect();
+    package
+}
+
+/// A repository listing `count` [made up packages](synthetic_packages).
+pub fn synthetic_repository(count: usize) -> ListedRepository {
+    ListedRepository::new("synthetic", synthetic_packages(count))
+}
+
+/// Provide a database within the directory knowing about `count` made up
+/// packages, as if synchronized with a large repository.
+pub fn synthetic_database(directory: &Path, count: usize) -> Database {
+    let mut database = empty_database(directory);
+    database.synchronize(&synthetic_repository(count)).unwrap();
+    database
+}
+
+/// Run gpg with its own keyring within the directory.
+pub fn gpg(directory: &Path, arguments: &[&str]) -> Vec<u8> {
+    let output = Command::new("gpg")
+        .env("GNUPGHOME", directory.join("gnupg"))
+        .args(["--batch", "--quiet", "--passphrase", ""])
+        .args(arguments)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    output.stdout
+}
+
+/// Generate a signing key in the keyring of [gpg](gpg), providing the path
+/// of its public key.
+pub fn generate_key(directory: &Path) -> PathBuf {
+    DirBuilder::new()
+        .mode(0o700)
+        .create(directory.join("gnupg"))
+        .unwrap();
+    gpg(
+        directory,
+        &[
+            "--quick-gen-key",
+            "mix@example.com",
+            "ed25519",
+            "sign",
+            "never",
+        ],
+    );
+    let key = directory.join("key.gpg");
+    fs::write(&key, gpg(directory, &["--export", "mix@example.com"])).unwrap();
+    key
+}
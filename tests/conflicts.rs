@@ -0,0 +1,66 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{Error, InstallState, Selections, SharedPackage};
+use std::fs;
+
+#[test]
+fn conflicting_installed_package_is_replaced_only_when_asked() {
+    let directory = scratch_directory("conflicts");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let foo_tarball = directory.join("foo.tar.xz");
+    write_tarball(&foo_tarball, "name = \"foo\"", &["usr/", "usr/tool"]);
+    let bar_tarball = directory.join("bar.tar.xz");
+    write_tarball(
+        &bar_tarball,
+        "name = \"bar\"\nconflicts = [\"foo\"]",
+        &["usr/", "usr/tool"],
+    );
+    let foo = SharedPackage::new(load_package(&foo_tarball));
+    let mut selections = Selections::default();
+    selections.install.push(foo.clone());
+    database.apply(selections).unwrap();
+
+    // Only bar declares the conflict, but it goes both ways.
+    let bar = SharedPackage::new(load_package(&bar_tarball));
+    let mut selections = Selections::default();
+    selections.install.push(bar.clone());
+    match mix::selection::resolve_conflicts(&mut selections, &database, false) {
+        Err(Error::ConflictingPackages { a, b }) => {
+            assert_eq!((a, b), ("bar".into(), "foo".into()))
+        }
+        result => panic!("expected a conflict, got {:?}", result),
+    }
+
+    mix::selection::resolve_conflicts(&mut selections, &database, true).unwrap();
+    assert_eq!(selections.remove, vec![foo.clone()]);
+    database.apply(selections).unwrap();
+    assert_eq!(foo.borrow().state, InstallState::Uninstalled);
+    assert_ne!(bar.borrow().state, InstallState::Uninstalled);
+    assert!(root.join("usr/tool").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn conflicting_tarballs_are_checked_with_everything_else() {
+    let directory = scratch_directory("conflicts-tarballs");
+    let mut database = empty_database(&directory);
+    let foo_tarball = directory.join("foo.tar.xz");
+    write_tarball(&foo_tarball, "name = \"foo\"", &["usr/", "usr/tool"]);
+    let bar_tarball = directory.join("bar.tar.xz");
+    write_tarball(
+        &bar_tarball,
+        "name = \"bar\"\nconflicts = [\"foo\"]",
+        &["usr/", "usr/tool"],
+    );
+    let foo = SharedPackage::new(load_package(&foo_tarball));
+    let mut selections = Selections::default();
+    selections.install.push(foo.clone());
+    database.apply(selections).unwrap();
+
+    let mut selections = mix::selection::install_tarballs(&[&bar_tarball], &database).unwrap();
+    mix::selection::resolve_conflicts(&mut selections, &database, true).unwrap();
+    assert_eq!(selections.remove, vec![foo]);
+    fs::remove_dir_all(&directory).unwrap();
+}
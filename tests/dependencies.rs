@@ -0,0 +1,36 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{Database, Selections, SharedPackage};
+use std::fs;
+
+#[test]
+fn dependencies_survive_saving_the_database() {
+    let directory = scratch_directory("dependencies");
+    let mut database = empty_database(&directory);
+    let tarball = directory.join("bar.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"bar\"\ndependencies = [\"foo\", \"baz\"]",
+        &["usr/", "usr/bar"],
+    );
+    let bar = load_package(&tarball);
+    assert_eq!(bar.dependencies, vec!["foo", "baz"]);
+    // Dependencies don't make packages different.
+    let mut without_dependencies = bar.clone();
+    without_dependencies.dependencies.clear();
+    assert_eq!(bar, without_dependencies);
+
+    let mut selections = Selections::default();
+    selections.install.push(SharedPackage::new(bar));
+    database.apply(selections).unwrap();
+    let path = directory.join("mix.db");
+    database.save(&path).unwrap();
+
+    let database = Database::load(&path).unwrap();
+    assert_eq!(
+        database.info("bar").unwrap().dependencies,
+        vec!["foo", "baz"]
+    );
+    fs::remove_dir_all(&directory).unwrap();
+}
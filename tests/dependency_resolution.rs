@@ -0,0 +1,145 @@
+mod common;
+
+use common::{empty_database, listed_package, scratch_directory, ListedRepository};
+use mix::{Error, InstallState, Selections, SharedPackage};
+use std::fs;
+
+/// A repository of packages with dependencies between them. `app` depends on
+/// `base` twice over, through `left` and `right`, `x` and `y` depend on each
+/// other, and `broken` needs packages the repository doesn't have.
+fn diamond() -> ListedRepository {
+    ListedRepository::new(
+        "diamond",
+        vec![
+            listed_package("app", &["left", "right"]),
+            listed_package("left", &["base"]),
+            listed_package("right", &["base"]),
+            listed_package("base", &[]),
+            listed_package("tool", &["base"]),
+            listed_package("x", &["y"]),
+            listed_package("y", &["x"]),
+            listed_package("broken", &["gone", "half"]),
+            listed_package("half", &["base", "lost", "gone"]),
+        ],
+    )
+}
+
+#[test]
+fn dependencies_are_selected_once() {
+    let directory = scratch_directory("dependency-resolution");
+    let mut database = empty_database(&directory);
+    database.synchronize(&diamond()).unwrap();
+
+    let selections = mix::selection::install(&["app"], &database).unwrap();
+    let names: Vec<String> = selections
+        .install
+        .iter()
+        .map(|package| package.borrow().name.clone())
+        .collect();
+    assert_eq!(names, vec!["base", "left", "right", "app"]);
+    database.apply(selections).unwrap();
+    assert_eq!(
+        database.install_reason("app").unwrap(),
+        InstallState::Manual
+    );
+    for name in ["base", "left", "right"] {
+        assert_eq!(
+            database.install_reason(name).unwrap(),
+            InstallState::Dependency
+        );
+    }
+
+    // Installed dependencies aren't selected again.
+    let selections = mix::selection::install(&["tool"], &database).unwrap();
+    assert_eq!(selections.install.len(), 1);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn dependency_cycles_are_errors() {
+    let directory = scratch_directory("dependency-cycle");
+    let mut database = empty_database(&directory);
+    database.synchronize(&diamond()).unwrap();
+
+    let (error, _) = mix::selection::install(&["x"], &database).unwrap_err();
+    assert!(matches!(error, Error::DependencyCycle(cycle) if cycle == vec!["x", "y", "x"]));
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn missing_dependencies_are_reported_together() {
+    let directory = scratch_directory("missing-dependencies");
+    let mut database = empty_database(&directory);
+    database.synchronize(&diamond()).unwrap();
+
+    let (error, _) = mix::selection::install(&["broken"], &database).unwrap_err();
+    assert!(matches!(error, Error::PackageNotFound(missing) if missing == vec!["gone", "lost"]));
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn leftover_dependencies_are_autoremoved() {
+    let directory = scratch_directory("autoremove");
+    let mut database = empty_database(&directory);
+    database.synchronize(&diamond()).unwrap();
+    let selections = mix::selection::install(&["app", "tool"], &database).unwrap();
+    database.apply(selections).unwrap();
+    assert!(mix::selection::orphans(&database).is_empty());
+
+    let selections = mix::selection::remove(&["app"], &database).unwrap();
+    database.apply(selections).unwrap();
+    let orphans: Vec<String> = mix::selection::orphans(&database)
+        .iter()
+        .map(|package| package.borrow().name.clone())
+        .collect();
+    assert_eq!(orphans, vec!["left", "right"]);
+
+    // base is still needed by tool, so it stays.
+    let selections = mix::selection::autoremove(&database);
+    assert_eq!(selections.remove.len(), 2);
+    database.apply(selections).unwrap();
+    assert!(database.install_reason("base").is_ok());
+
+    // Once tool is gone, base goes with the leftovers.
+    let selections = mix::selection::remove(&["tool"], &database).unwrap();
+    database.apply(selections).unwrap();
+    let selections = mix::selection::autoremove(&database);
+    assert_eq!(selections.remove.len(), 1);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn autoremove_follows_providers_and_cycles() {
+    let directory = scratch_directory("autoremove-providers");
+    let mut database = empty_database(&directory);
+    let package = |name: &str, dependencies: &[&str], provides: &[&str]| {
+        let mut package = listed_package(name, dependencies);
+        package.provides = provides.iter().map(|name| name.to_string()).collect();
+        SharedPackage::new(package)
+    };
+    // p and q need each other, but nothing manually installed needs either.
+    for installed in [
+        package("vim", &[], &["editor"]),
+        package("p", &["q"], &[]),
+        package("q", &["p"], &[]),
+    ] {
+        let mut selections = Selections::default();
+        selections.install.push(installed);
+        database.apply(selections).unwrap();
+    }
+    let writer = package("writer", &["editor"], &[]);
+    writer.borrow_mut().mark_as_manually_installed();
+    let mut selections = Selections::default();
+    selections.install.push(writer);
+    database.apply(selections).unwrap();
+
+    assert!(mix::selection::orphans(&database).is_empty());
+    let mut removed: Vec<String> = mix::selection::autoremove(&database)
+        .remove
+        .iter()
+        .map(|package| package.borrow().name.clone())
+        .collect();
+    removed.sort();
+    assert_eq!(removed, vec!["p", "q"]);
+    fs::remove_dir_all(&directory).unwrap();
+}
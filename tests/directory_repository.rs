@@ -0,0 +1,105 @@
+mod common;
+
+use common::{empty_database, scratch_directory, write_tarball};
+use mix::{config::RepositoryConfig, Config, InstallState};
+use std::fs;
+
+#[test]
+fn synchronize_and_install_from_directory() {
+    let directory = scratch_directory("directory-repository");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let repository = directory.join("repository");
+    fs::create_dir(&repository).unwrap();
+    write_tarball(
+        &repository.join("foo-1.0.0.tar.xz"),
+        "name = \"foo\"",
+        &["usr/", "usr/bin/", "usr/bin/foo"],
+    );
+    fs::write(
+        repository.join("index.toml"),
+        "[[packages]]\n\
+         name = \"foo\"\n\
+         version = \"1.0.0\"\n\
+         files = [\"usr\", \"usr/bin\", \"usr/bin/foo\"]\n",
+    )
+    .unwrap();
+    database.set_config(Config {
+        repositories: vec![RepositoryConfig {
+            name: String::from("local"),
+            mirrors: vec![format!("file://{}", repository.display())],
+        }],
+        download_cache: Some(directory.join("downloads")),
+        ..database.config().clone()
+    });
+
+    for repository in database.repositories() {
+        database.synchronize(repository.as_ref()).unwrap();
+    }
+    assert!(database.sync_age().is_some());
+    let selections = mix::selection::install(&["foo"], &database)
+        .map_err(|(error, _)| error)
+        .unwrap();
+    database.apply(selections).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(root.join("usr/bin/foo")).unwrap(),
+        "usr/bin/foo"
+    );
+    // Downloads are kept apart from the package cache.
+    assert!(directory.join("downloads/foo-1.0.0.tar.xz").exists());
+    assert!(!directory.join("cache/foo-1.0.0.tar.xz").exists());
+    let info = database.info("foo").unwrap();
+    assert_eq!(info.repository.as_deref(), Some("local"));
+    assert_ne!(info.state, InstallState::Uninstalled);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn corrupt_cached_tarballs_are_downloaded_again() {
+    let directory = scratch_directory("corrupt-cache");
+    let mut database = empty_database(&directory);
+    let repository = directory.join("repository");
+    fs::create_dir(&repository).unwrap();
+    let tarball = repository.join("foo-1.0.0.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let checksum = mix::package::checksum(fs::File::open(&tarball).unwrap()).unwrap();
+    fs::write(
+        repository.join("index.toml"),
+        format!(
+            "[[packages]]\n\
+             name = \"foo\"\n\
+             version = \"1.0.0\"\n\
+             checksum = \"{}\"\n\
+             files = [\"usr\", \"usr/foo\"]\n",
+            checksum
+        ),
+    )
+    .unwrap();
+    let downloads = directory.join("downloads");
+    database.set_config(Config {
+        repositories: vec![RepositoryConfig {
+            name: String::from("local"),
+            mirrors: vec![repository.display().to_string()],
+        }],
+        download_cache: Some(downloads.clone()),
+        ..database.config().clone()
+    });
+    for repository in database.repositories() {
+        database.synchronize(repository.as_ref()).unwrap();
+    }
+    let selections = mix::selection::install(&["foo"], &database)
+        .map_err(|(error, _)| error)
+        .unwrap();
+    fs::create_dir_all(&downloads).unwrap();
+    fs::write(downloads.join("foo-1.0.0.tar.xz"), "corrupt").unwrap();
+
+    assert_eq!(database.fetch_all(&selections.install).unwrap().len(), 1);
+    assert_eq!(
+        fs::read(downloads.join("foo-1.0.0.tar.xz")).unwrap(),
+        fs::read(&tarball).unwrap()
+    );
+    // The valid copy is kept from now on.
+    assert!(database.fetch_all(&selections.install).unwrap().is_empty());
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{
+    empty_database, listed_package, load_package, scratch_directory, write_tarball,
+    ListedRepository,
+};
+use mix::{ConsistencyWarning, InstallState, Selections, SharedPackage, Version};
+use std::fs;
+
+/// A repository with only the meta-package `baz`.
+fn baz() -> ListedRepository {
+    ListedRepository::new("baz", vec![listed_package("baz", &[])])
+}
+
+/// Provide a meta-package with the given dependencies.
+fn meta(name: &str, dependencies: &[&str]) -> SharedPackage {
+    let mut package = listed_package(name, dependencies);
+    package.mark_as_manually_installed();
+    SharedPackage::new(package)
+}
+
+#[test]
+fn repairs_fix_every_broken_package() {
+    let directory = scratch_directory("fix-broken");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    database.synchronize(&baz()).unwrap();
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let mut foo = load_package(&tarball);
+    foo.mark_as_manually_installed();
+    let mut selections = Selections::default();
+    selections.install.push(SharedPackage::new(foo));
+    // Neither dependency is installed along with them.
+    selections.install.push(meta("bar", &["baz"]));
+    selections.install.push(meta("qux", &["missing"]));
+    database.apply(selections).unwrap();
+    fs::remove_file(root.join("usr/foo")).unwrap();
+
+    let repairs = database.compute_repairs();
+    let summary = repairs.describe();
+    let names = |packages: &[(String, Version)]| -> Vec<String> {
+        packages.iter().map(|(name, _)| name.clone()).collect()
+    };
+    assert_eq!(names(&summary.repair), vec!["foo"]);
+    assert_eq!(names(&summary.install), vec!["baz"]);
+    assert_eq!(names(&summary.remove), vec!["qux"]);
+
+    database.apply(repairs).unwrap();
+
+    assert!(root.join("usr/foo").exists());
+    assert_eq!(
+        database.info("baz").unwrap().state,
+        InstallState::Dependency
+    );
+    assert_eq!(
+        database.info("qux").unwrap().state,
+        InstallState::Uninstalled
+    );
+    assert!(!database
+        .validate()
+        .iter()
+        .any(|warning| matches!(warning, ConsistencyWarning::BrokenDependency { .. })));
+    assert_eq!(database.compute_repairs().describe().count(), 0);
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{Selections, SharedPackage};
+use std::{fs, os::unix::fs::MetadataExt};
+
+#[test]
+fn hardlinks_share_the_file() {
+    let directory = scratch_directory("hardlinks");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"foo\"",
+        &[
+            "usr/",
+            "usr/bin/",
+            "usr/bin/foo",
+            "usr/bin/bar => usr/bin/foo",
+        ],
+    );
+
+    let mut selections = Selections::default();
+    selections
+        .install
+        .push(SharedPackage::new(load_package(&tarball)));
+    database.apply(selections).unwrap();
+
+    let foo = fs::metadata(root.join("usr/bin/foo")).unwrap();
+    let bar = fs::metadata(root.join("usr/bin/bar")).unwrap();
+    assert_eq!(foo.ino(), bar.ino());
+    assert_eq!(foo.nlink(), 2);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn hardlinks_before_their_target_still_install() {
+    let directory = scratch_directory("hardlinks-early");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"foo\"",
+        &[
+            "usr/",
+            "usr/bin/",
+            "usr/bin/bar => usr/bin/foo",
+            "usr/bin/foo",
+        ],
+    );
+
+    let mut selections = Selections::default();
+    selections
+        .install
+        .push(SharedPackage::new(load_package(&tarball)));
+    database.apply(selections).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(root.join("usr/bin/bar")).unwrap(),
+        "usr/bin/foo"
+    );
+    let foo = fs::metadata(root.join("usr/bin/foo")).unwrap();
+    let bar = fs::metadata(root.join("usr/bin/bar")).unwrap();
+    assert_eq!(foo.ino(), bar.ino());
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::{scratch_directory, write_tarball};
+use mix::{
+    filesystem::{InMemoryFileSystem, Node},
+    Config, Database, Selections, SharedPackage,
+};
+use std::{fs, path::Path, sync::Arc};
+
+#[test]
+fn install_and_remove_in_memory() {
+    let directory = scratch_directory("in-memory");
+    let filesystem = Arc::new(InMemoryFileSystem::default());
+    let mut database = Database::new_empty(directory.join("cache"));
+    database.set_config(Config {
+        root: "/".into(),
+        ..Config::default()
+    });
+    database.set_filesystem(filesystem.clone());
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"foo\"",
+        &["usr/", "usr/bin/", "usr/bin/foo"],
+    );
+    let foo = SharedPackage::new(common::load_package(&tarball));
+    let mut selections = Selections::default();
+    selections.install.push(foo.clone());
+
+    database.apply(selections).unwrap();
+
+    let mode = database.config().installed_mode(0o755);
+    assert_eq!(
+        filesystem.get(Path::new("/usr/bin/foo")),
+        Some(Node::File {
+            contents: b"usr/bin/foo".to_vec(),
+            mode
+        })
+    );
+    assert_eq!(
+        filesystem.get(Path::new("/usr/bin")),
+        Some(Node::Directory { mode })
+    );
+
+    let mut selections = Selections::default();
+    selections.remove.push(foo);
+    database.apply(selections).unwrap();
+
+    assert_eq!(filesystem.paths(), vec![Path::new("/").to_owned()]);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn missing_files_are_found_in_memory() {
+    let directory = scratch_directory("in-memory-missing");
+    let filesystem = Arc::new(InMemoryFileSystem::default());
+    let mut database = Database::new_empty(directory.join("cache"));
+    database.set_config(Config {
+        root: "/".into(),
+        ..Config::default()
+    });
+    database.set_filesystem(filesystem.clone());
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"foo\"",
+        &["opt/", "opt/mix-in-memory/", "opt/mix-in-memory/foo"],
+    );
+    let mut selections = Selections::default();
+    selections
+        .install
+        .push(SharedPackage::new(common::load_package(&tarball)));
+    database.apply(selections).unwrap();
+
+    // None of the files exist on the real disk, only in memory.
+    assert!(database.missing_files("foo").unwrap().is_empty());
+    assert!(database.compute_repairs().repair.is_empty());
+    fs::remove_dir_all(&directory).unwrap();
+}
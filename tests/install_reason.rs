@@ -0,0 +1,141 @@
+mod common;
+
+use common::{
+    empty_database, listed_package, load_package, scratch_directory, write_tarball,
+    ListedRepository,
+};
+use mix::{ConsistencyWarning, Database, InstallState, SharedPackage};
+use std::fs;
+
+#[test]
+fn install_as_dependency_leaves_an_orphan() {
+    let directory = scratch_directory("install-reason");
+    let mut database = empty_database(&directory);
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let foo = SharedPackage::new(load_package(&tarball));
+    let mut selections = mix::Selections::default();
+    selections.install.push(foo.clone());
+
+    mix::selection::set_install_reason(&mut selections, &["foo"], InstallState::Dependency);
+    database.apply(selections).unwrap();
+
+    assert_eq!(foo.borrow().state, InstallState::Dependency);
+    assert!(database
+        .validate()
+        .contains(&ConsistencyWarning::Orphan(String::from("foo"))));
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn install_explicitly_is_manual() {
+    let directory = scratch_directory("install-reason-explicit");
+    let mut database = empty_database(&directory);
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let foo = SharedPackage::new(load_package(&tarball));
+    let mut selections = mix::Selections::default();
+    selections.install.push(foo.clone());
+
+    mix::selection::set_install_reason(&mut selections, &["foo"], InstallState::Manual);
+    database.apply(selections).unwrap();
+
+    assert_eq!(foo.borrow().state, InstallState::Manual);
+    assert!(database.validate().is_empty());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn undoing_a_removal_keeps_the_reason_until_applied() {
+    let directory = scratch_directory("install-reason-undo");
+    let database = empty_database(&directory);
+    let config = database.config().clone();
+    let path = directory.join("mix.db");
+    database.save(&path).unwrap();
+    let mut database = Database::load(&path).unwrap();
+    database.set_config(config);
+    database.set_package_cache(directory.join("cache"));
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let foo = mix::package::install_tarball(&tarball, &mut database).unwrap();
+    let mut selections = mix::Selections::default();
+    selections.remove.push(foo.clone());
+    database.apply(selections).unwrap();
+
+    let selections = database.undo_selections().unwrap();
+    assert_eq!(foo.borrow().state, InstallState::Uninstalled);
+    assert!(selections
+        .explicit
+        .iter()
+        .any(|package| SharedPackage::ptr_eq(package, &foo)));
+    database.apply(selections).unwrap();
+
+    assert_eq!(
+        database.install_reason("foo").unwrap(),
+        InstallState::Manual
+    );
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn selecting_an_install_changes_nothing() {
+    let directory = scratch_directory("install-reason-selecting");
+    let mut database = empty_database(&directory);
+    let listing = ListedRepository::new(
+        "listing",
+        vec![listed_package("foo", &["bar"]), listed_package("bar", &[])],
+    );
+    database.synchronize(&listing).unwrap();
+
+    let selections = mix::selection::install(&["foo"], &database).unwrap();
+
+    assert_eq!(selections.explicit.len(), 1);
+    assert_eq!(selections.explicit[0].borrow().name, "foo");
+    for name in ["foo", "bar"] {
+        assert_eq!(
+            database.info(name).unwrap().state,
+            InstallState::Uninstalled
+        );
+    }
+    database.apply(selections).unwrap();
+    assert_eq!(
+        database.install_reason("foo").unwrap(),
+        InstallState::Manual
+    );
+    assert_eq!(
+        database.install_reason("bar").unwrap(),
+        InstallState::Dependency
+    );
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn a_failed_install_saves_nothing_installed() {
+    let directory = scratch_directory("install-reason-failed");
+    let mut database = empty_database(&directory);
+    let config = database.config().clone();
+    let path = directory.join("mix.db");
+    // Packages with files have a tarball to fetch, which the listing lacks.
+    let mut foo = listed_package("foo", &["bar"]);
+    foo.files = vec!["usr/foo".into()];
+    let mut bar = listed_package("bar", &[]);
+    bar.files = vec!["usr/bar".into()];
+    database
+        .synchronize(&ListedRepository::new("listing", vec![foo, bar]))
+        .unwrap();
+
+    let selections = mix::selection::install(&["foo"], &database).unwrap();
+    assert!(database.apply(selections).is_err());
+    database.save(&path).unwrap();
+
+    let mut database = Database::load(&path).unwrap();
+    database.set_config(config);
+    database.set_package_cache(directory.join("cache"));
+    for name in ["foo", "bar"] {
+        assert_eq!(
+            database.info(name).unwrap().state,
+            InstallState::Uninstalled
+        );
+    }
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::{empty_database, scratch_directory, write_tarball};
+use mix::{repository::DirectoryRepository, Database, Error, InstallState};
+use std::fs;
+
+#[test]
+fn dependencies_resolve_among_the_tarballs() {
+    let directory = scratch_directory("install-tarballs");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let foo = directory.join("foo.tar.xz");
+    write_tarball(&foo, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let bar = directory.join("bar.tar.xz");
+    write_tarball(
+        &bar,
+        "name = \"bar\"\ndependencies = [\"foo\", \"baz\"]",
+        &["usr/", "usr/bar"],
+    );
+
+    match mix::selection::install_tarballs(&[&bar], &database) {
+        Err(Error::PackageNotFound(missing)) => assert_eq!(missing, vec!["foo", "baz"]),
+        result => panic!("expected missing dependencies, got {:?}", result),
+    }
+
+    let baz = directory.join("baz.tar.xz");
+    write_tarball(&baz, "name = \"baz\"", &["usr/", "usr/baz"]);
+    // The dependencies are listed last, but installed first.
+    let selections = mix::selection::install_tarballs(&[&bar, &foo, &baz], &database).unwrap();
+    database.apply(selections).unwrap();
+    for file in ["usr/foo", "usr/bar", "usr/baz"] {
+        assert!(root.join(file).exists());
+    }
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn single_pass_install_marks_the_known_package() {
+    let directory = scratch_directory("install-tarball-known");
+    let mut database = empty_database(&directory);
+    let config = database.config().clone();
+    let repository = directory.join("repository");
+    fs::create_dir(&repository).unwrap();
+    fs::write(
+        repository.join("index.toml"),
+        "[[packages]]\n\
+         name = \"foo\"\n\
+         version = \"1.0.0\"\n\
+         files = [\"usr\", \"usr/foo\"]\n",
+    )
+    .unwrap();
+    database
+        .synchronize(&DirectoryRepository::new("local", &repository))
+        .unwrap();
+    let path = directory.join("mix.db");
+    database.save(&path).unwrap();
+    let mut database = Database::load(&path).unwrap();
+    database.set_config(config.clone());
+    database.set_package_cache(directory.join("cache"));
+
+    let tarball = directory.join("foo-1.0.0.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"foo\"\nversion = \"1.0.0\"",
+        &["usr/", "usr/foo"],
+    );
+    let package = mix::package::install_tarball(&tarball, &mut database).unwrap();
+
+    let info = database.info("foo").unwrap();
+    assert_eq!(info.state, InstallState::Manual);
+    assert_eq!(info.repository.as_deref(), Some("local"));
+    assert_eq!(package.borrow().repository.as_deref(), Some("local"));
+    assert!(config.root.join("usr/foo").exists());
+    let history = mix::history::read(&mix::history::log_path(&path)).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].changes[0].name, "foo");
+    assert!(database.pending_transaction().unwrap().is_none());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn failed_single_pass_installs_are_not_left_pending() {
+    let directory = scratch_directory("install-tarball-failed");
+    let database = empty_database(&directory);
+    let config = database.config().clone();
+    let path = directory.join("mix.db");
+    database.save(&path).unwrap();
+    let mut database = Database::load(&path).unwrap();
+    database.set_config(config.clone());
+    database.set_package_cache(directory.join("cache"));
+
+    let bar = directory.join("bar.tar.xz");
+    write_tarball(
+        &bar,
+        "name = \"bar\"\ndependencies = [\"baz\"]",
+        &["usr/", "usr/bar"],
+    );
+    match mix::package::install_tarball(&bar, &mut database) {
+        Err(Error::PackageNotFound(missing)) => assert_eq!(missing, vec!["baz"]),
+        result => panic!("expected a missing dependency, got {:?}", result),
+    }
+    assert!(!config.root.join("usr").exists());
+
+    // A file that's already there makes the install fail partway.
+    fs::create_dir(config.root.join("usr")).unwrap();
+    fs::write(config.root.join("usr/zzz"), "mine").unwrap();
+    let foo = directory.join("foo.tar.xz");
+    write_tarball(&foo, "name = \"foo\"", &["usr/", "usr/foo", "usr/zzz"]);
+    assert!(mix::package::install_tarball(&foo, &mut database).is_err());
+
+    assert!(!config.root.join("usr/foo").exists());
+    assert!(database.pending_transaction().unwrap().is_none());
+    assert!(database.info("foo").is_err());
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{Database, Selections, SharedPackage};
+use std::{fs, path::PathBuf};
+
+#[test]
+fn implied_directories_are_recorded_and_removed() {
+    let directory = scratch_directory("installed-files");
+    let mut database = empty_database(&directory);
+    let config = database.config().clone();
+    let tarball = directory.join("foo.tar.xz");
+    // Like many tarballs, this one has no entries for its directories.
+    write_tarball(
+        &tarball,
+        "name = \"foo\"",
+        &["usr/bin/foo", "usr/share/foo"],
+    );
+
+    let package = SharedPackage::new(load_package(&tarball));
+    let mut selections = Selections::default();
+    selections.install.push(package.clone());
+    database.apply(selections).unwrap();
+
+    let mut files = package.borrow().files.clone();
+    files.sort();
+    assert_eq!(
+        files,
+        [
+            "usr",
+            "usr/bin",
+            "usr/bin/foo",
+            "usr/share",
+            "usr/share/foo"
+        ]
+        .map(PathBuf::from)
+    );
+    assert_eq!(database.audit("foo").unwrap(), []);
+    let path = directory.join("mix.db");
+    database.save(&path).unwrap();
+
+    let mut database = Database::load(&path).unwrap();
+    database.set_config(config.clone());
+    let selections = mix::selection::remove(&["foo"], &database).unwrap();
+    database.apply(selections).unwrap();
+
+    assert_eq!(fs::read_dir(&config.root).unwrap().count(), 0);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn existing_directories_are_not_recorded() {
+    let directory = scratch_directory("installed-files-existing");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    fs::create_dir_all(root.join("usr/share")).unwrap();
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/share/foo/data"]);
+
+    let package = SharedPackage::new(load_package(&tarball));
+    let mut selections = Selections::default();
+    selections.install.push(package.clone());
+    database.apply(selections).unwrap();
+
+    let mut files = package.borrow().files.clone();
+    files.sort();
+    assert_eq!(
+        files,
+        ["usr/share/foo", "usr/share/foo/data"].map(PathBuf::from)
+    );
+    let mut selections = Selections::default();
+    selections.remove.push(package);
+    database.apply(selections).unwrap();
+
+    assert!(root.join("usr/share").is_dir());
+    assert!(!root.join("usr/share/foo").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
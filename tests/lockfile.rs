@@ -0,0 +1,111 @@
+mod common;
+
+use common::{empty_database, scratch_directory, write_tarball};
+use mix::{config::RepositoryConfig, Config, Database, Error, InstallState};
+use std::{
+    fs::{self, File},
+    path::Path,
+};
+
+/// Provide a database synchronized with a directory repository holding foo
+/// 1.0.0, which depends on bar 1.0.0, along with the checksums of their
+/// tarballs. Nothing is in the package cache yet.
+fn repository_database(directory: &Path) -> (Database, String, String) {
+    let mut database = empty_database(directory);
+    let repository = directory.join("repository");
+    fs::create_dir(&repository).unwrap();
+    let foo_tarball = repository.join("foo-1.0.0.tar.xz");
+    write_tarball(&foo_tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let bar_tarball = repository.join("bar-1.0.0.tar.xz");
+    write_tarball(&bar_tarball, "name = \"bar\"", &["usr/", "usr/bar"]);
+    fs::write(
+        repository.join("index.toml"),
+        "[[packages]]\n\
+         name = \"foo\"\n\
+         version = \"1.0.0\"\n\
+         dependencies = [\"bar\"]\n\
+         files = [\"usr\", \"usr/foo\"]\n\
+         [[packages]]\n\
+         name = \"bar\"\n\
+         version = \"1.0.0\"\n\
+         files = [\"usr\", \"usr/bar\"]\n",
+    )
+    .unwrap();
+    database.set_config(Config {
+        repositories: vec![RepositoryConfig {
+            name: String::from("local"),
+            mirrors: vec![format!("file://{}", repository.display())],
+        }],
+        ..database.config().clone()
+    });
+    for repository in database.repositories() {
+        database.synchronize(repository.as_ref()).unwrap();
+    }
+    let checksum = |tarball| mix::package::checksum(File::open(tarball).unwrap()).unwrap();
+    (database, checksum(foo_tarball), checksum(bar_tarball))
+}
+
+/// Provide the lockfile entry pinning a package.
+fn locked(name: &str, version: &str, checksum: &str, state: &str) -> String {
+    format!(
+        "[[package]]\nname = \"{}\"\nversion = \"{}\"\nchecksum = \"{}\"\nstate = \"{}\"\n",
+        name, version, checksum, state
+    )
+}
+
+#[test]
+fn locked_packages_are_downloaded_and_keep_their_state() {
+    let directory = scratch_directory("lockfile");
+    let (mut database, foo, bar) = repository_database(&directory);
+    let lockfile = directory.join("mix.lock");
+    fs::write(
+        &lockfile,
+        locked("foo", "1.0.0", &foo, "Manual") + &locked("bar", "1.0.0", &bar, "Dependency"),
+    )
+    .unwrap();
+
+    database.install_from_lockfile(&lockfile).unwrap();
+
+    assert_eq!(
+        database.install_reason("foo").unwrap(),
+        InstallState::Manual
+    );
+    assert_eq!(
+        database.install_reason("bar").unwrap(),
+        InstallState::Dependency
+    );
+    assert!(database.config().root.join("usr/foo").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn mismatched_locks_install_nothing() {
+    let directory = scratch_directory("lockfile-mismatch");
+    let (mut database, foo, bar) = repository_database(&directory);
+    let lockfile = directory.join("mix.lock");
+    fs::write(
+        &lockfile,
+        locked("foo", "1.0.0", &foo, "Manual") + &locked("bar", "1.0.0", &foo, "Dependency"),
+    )
+    .unwrap();
+    match database.install_from_lockfile(&lockfile) {
+        Err(Error::ChecksumMismatch { expected, found }) => {
+            assert_eq!(expected, foo);
+            assert_eq!(found, bar);
+        }
+        result => panic!("expected a checksum mismatch, got {:?}", result),
+    }
+    assert_eq!(
+        database.info("foo").unwrap().state,
+        InstallState::Uninstalled
+    );
+
+    fs::write(&lockfile, locked("bar", "1.0.0", &bar, "Manual")).unwrap();
+    database.install_from_lockfile(&lockfile).unwrap();
+    fs::write(&lockfile, locked("bar", "0.9.0", &bar, "Manual")).unwrap();
+    assert!(matches!(
+        database.install_from_lockfile(&lockfile),
+        Err(Error::LockedVersionInstalled { .. })
+    ));
+    fs::remove_dir_all(&directory).unwrap();
+}
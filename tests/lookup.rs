@@ -0,0 +1,49 @@
+mod common;
+
+use common::{scratch_directory, synthetic_database, synthetic_repository};
+use mix::{Database, InstallState, Package, Selections, SharedPackage, Version};
+use std::fs;
+
+#[test]
+fn lookups_follow_imports_and_removals() {
+    let directory = scratch_directory("lookup");
+    let mut database = synthetic_database(&directory, 100);
+    assert_eq!(
+        database.info("package-42").unwrap().version,
+        Version::SemVer(2, 0, 0)
+    );
+    assert!(database.info("package-100").is_err());
+
+    // Synchronizing again imports only the packages that are new.
+    database.synchronize(&synthetic_repository(200)).unwrap();
+    assert_eq!(database.all_packages().len(), 200);
+    assert_eq!(database.info("package-150").unwrap().name, "package-150");
+
+    let meta = SharedPackage::new(Package::new("meta", Version::SemVer(1, 0, 0)));
+    let mut selections = Selections::default();
+    selections.install.push(meta.clone());
+    database.apply(selections).unwrap();
+    assert_ne!(
+        database.info("meta").unwrap().state,
+        InstallState::Uninstalled
+    );
+    let mut selections = Selections::default();
+    selections.remove.push(meta);
+    database.apply(selections).unwrap();
+    assert_eq!(
+        database.info("meta").unwrap().state,
+        InstallState::Uninstalled
+    );
+    assert!(database.install_reason("meta").is_err());
+
+    // The index isn't saved, so it's rebuilt when loading.
+    let path = directory.join("mix.db");
+    database.save(&path).unwrap();
+    let database = Database::load(&path).unwrap();
+    assert_eq!(database.info("package-199").unwrap().name, "package-199");
+    assert_eq!(
+        database.info("meta").unwrap().state,
+        InstallState::Uninstalled
+    );
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::{empty_database, scratch_directory, write_tarball_with, TarballLayout};
+use mix::{Config, Package};
+use std::{fs, fs::File};
+
+#[test]
+fn manifest_name_is_configurable() {
+    let directory = scratch_directory("manifest-name");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    database.set_config(Config {
+        manifest_name: Some(String::from(".PKGINFO")),
+        ..database.config().clone()
+    });
+    let tarball = directory.join("foo.tar.xz");
+    // Made from within its directory, so every entry starts with `./`, with
+    // the manifest under another name at the end.
+    write_tarball_with(
+        &tarball,
+        "name = \"foo\"",
+        &["usr/", "usr/foo"],
+        &TarballLayout {
+            manifest_name: ".PKGINFO",
+            manifest_last: true,
+            prefix: "./",
+            ..TarballLayout::default()
+        },
+    );
+    assert!(Package::from_tarball(File::open(&tarball).unwrap()).is_err());
+
+    let selections = mix::selection::install_tarballs(&[&tarball], &database).unwrap();
+    database.apply(selections).unwrap();
+
+    assert_eq!(fs::read_to_string(root.join("usr/foo")).unwrap(), "usr/foo");
+    assert!(!root.join(".PKGINFO").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::{scratch_directory, write_tarball};
+use mix::{Error, Package, Version};
+use std::{fs, fs::File};
+
+#[test]
+fn manifest_version_is_read() {
+    let directory = scratch_directory("manifest-version");
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"foo\"\nversion = \"1.2.3\"",
+        &["usr/", "usr/foo"],
+    );
+    let package = Package::from_tarball(File::open(&tarball).unwrap()).unwrap();
+    assert_eq!(package.version, Version::SemVer(1, 2, 3));
+    assert_eq!(package.version.to_string(), "1.2.3");
+
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let package = Package::from_tarball(File::open(&tarball).unwrap()).unwrap();
+    assert_eq!(package.version, Version::Unknown);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn invalid_manifest_version_is_an_error() {
+    let directory = scratch_directory("invalid-manifest-version");
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"foo\"\nversion = \"1.two.3\"",
+        &["usr/", "usr/foo"],
+    );
+    let error = Package::from_tarball(File::open(&tarball).unwrap()).unwrap_err();
+    assert!(matches!(error, Error::InvalidVersion(version) if version == "1.two.3"));
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,33 @@
+use mix::{Config, Database, InstallState, Package, Selections, SharedPackage, Version};
+use std::fs;
+
+#[test]
+fn package_without_files_installs_and_removes() {
+    let directory =
+        std::env::temp_dir().join(format!("mix-test-meta-package-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(directory.join("root")).unwrap();
+    let mut database = Database::new_empty(directory.join("cache"));
+    database.set_config(Config {
+        root: directory.join("root"),
+        ..Config::default()
+    });
+    let mut group = Package::new("group", Version::SemVer(1, 0, 0));
+    group.dependencies = vec![String::from("member")];
+    let group = SharedPackage::new(group);
+
+    let mut selections = Selections::default();
+    selections.install.push(group.clone());
+    database.apply(selections).unwrap();
+    let listed = database.all_packages();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].name, "group");
+    assert_ne!(listed[0].state, InstallState::Uninstalled);
+    assert_eq!(fs::read_dir(directory.join("root")).unwrap().count(), 0);
+
+    let mut selections = Selections::default();
+    selections.remove.push(group);
+    database.apply(selections).unwrap();
+    assert_eq!(database.all_packages()[0].state, InstallState::Uninstalled);
+    fs::remove_dir_all(&directory).unwrap();
+}
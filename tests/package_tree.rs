@@ -0,0 +1,30 @@
+mod common;
+
+use common::{empty_database, scratch_directory};
+use mix::{Package, Selections, SharedPackage};
+use std::{fs, os::unix::fs::symlink, path::Path};
+
+#[test]
+fn unpacked_trees_can_be_installed() {
+    let directory = scratch_directory("package-tree");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tree = directory.join("foo");
+    fs::create_dir_all(tree.join("usr/bin")).unwrap();
+    fs::write(tree.join(".MANIFEST"), "name = \"foo\"").unwrap();
+    fs::write(tree.join("usr/bin/foo"), "foo").unwrap();
+    symlink("foo", tree.join("usr/bin/bar")).unwrap();
+
+    let package = SharedPackage::new(Package::from_directory(&tree).unwrap());
+    let mut selections = Selections::default();
+    selections.install.push(package);
+    database.apply(selections).unwrap();
+
+    assert_eq!(fs::read_to_string(root.join("usr/bin/foo")).unwrap(), "foo");
+    assert_eq!(
+        fs::read_link(root.join("usr/bin/bar")).unwrap(),
+        Path::new("foo")
+    );
+    assert!(!root.join(".MANIFEST").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,115 @@
+use mix::{Config, Database, InstallState, Package, Selections, SharedPackage, Version};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+use xz2::write::XzEncoder;
+
+/// Create an empty directory for the test to work in.
+fn scratch_directory(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("mix-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// Write a package tarball containing a shared `usr/bin` directory and one
+/// file named after the package, providing the loaded package.
+fn make_package(directory: &Path, name: &str) -> SharedPackage {
+    let path = directory.join(format!("{}.tar.xz", name));
+    let mut builder = tar::Builder::new(XzEncoder::new(File::create(&path).unwrap(), 6));
+    let mut append = |path: &str, entry_type, contents: &[u8]| {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(0o755);
+        header.set_size(contents.len() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, path, contents).unwrap();
+    };
+    append(
+        ".MANIFEST",
+        tar::EntryType::Regular,
+        format!("name = \"{}\"", name).as_bytes(),
+    );
+    append("usr", tar::EntryType::Directory, b"");
+    append("usr/bin", tar::EntryType::Directory, b"");
+    append(
+        &format!("usr/bin/{}", name),
+        tar::EntryType::Regular,
+        name.as_bytes(),
+    );
+    builder.into_inner().unwrap().finish().unwrap();
+    let mut package = Package::from_tarball(File::open(&path).unwrap()).unwrap();
+    package.local_path = Some(path);
+    SharedPackage::new(package)
+}
+
+#[test]
+fn parallel_install_places_every_file() {
+    let directory = scratch_directory("parallel-install");
+    let root = directory.join("root");
+    fs::create_dir(&root).unwrap();
+    let mut database = Database::new_empty(directory.join("cache"));
+    database.set_config(Config {
+        root: root.clone(),
+        install_jobs: 4,
+        ..Config::default()
+    });
+    let names = ["alpha", "beta", "gamma", "delta", "epsilon", "zeta"];
+    let mut selections = Selections::default();
+    for name in &names {
+        selections.install.push(make_package(&directory, name));
+    }
+
+    database.apply(selections).unwrap();
+
+    for name in &names {
+        let file = root.join("usr/bin").join(name);
+        assert_eq!(fs::read_to_string(&file).unwrap(), *name);
+    }
+    for package in database.all_packages() {
+        assert!(package.state != mix::InstallState::Uninstalled);
+    }
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn parallel_upgrades_keep_install_state() {
+    let directory = scratch_directory("parallel-upgrade");
+    let root = directory.join("root");
+    fs::create_dir(&root).unwrap();
+    let mut database = Database::new_empty(directory.join("cache"));
+    database.set_config(Config {
+        root: root.clone(),
+        install_jobs: 4,
+        ..Config::default()
+    });
+    let names = ["alpha", "beta", "gamma"];
+    let mut selections = Selections::default();
+    for name in &names {
+        let package = make_package(&directory, name);
+        package.borrow_mut().version = Version::SemVer(1, 0, 0);
+        package.borrow_mut().mark_as_manually_installed();
+        selections.install.push(package);
+    }
+    database.apply(selections).unwrap();
+
+    let upgrades = directory.join("upgrades");
+    fs::create_dir(&upgrades).unwrap();
+    let mut selections = Selections::default();
+    for name in &names {
+        let package = make_package(&upgrades, name);
+        package.borrow_mut().version = Version::SemVer(2, 0, 0);
+        selections.upgrade.push(package);
+    }
+    let upgraded = selections.upgrade.clone();
+    database.apply(selections).unwrap();
+
+    for name in &names {
+        assert!(root.join("usr/bin").join(name).exists());
+    }
+    for package in upgraded {
+        assert_eq!(package.borrow().state, InstallState::Manual);
+    }
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{Selections, SharedPackage};
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+fn mode(path: &Path) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o7777
+}
+
+#[test]
+fn files_keep_their_mode() {
+    let directory = scratch_directory("permissions");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("foo.tar.xz");
+    // A binary and a read-only file, with their own modes.
+    write_tarball(
+        &tarball,
+        "name = \"foo\"",
+        &["usr/", "usr/bin/", "usr/bin/foo", "usr/foo.conf @ 444"],
+    );
+
+    let mut selections = Selections::default();
+    selections
+        .install
+        .push(SharedPackage::new(load_package(&tarball)));
+    database.apply(selections).unwrap();
+
+    let config = database.config();
+    assert_eq!(
+        mode(&root.join("usr/bin/foo")),
+        config.installed_mode(0o755)
+    );
+    assert_eq!(mode(&root.join("usr/bin/foo")) & 0o111, 0o111);
+    // The contents were written before the file was made read-only.
+    assert_eq!(
+        mode(&root.join("usr/foo.conf")),
+        config.installed_mode(0o444)
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("usr/foo.conf")).unwrap(),
+        "usr/foo.conf"
+    );
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{Config, Selections, SharedPackage};
+use std::{fs, path::PathBuf};
+
+#[test]
+fn every_placed_file_is_reported() {
+    let directory = scratch_directory("progress");
+    let mut database = empty_database(&directory);
+    database.set_config(Config {
+        install_jobs: 2,
+        ..database.config().clone()
+    });
+    let mut selections = Selections::default();
+    for name in ["foo", "bar"] {
+        let tarball = directory.join(format!("{}.tar.xz", name));
+        let file = format!("usr/{}", name);
+        write_tarball(&tarball, &format!("name = \"{}\"", name), &["usr/", &file]);
+        selections
+            .install
+            .push(SharedPackage::new(load_package(&tarball)));
+    }
+
+    let mut placed = vec![];
+    database
+        .apply_with_progress(selections, &mut |path| placed.push(path.to_owned()))
+        .unwrap();
+
+    // Packages installed at the same time report in any order.
+    placed.sort();
+    assert_eq!(
+        placed,
+        ["usr/", "usr/", "usr/bar", "usr/foo"]
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>()
+    );
+    fs::remove_dir_all(&directory).unwrap();
+}
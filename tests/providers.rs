@@ -0,0 +1,60 @@
+mod common;
+
+use common::{empty_database, listed_package, scratch_directory, ListedRepository};
+use mix::{Package, Version};
+use std::fs;
+
+/// A repository of editors, two of which provide `editor`, and a package
+/// that needs an editor.
+fn editors() -> ListedRepository {
+    let editor = |name: &str, version, provides: bool| {
+        let mut package = Package::new(name, version);
+        if provides {
+            package.provides = vec![String::from("editor")];
+        }
+        package
+    };
+    ListedRepository::new(
+        "editors",
+        vec![
+            editor("nano", Version::SemVer(7, 2, 0), true),
+            editor("ed", Version::SemVer(1, 19, 0), false),
+            editor("vim", Version::SemVer(9, 0, 0), true),
+            listed_package("writer", &["editor"]),
+        ],
+    )
+}
+
+#[test]
+fn providers_are_newest_first() {
+    let directory = scratch_directory("providers");
+    let mut database = empty_database(&directory);
+    database.synchronize(&editors()).unwrap();
+
+    let providers: Vec<String> = database
+        .packages_providing("editor")
+        .into_iter()
+        .map(|package| package.name)
+        .collect();
+
+    assert_eq!(providers, vec!["vim", "nano"]);
+    assert_eq!(database.packages_providing("ed").len(), 1);
+    assert!(database.packages_providing("emacs").is_empty());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn virtual_dependencies_select_a_provider() {
+    let directory = scratch_directory("providers-dependency");
+    let mut database = empty_database(&directory);
+    database.synchronize(&editors()).unwrap();
+
+    let selections = mix::selection::install(&["writer"], &database).unwrap();
+    let names: Vec<String> = selections
+        .install
+        .iter()
+        .map(|package| package.borrow().name.clone())
+        .collect();
+    assert_eq!(names, vec!["vim", "writer"]);
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::{empty_database, scratch_directory};
+use mix::{database::backup_path, Database, Package, Recovery, Selections, SharedPackage, Version};
+use std::fs;
+
+#[test]
+fn corrupt_database_is_restored_from_backup() {
+    let directory = scratch_directory("recover");
+    let mut database = empty_database(&directory);
+    let path = directory.join("mix.db");
+    let mut selections = Selections::default();
+    selections.install.push(SharedPackage::new(Package::new(
+        "foo",
+        Version::SemVer(1, 0, 0),
+    )));
+    database.apply(selections).unwrap();
+    // The second save backs up the first.
+    database.save(&path).unwrap();
+    database.save(&path).unwrap();
+    assert!(backup_path(&path).exists());
+
+    fs::write(&path, b"not a database").unwrap();
+    assert!(Database::load(&path).is_err());
+    let restored = Database::load_or_recover(&path, |_, backup| {
+        assert_eq!(backup, Some(backup_path(&path).as_path()));
+        Recovery::Restore
+    })
+    .unwrap();
+    assert!(restored.install_reason("foo").is_ok());
+    // The restored backup replaces the corrupt database.
+    assert!(Database::load(&path).is_ok());
+
+    fs::write(&path, b"not a database").unwrap();
+    assert!(Database::load_or_recover(&path, |_, _| Recovery::Abort).is_err());
+    let fresh = Database::load_or_recover(&path, |_, _| Recovery::StartFresh).unwrap();
+    assert!(fresh.install_reason("foo").is_err());
+    assert_eq!(
+        fs::read(directory.join("mix.db.corrupt")).unwrap(),
+        b"not a database"
+    );
+    // Saving over the fresh database keeps the good backup.
+    fresh.save(&path).unwrap();
+    assert!(Database::load(backup_path(&path))
+        .unwrap()
+        .install_reason("foo")
+        .is_ok());
+    fs::remove_dir_all(&directory).unwrap();
+}
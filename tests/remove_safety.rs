@@ -0,0 +1,66 @@
+mod common;
+
+use common::{empty_database, scratch_directory, synthetic_packages};
+use mix::{Error, Package, Selections, SharedPackage, Version};
+use std::fs;
+
+#[test]
+fn removing_a_dependency_needs_its_dependents() {
+    let directory = scratch_directory("remove-safety");
+    let mut database = empty_database(&directory);
+    // Each package depends on the one before it.
+    let mut selections = Selections::default();
+    for mut package in synthetic_packages(3) {
+        package.files.clear();
+        selections.install.push(SharedPackage::new(package));
+    }
+    database.apply(selections).unwrap();
+
+    let (error, blocking) = mix::selection::remove(&["package-0"], &database).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::RequiredBy { package, dependents }
+            if package == "package-0" && dependents == vec!["package-1"]
+    ));
+    assert_eq!(blocking.len(), 1);
+
+    let selections =
+        mix::selection::remove(&["package-0", "package-1", "package-2"], &database).unwrap();
+    assert_eq!(selections.remove.len(), 3);
+    let selections = mix::selection::remove(&["package-2"], &database).unwrap();
+    assert_eq!(selections.remove.len(), 1);
+    let selections = mix::selection::remove_cascade(&["package-0"], &database).unwrap();
+    assert_eq!(selections.remove.len(), 3);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn removing_a_provider_needs_another_provider() {
+    let directory = scratch_directory("remove-safety-providers");
+    let mut database = empty_database(&directory);
+    let package = |name: &str, dependencies: &[&str], provides: &[&str]| {
+        let mut package = Package::new(name, Version::SemVer(1, 0, 0));
+        package.dependencies = dependencies.iter().map(|name| name.to_string()).collect();
+        package.provides = provides.iter().map(|name| name.to_string()).collect();
+        SharedPackage::new(package)
+    };
+    let mut selections = Selections::default();
+    selections.install.push(package("vim", &[], &["editor"]));
+    selections.install.push(package("writer", &["editor"], &[]));
+    database.apply(selections).unwrap();
+
+    let (error, _) = mix::selection::remove(&["vim"], &database).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::RequiredBy { package, dependents }
+            if package == "vim" && dependents == vec!["writer"]
+    ));
+
+    // With nano providing the editor too, either one can go, but not both.
+    let mut selections = Selections::default();
+    selections.install.push(package("nano", &[], &["editor"]));
+    database.apply(selections).unwrap();
+    assert!(mix::selection::remove(&["vim"], &database).is_ok());
+    assert!(mix::selection::remove(&["vim", "nano"], &database).is_err());
+    fs::remove_dir_all(&directory).unwrap();
+}
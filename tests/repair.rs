@@ -0,0 +1,71 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{history::Action, Database, Selections, SharedPackage};
+use std::fs;
+
+#[test]
+fn repair_restores_only_missing_files() {
+    let directory = scratch_directory("repair");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"foo\"",
+        &["etc/", "etc/foo.conf", "usr/", "usr/bin/", "usr/bin/foo"],
+    );
+    let mut selections = Selections::default();
+    selections
+        .install
+        .push(SharedPackage::new(load_package(&tarball)));
+    database.apply(selections).unwrap();
+
+    fs::write(root.join("etc/foo.conf"), "modified").unwrap();
+    fs::remove_dir_all(root.join("usr/bin")).unwrap();
+    assert_eq!(database.missing_files("foo").unwrap().len(), 2);
+
+    let repaired = database.repair("foo").unwrap();
+    assert_eq!(repaired.len(), 2);
+    assert!(database.missing_files("foo").unwrap().is_empty());
+    assert_eq!(
+        fs::read_to_string(root.join("usr/bin/foo")).unwrap(),
+        "usr/bin/foo"
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("etc/foo.conf")).unwrap(),
+        "modified"
+    );
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn repairs_are_recorded_in_the_history() {
+    let directory = scratch_directory("repair-history");
+    let database = empty_database(&directory);
+    let config = database.config().clone();
+    let path = directory.join("mix.db");
+    database.save(&path).unwrap();
+    let mut database = Database::load(&path).unwrap();
+    database.set_config(config.clone());
+    database.set_package_cache(directory.join("cache"));
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let mut selections = Selections::default();
+    selections
+        .install
+        .push(SharedPackage::new(load_package(&tarball)));
+    database.apply(selections).unwrap();
+
+    fs::remove_file(config.root.join("usr/foo")).unwrap();
+    let selections = database.compute_repairs();
+    assert_eq!(selections.repair.len(), 1);
+    database.apply(selections).unwrap();
+
+    assert!(config.root.join("usr/foo").exists());
+    let history = mix::history::read(&mix::history::log_path(&path)).unwrap();
+    let change = &history.last().unwrap().changes[0];
+    assert_eq!(change.action, Action::Repair);
+    assert_eq!(change.name, "foo");
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{Error, InstallState, Selections, SharedPackage};
+use std::fs;
+
+#[test]
+fn failed_installs_are_rolled_back() {
+    let directory = scratch_directory("rollback");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"foo\"",
+        &[
+            "opt/",
+            "opt/foo/",
+            "opt/foo/bin",
+            "opt/foo/lib -> bin",
+            "usr/",
+            "usr/foo",
+            "usr/zzz",
+        ],
+    );
+    // A file that's already there makes the install fail near the end.
+    fs::create_dir(root.join("usr")).unwrap();
+    fs::write(root.join("usr/zzz"), "mine").unwrap();
+
+    let package = SharedPackage::new(load_package(&tarball));
+    let mut selections = Selections::default();
+    selections.install.push(package.clone());
+    assert!(database.apply(selections).is_err());
+
+    assert!(!root.join("opt").exists());
+    assert!(!root.join("usr/foo").exists());
+    assert_eq!(fs::read_to_string(root.join("usr/zzz")).unwrap(), "mine");
+    assert_eq!(package.borrow().state, InstallState::Uninstalled);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn unsupported_entries_are_rolled_back() {
+    let directory = scratch_directory("rollback-unsupported");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"foo\"",
+        &["usr/", "usr/foo", "usr/pipe|"],
+    );
+
+    let package = SharedPackage::new(load_package(&tarball));
+    let mut selections = Selections::default();
+    selections.install.push(package.clone());
+    assert!(matches!(
+        database.apply(selections),
+        Err(Error::UnsupportedEntry { .. })
+    ));
+
+    assert!(!root.join("usr").exists());
+    assert_eq!(package.borrow().state, InstallState::Uninstalled);
+    fs::remove_dir_all(&directory).unwrap();
+}
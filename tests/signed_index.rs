@@ -0,0 +1,103 @@
+mod common;
+
+use common::{empty_database, generate_key, gpg, scratch_directory};
+use mix::{config::RepositoryConfig, Config, Database, Error};
+use std::{fs, path::Path};
+
+const INDEX: &str = "[[packages]]\nname = \"foo\"\nversion = \"1.0.0\"\n";
+
+/// Provide a database that trusts the key of a directory repository with a
+/// signed index, along with the repository.
+fn signed_repository(directory: &Path) -> (Database, Vec<Box<dyn mix::Repository>>) {
+    let key = generate_key(directory);
+    let repository = directory.join("repository");
+    fs::create_dir(&repository).unwrap();
+    let index = repository.join("index.toml");
+    fs::write(&index, INDEX).unwrap();
+    gpg(directory, &["--detach-sign", index.to_str().unwrap()]);
+
+    let mut database = empty_database(directory);
+    let mirror = format!("file://{}", repository.display());
+    database.set_config(Config {
+        repositories: vec![RepositoryConfig {
+            name: String::from("local"),
+            mirrors: vec![mirror.clone()],
+        }],
+        signing_keys: vec![(mirror, key)].into_iter().collect(),
+        allow_unsigned: false,
+        ..database.config().clone()
+    });
+    let repositories = database.repositories();
+    (database, repositories)
+}
+
+#[test]
+fn signed_index_is_trusted() {
+    let directory = scratch_directory("signed-index");
+    let (mut database, repositories) = signed_repository(&directory);
+
+    database.synchronize(repositories[0].as_ref()).unwrap();
+
+    assert!(database.info("foo").is_ok());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn tampered_index_is_rejected() {
+    let directory = scratch_directory("tampered-index");
+    let (mut database, repositories) = signed_repository(&directory);
+    fs::write(
+        directory.join("repository/index.toml"),
+        format!("{}description = \"Not what was signed\"\n", INDEX),
+    )
+    .unwrap();
+
+    let result = database.synchronize(repositories[0].as_ref());
+
+    assert!(matches!(
+        result,
+        Err(Error::SignatureVerificationFailed { .. })
+    ));
+    assert!(database.info("foo").is_err());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn unsigned_index_is_rejected() {
+    let directory = scratch_directory("unsigned-index");
+    let (mut database, repositories) = signed_repository(&directory);
+    fs::remove_file(directory.join("repository/index.toml.sig")).unwrap();
+
+    let result = database.synchronize(repositories[0].as_ref());
+
+    assert!(matches!(
+        result,
+        Err(Error::SignatureVerificationFailed { .. })
+    ));
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn repository_without_key_needs_opt_in() {
+    let directory = scratch_directory("unsigned-opt-in");
+    let (mut database, repositories) = signed_repository(&directory);
+    let config = Config {
+        signing_keys: Default::default(),
+        ..database.config().clone()
+    };
+    database.set_config(config.clone());
+
+    let result = database.synchronize(repositories[0].as_ref());
+    assert!(matches!(
+        result,
+        Err(Error::SignatureVerificationFailed { .. })
+    ));
+
+    database.set_config(Config {
+        allow_unsigned: true,
+        ..config
+    });
+    database.synchronize(repositories[0].as_ref()).unwrap();
+    assert!(database.info("foo").is_ok());
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use common::{empty_database, generate_key, gpg, scratch_directory, write_tarball};
+use mix::{config::RepositoryConfig, Config, Database, Error};
+use std::{fs, path::Path};
+
+/// Provide a database verifying the packages of a signed directory
+/// repository, holding a signed tarball of `foo`.
+fn signed_packages(directory: &Path) -> Database {
+    let key = generate_key(directory);
+    let repository = directory.join("repository");
+    fs::create_dir(&repository).unwrap();
+    let tarball = repository.join("foo-1.0.0.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let index = repository.join("index.toml");
+    fs::write(
+        &index,
+        "[[packages]]\nname = \"foo\"\nversion = \"1.0.0\"\nfiles = [\"usr\", \"usr/foo\"]\n",
+    )
+    .unwrap();
+    for file in [&tarball, &index] {
+        gpg(directory, &["--detach-sign", file.to_str().unwrap()]);
+    }
+
+    let mut database = empty_database(directory);
+    let mirror = repository.display().to_string();
+    database.set_config(Config {
+        repositories: vec![RepositoryConfig {
+            name: String::from("local"),
+            mirrors: vec![mirror.clone()],
+        }],
+        signing_keys: vec![(mirror, key)].into_iter().collect(),
+        allow_unsigned: false,
+        verify_packages: true,
+        ..database.config().clone()
+    });
+    for repository in database.repositories() {
+        database.synchronize(repository.as_ref()).unwrap();
+    }
+    database
+}
+
+#[test]
+fn signed_package_is_installed() {
+    let directory = scratch_directory("signed-package");
+    let mut database = signed_packages(&directory);
+    let root = database.config().root.clone();
+
+    let selections = mix::selection::install(&["foo"], &database)
+        .map_err(|(error, _)| error)
+        .unwrap();
+    database.apply(selections).unwrap();
+
+    assert!(root.join("usr/foo").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn tampered_package_places_no_files() {
+    let directory = scratch_directory("tampered-package");
+    let mut database = signed_packages(&directory);
+    let root = database.config().root.clone();
+    write_tarball(
+        &directory.join("repository/foo-1.0.0.tar.xz"),
+        "name = \"foo\"",
+        &["usr/", "usr/foo", "usr/extra"],
+    );
+
+    let selections = mix::selection::install(&["foo"], &database)
+        .map_err(|(error, _)| error)
+        .unwrap();
+    let result = database.apply(selections);
+
+    assert!(matches!(
+        result,
+        Err(Error::SignatureVerificationFailed { .. })
+    ));
+    assert!(!root.join("usr").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn unsigned_package_is_rejected() {
+    let directory = scratch_directory("unsigned-package");
+    let mut database = signed_packages(&directory);
+    fs::remove_file(directory.join("repository/foo-1.0.0.tar.xz.sig")).unwrap();
+
+    let selections = mix::selection::install(&["foo"], &database)
+        .map_err(|(error, _)| error)
+        .unwrap();
+    assert!(matches!(
+        database.apply(selections),
+        Err(Error::SignatureVerificationFailed { .. })
+    ));
+    fs::remove_dir_all(&directory).unwrap();
+}
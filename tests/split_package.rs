@@ -0,0 +1,61 @@
+mod common;
+
+use common::{empty_database, scratch_directory, write_tarball};
+use mix::{Package, Selections, SharedPackage};
+use std::{
+    fs::{self, File},
+    path::PathBuf,
+};
+
+#[test]
+fn sub_packages_only_place_their_own_files() {
+    let directory = scratch_directory("split-package");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("foo.tar.xz");
+    write_tarball(
+        &tarball,
+        "description = \"Foo\"\n\
+         [[packages]]\nname = \"foo\"\n\
+         [[packages]]\nname = \"foo-docs\"\nfiles = [\"usr/share/doc/*\"]\ndependencies = [\"foo\"]",
+        &[
+            "usr/",
+            "usr/bin/",
+            "usr/bin/foo",
+            "usr/share/",
+            "usr/share/doc/",
+            "usr/share/doc/foo.txt",
+        ],
+    );
+
+    let packages = Package::all_from_tarball(File::open(&tarball).unwrap()).unwrap();
+    assert_eq!(packages.len(), 2);
+    let (foo, docs) = (&packages[0], &packages[1]);
+    assert_eq!(foo.name, "foo");
+    assert_eq!(docs.name, "foo-docs");
+    assert_eq!(docs.description.as_deref(), Some("Foo"));
+    assert_eq!(docs.dependencies, vec![String::from("foo")]);
+    assert!(foo.files.contains(&PathBuf::from("usr/bin/foo")));
+    assert!(!foo.files.contains(&PathBuf::from("usr/share/doc/foo.txt")));
+    assert_eq!(
+        docs.files,
+        [
+            "usr/",
+            "usr/share/",
+            "usr/share/doc/",
+            "usr/share/doc/foo.txt"
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>()
+    );
+
+    let mut docs = docs.clone();
+    docs.local_path = Some(tarball.clone());
+    let mut selections = Selections::default();
+    selections.install.push(SharedPackage::new(docs));
+    database.apply(selections).unwrap();
+    assert!(root.join("usr/share/doc/foo.txt").exists());
+    assert!(!root.join("usr/bin/foo").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
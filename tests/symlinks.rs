@@ -0,0 +1,81 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{Selections, SharedPackage};
+use std::{fs, os::unix::fs::symlink, path::Path};
+
+#[test]
+fn symlinks_are_placed_and_removed() {
+    let directory = scratch_directory("symlinks");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("libfoo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"libfoo\"",
+        &[
+            "usr/",
+            "usr/lib/",
+            "usr/lib/libfoo.so.1",
+            "usr/lib/libfoo.so -> libfoo.so.1",
+            "usr/lib64 -> lib",
+        ],
+    );
+    // A link left behind by an older version is replaced.
+    fs::create_dir_all(root.join("usr/lib")).unwrap();
+    symlink("libfoo.so.0", root.join("usr/lib/libfoo.so")).unwrap();
+
+    let package = SharedPackage::new(load_package(&tarball));
+    let mut selections = Selections::default();
+    selections.install.push(package.clone());
+    database.apply(selections).unwrap();
+
+    assert_eq!(
+        fs::read_link(root.join("usr/lib/libfoo.so")).unwrap(),
+        Path::new("libfoo.so.1")
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("usr/lib64/libfoo.so")).unwrap(),
+        "usr/lib/libfoo.so.1"
+    );
+    assert!(database.audit("libfoo").unwrap().is_empty());
+
+    let mut selections = Selections::default();
+    selections.remove.push(package);
+    database.apply(selections).unwrap();
+    assert!(!root.join("usr").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn symlinks_leave_other_files_in_their_way() {
+    let directory = scratch_directory("symlinks-in-the-way");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("libfoo.tar.xz");
+    write_tarball(
+        &tarball,
+        "name = \"libfoo\"",
+        &[
+            "usr/",
+            "usr/lib/",
+            "usr/lib/libfoo.so.1",
+            "usr/lib/libfoo.so -> libfoo.so.1",
+        ],
+    );
+    fs::create_dir_all(root.join("usr/lib")).unwrap();
+    fs::write(root.join("usr/lib/libfoo.so"), "mine").unwrap();
+
+    let mut selections = Selections::default();
+    selections
+        .install
+        .push(SharedPackage::new(load_package(&tarball)));
+    assert!(database.apply(selections).is_err());
+
+    assert_eq!(
+        fs::read_to_string(root.join("usr/lib/libfoo.so")).unwrap(),
+        "mine"
+    );
+    assert!(!root.join("usr/lib/libfoo.so.1").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{Error, Selections, SharedPackage};
+use std::{
+    fs::{self, File},
+    os::unix::fs::symlink,
+    path::Path,
+};
+use xz2::write::XzEncoder;
+
+/// Write a package tarball with a file at the given path, which the tar
+/// crate would otherwise refuse to write.
+fn write_crafted_tarball(path: &Path, manifest: &str, file: &str) {
+    let mut builder = tar::Builder::new(XzEncoder::new(File::create(path).unwrap(), 6));
+    let mut header = tar::Header::new_gnu();
+    header.set_mode(0o644);
+    header.set_size(manifest.len() as u64);
+    header.set_path(".MANIFEST").unwrap();
+    header.set_cksum();
+    builder.append(&header, manifest.as_bytes()).unwrap();
+    let mut header = tar::Header::new_gnu();
+    header.set_mode(0o644);
+    header.set_size(file.len() as u64);
+    header.as_gnu_mut().unwrap().name[..file.len()].copy_from_slice(file.as_bytes());
+    header.set_cksum();
+    builder.append(&header, file.as_bytes()).unwrap();
+    builder.into_inner().unwrap().finish().unwrap();
+}
+
+fn install(directory: &Path, tarball: &Path) -> mix::Result<()> {
+    let mut database = empty_database(directory);
+    let mut selections = Selections::default();
+    selections
+        .install
+        .push(SharedPackage::new(load_package(tarball)));
+    database.apply(selections)
+}
+
+#[test]
+fn parent_directories_are_refused() {
+    let directory = scratch_directory("unsafe-path-parent");
+    let tarball = directory.join("evil.tar.xz");
+    write_crafted_tarball(&tarball, "name = \"evil\"", "../../etc/passwd");
+    match install(&directory, &tarball) {
+        Err(Error::UnsafePath(path)) => assert_eq!(path, Path::new("../../etc/passwd")),
+        result => panic!("expected an unsafe path, got {:?}", result),
+    }
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn absolute_paths_are_refused() {
+    let directory = scratch_directory("unsafe-path-absolute");
+    let tarball = directory.join("evil.tar.xz");
+    let target = directory.join("outside");
+    write_crafted_tarball(&tarball, "name = \"evil\"", target.to_str().unwrap());
+    assert!(matches!(
+        install(&directory, &tarball),
+        Err(Error::UnsafePath(_))
+    ));
+    assert!(!target.exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn symlinks_out_of_the_root_are_refused() {
+    let directory = scratch_directory("unsafe-path-symlink");
+    let outside = directory.join("outside");
+    fs::create_dir(&outside).unwrap();
+    fs::create_dir_all(directory.join("root")).unwrap();
+    symlink(&outside, directory.join("root/escape")).unwrap();
+    let tarball = directory.join("evil.tar.xz");
+    write_tarball(&tarball, "name = \"evil\"", &["escape/file"]);
+    assert!(matches!(
+        install(&directory, &tarball),
+        Err(Error::UnsafePath(_))
+    ));
+    assert!(!outside.join("file").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{empty_database, load_package, scratch_directory, write_tarball};
+use mix::{InstallState, Selections, SharedPackage, Version};
+use std::fs;
+
+#[test]
+fn upgrade_keeps_install_state() {
+    let directory = scratch_directory("upgrade");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let old_tarball = directory.join("foo-1.tar.xz");
+    write_tarball(
+        &old_tarball,
+        "name = \"foo\"",
+        &["usr/", "usr/old", "usr/shared"],
+    );
+    let new_tarball = directory.join("foo-2.tar.xz");
+    write_tarball(
+        &new_tarball,
+        "name = \"foo\"",
+        &["usr/", "usr/new", "usr/shared"],
+    );
+
+    let mut old = load_package(&old_tarball);
+    old.version = Version::SemVer(1, 0, 0);
+    old.mark_as_manually_installed();
+    let old = SharedPackage::new(old);
+    let mut selections = Selections::default();
+    selections.install.push(old.clone());
+    database.apply(selections).unwrap();
+    assert_eq!(old.borrow().state, InstallState::Manual);
+
+    let mut new = load_package(&new_tarball);
+    new.version = Version::SemVer(2, 0, 0);
+    let new = SharedPackage::new(new);
+    let mut selections = Selections::default();
+    selections.upgrade.push(new.clone());
+    database.apply(selections).unwrap();
+
+    assert_eq!(new.borrow().state, InstallState::Manual);
+    assert_eq!(old.borrow().state, InstallState::Uninstalled);
+    assert!(!root.join("usr/old").exists());
+    assert!(root.join("usr/new").exists());
+    assert!(root.join("usr/shared").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn upgrade_skips_current_versions() {
+    let directory = scratch_directory("upgrade-current");
+    let mut database = empty_database(&directory);
+    let root = database.config().root.clone();
+    let tarball = directory.join("foo-1.tar.xz");
+    write_tarball(&tarball, "name = \"foo\"", &["usr/", "usr/foo"]);
+    let mut installed = load_package(&tarball);
+    installed.version = Version::SemVer(1, 0, 0);
+    let mut selections = Selections::default();
+    selections.install.push(SharedPackage::new(installed));
+    database.apply(selections).unwrap();
+
+    // The same version from another tarball isn't installed over the first.
+    let rebuilt_tarball = directory.join("foo-1-rebuilt.tar.xz");
+    write_tarball(&rebuilt_tarball, "name = \"foo\"", &["usr/", "usr/rebuilt"]);
+    let mut rebuilt = load_package(&rebuilt_tarball);
+    rebuilt.version = Version::SemVer(1, 0, 0);
+    let rebuilt = SharedPackage::new(rebuilt);
+    let mut selections = Selections::default();
+    selections.upgrade.push(rebuilt.clone());
+    let skipped = mix::selection::skip_up_to_date(&mut selections, &database);
+    assert!(selections.upgrade.is_empty());
+    assert!(skipped
+        .iter()
+        .any(|package| SharedPackage::ptr_eq(package, &rebuilt)));
+    let mut selections = Selections::default();
+    selections.upgrade.push(rebuilt.clone());
+    database.apply(selections).unwrap();
+
+    assert_eq!(rebuilt.borrow().state, InstallState::Uninstalled);
+    assert!(root.join("usr/foo").exists());
+    assert!(!root.join("usr/rebuilt").exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::{empty_database, listed_package, scratch_directory, ListedRepository};
+use mix::{repository::DirectoryRepository, Package, Version};
+use std::{collections::BTreeMap, fs};
+
+/// A repository providing whichever packages it was given, at version 1.0.0.
+fn listing(names: &[&str]) -> ListedRepository {
+    let packages = names.iter().map(|name| listed_package(name, &[])).collect();
+    ListedRepository::new("listing", packages)
+}
+
+#[test]
+fn packages_leaving_the_repository_are_withdrawn() {
+    let directory = scratch_directory("withdrawn");
+    let mut database = empty_database(&directory);
+    database
+        .synchronize(&listing(&["foo", "bar", "baz"]))
+        .unwrap();
+    let selections = mix::selection::install(&["foo"], &database)
+        .map_err(|(error, _)| error)
+        .unwrap();
+    database.apply(selections).unwrap();
+    assert!(database.foreign().is_empty());
+
+    database.synchronize(&listing(&["baz"])).unwrap();
+
+    // The installed package is kept, but the other one is forgotten.
+    let foo = database.info("foo").unwrap();
+    assert_eq!(foo.repository.as_deref(), Some("listing"));
+    assert!(database.info("bar").is_err());
+    let foreign: Vec<String> = database
+        .foreign()
+        .into_iter()
+        .map(|package| package.name)
+        .collect();
+    assert_eq!(foreign, vec!["foo"]);
+    assert!(database
+        .all_packages()
+        .iter()
+        .any(|package| package.name == "foo" && package.withdrawn));
+
+    // Coming back to the repository undoes the withdrawal.
+    database.synchronize(&listing(&["foo", "baz"])).unwrap();
+    assert!(database.foreign().is_empty());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn outdated_packages_are_not_withdrawn() {
+    let directory = scratch_directory("withdrawn-outdated");
+    let mut database = empty_database(&directory);
+    database.synchronize(&listing(&["foo"])).unwrap();
+    let selections = mix::selection::install(&["foo"], &database)
+        .map_err(|(error, _)| error)
+        .unwrap();
+    database.apply(selections).unwrap();
+
+    let foo = Package::new("foo", Version::SemVer(1, 1, 0));
+    database
+        .synchronize(&ListedRepository::new("listing", vec![foo]))
+        .unwrap();
+
+    assert!(database.foreign().is_empty());
+    assert!(database
+        .all_packages()
+        .iter()
+        .all(|package| !package.withdrawn));
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn indexes_can_be_cbor() {
+    let directory = scratch_directory("cbor-index");
+    let mut database = empty_database(&directory);
+    let repository = directory.join("repository");
+    fs::create_dir(&repository).unwrap();
+    let mut index = BTreeMap::new();
+    index.insert(
+        "packages",
+        vec![Package::new("foo", Version::SemVer(1, 0, 0))],
+    );
+    fs::write(
+        repository.join("index.toml"),
+        serde_cbor::to_vec(&index).unwrap(),
+    )
+    .unwrap();
+
+    database
+        .synchronize(&DirectoryRepository::new("local", &repository))
+        .unwrap();
+    assert_eq!(
+        database.info("foo").unwrap().version,
+        Version::SemVer(1, 0, 0)
+    );
+    fs::remove_dir_all(&directory).unwrap();
+}